@@ -0,0 +1,156 @@
+//! The smallest useful `Simulation`: circles under gravity bouncing off the walls of the window
+//! and off each other, with no broad phase and no continuous collision detection.
+//!
+//! Shows the required trait methods and a boundary condition from `engine::boundary`. Click a
+//! circle to kick it upwards.
+//!
+//! ```sh
+//! cargo run -p engine --example bouncing -- 40
+//! ```
+
+use engine::{
+    Bounds, Simulation, SimulationConfig, StepResult,
+    boundary::{BoundaryCondition, ReflectiveBox},
+    color::hsl_to_rgb,
+    context::Context,
+    particle::Particle,
+};
+use glam::Vec2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+const DEFAULT_COUNT: usize = 30;
+const GRAVITY: Vec2 = Vec2::new(0.0, -400.0);
+const KICK: f32 = 500.0;
+
+struct Bouncing {
+    count: usize,
+    particles: Vec<Particle>,
+    bounds: Bounds,
+}
+
+impl Bouncing {
+    /// Elastic response for every overlapping pair that is still approaching.
+    fn collide_pairs(&mut self) {
+        for i in 0..self.particles.len() {
+            let (head, tail) = self.particles.split_at_mut(i + 1);
+            let a = &mut head[i];
+
+            for b in tail {
+                let n = b.position - a.position;
+                let dist = n.length();
+
+                if dist == 0.0 || dist > a.radius + b.radius {
+                    continue;
+                }
+
+                let n = n / dist;
+                let approach = (a.velocity - b.velocity).dot(n);
+
+                if approach <= 0.0 {
+                    continue;
+                }
+
+                let impulse = 2.0 * approach / (1.0 / a.mass + 1.0 / b.mass);
+
+                a.velocity -= n * impulse / a.mass;
+                b.velocity += n * impulse / b.mass;
+            }
+        }
+    }
+}
+
+impl Simulation for Bouncing {
+    fn init(&mut self, _ctx: &mut Context, bounds: Bounds) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (hw, hh) = bounds.half_extents();
+
+        self.bounds = bounds;
+        self.particles = (0..self.count)
+            .map(|i| {
+                let radius = rng.random_range(8.0..24.0);
+
+                Particle::new(
+                    Vec2::new(
+                        rng.random_range(-hw + radius..hw - radius),
+                        rng.random_range(-hh + radius..hh - radius),
+                    ),
+                    Vec2::new(rng.random_range(-200.0..200.0), 0.0),
+                    radius,
+                    radius * radius,
+                    hsl_to_rgb(i as f32 * 360.0 / self.count as f32, 0.7, 0.6),
+                )
+            })
+            .collect();
+    }
+
+    // `bounds` follows the window, so resizing it moves the walls.
+    fn step(&mut self, _ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult {
+        self.bounds = bounds;
+
+        for p in &mut self.particles {
+            p.velocity += GRAVITY * dt;
+            p.position += p.velocity * dt;
+
+            if !ReflectiveBox.contains(p, &bounds) {
+                ReflectiveBox.resolve(p, &bounds);
+                ReflectiveBox.clamp(p, &bounds);
+            }
+        }
+
+        self.collide_pairs();
+        StepResult::Continue
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn clicked(&mut self, world: Vec2) {
+        if let Some(p) = self
+            .particles
+            .iter_mut()
+            .find(|p| p.position.distance(world) <= p.radius)
+        {
+            p.velocity.y += KICK;
+        }
+    }
+
+    fn hud(&self) -> Vec<String> {
+        let energy = self
+            .particles
+            .iter()
+            .map(|p| {
+                0.5 * p.mass * p.velocity.length_squared()
+                    - p.mass * GRAVITY.dot(p.position + Vec2::Y * self.bounds.height / 2.0)
+            })
+            .sum::<f32>();
+
+        vec![format!("Energy {energy:.3e}")]
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let count = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => DEFAULT_COUNT,
+    };
+
+    engine::run_with(
+        Bouncing {
+            count,
+            particles: Vec::new(),
+            bounds: Bounds {
+                width: 0.0,
+                height: 0.0,
+            },
+        },
+        SimulationConfig::builder()
+            .title("Bouncing circles")
+            .msaa_samples(4)
+            .build()?,
+    )?;
+
+    Ok(())
+}
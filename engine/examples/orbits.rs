@@ -0,0 +1,192 @@
+//! Points on circular orbits around a central body, drawn with trails. Nothing here collides;
+//! the example covers the optional hooks instead: debug lines on layers, world-space labels,
+//! a camera focus, keyboard input and `quiescent` idling.
+//!
+//! Space pauses as usual, T toggles the trails and L makes the outermost planet the focus, which
+//! the engine's F centers the camera on.
+//!
+//! ```sh
+//! cargo run -p engine --example orbits
+//! ```
+
+use std::collections::VecDeque;
+
+use engine::{
+    Bounds, Simulation, SimulationConfig, StepResult, context::Context, egui, line::Line,
+    particle::Particle,
+};
+use glam::Vec2;
+use winit::keyboard::KeyCode;
+
+const TRAIL_LEN: usize = 90;
+/// Gravitational parameter of the central body, in world units³/s².
+const MU: f32 = 4.0e7;
+
+struct Planet {
+    name: &'static str,
+    radius: f32,
+    phase: f32,
+}
+
+struct Orbits {
+    planets: Vec<Planet>,
+    particles: Vec<Particle>,
+    trails: Vec<VecDeque<Vec2>>,
+    time: f32,
+    show_trails: bool,
+    follow: bool,
+    /// Frozen in time, so nothing moves until unfrozen.
+    frozen: bool,
+}
+
+impl Orbits {
+    fn new() -> Self {
+        let planets = vec![
+            Planet {
+                name: "inner",
+                radius: 80.0,
+                phase: 0.0,
+            },
+            Planet {
+                name: "middle",
+                radius: 150.0,
+                phase: 2.0,
+            },
+            Planet {
+                name: "outer",
+                radius: 240.0,
+                phase: 4.0,
+            },
+        ];
+        let mut particles = vec![Particle::new(
+            Vec2::ZERO,
+            Vec2::ZERO,
+            20.0,
+            1.0,
+            [1.0, 0.85, 0.3],
+        )];
+
+        particles.extend(
+            planets
+                .iter()
+                .map(|_| Particle::new(Vec2::ZERO, Vec2::ZERO, 6.0, 1.0, [0.4, 0.7, 1.0])),
+        );
+
+        Self {
+            trails: vec![VecDeque::new(); planets.len()],
+            planets,
+            particles,
+            time: 0.0,
+            show_trails: true,
+            follow: false,
+            frozen: false,
+        }
+    }
+
+    fn place(&mut self) {
+        for (k, planet) in self.planets.iter().enumerate() {
+            // Kepler's third law for a circular orbit.
+            let omega = (MU / planet.radius.powi(3)).sqrt();
+            let angle = planet.phase + omega * self.time;
+            let p = &mut self.particles[k + 1];
+
+            p.position = Vec2::from_angle(angle) * planet.radius;
+            p.velocity = Vec2::from_angle(angle).perp() * omega * planet.radius;
+
+            let trail = &mut self.trails[k];
+
+            trail.push_back(p.position);
+
+            if trail.len() > TRAIL_LEN {
+                trail.pop_front();
+            }
+        }
+    }
+}
+
+impl Simulation for Orbits {
+    fn init(&mut self, _ctx: &mut Context, _bounds: Bounds) {
+        self.place();
+    }
+
+    fn step(&mut self, _ctx: &mut Context, dt: f32, _bounds: Bounds) -> StepResult {
+        if self.frozen {
+            return StepResult::Continue;
+        }
+
+        self.time += dt;
+        self.place();
+        StepResult::Continue
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.show_trails, "Trails (T)");
+        ui.checkbox(&mut self.follow, "Focus outer planet (L)");
+        ui.checkbox(&mut self.frozen, "Freeze");
+    }
+
+    fn hud(&self) -> Vec<String> {
+        vec![format!("t = {:.1} s", self.time)]
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::KeyT => self.show_trails = !self.show_trails,
+            KeyCode::KeyL => self.follow = !self.follow,
+            _ => {}
+        }
+    }
+
+    fn focus(&self) -> Option<Vec2> {
+        self.follow
+            .then(|| self.particles.last().map(|p| p.position))
+            .flatten()
+    }
+
+    // Trails sit under the planets; a negative layer draws before the particles.
+    fn debug_lines(&self) -> Vec<Line> {
+        if !self.show_trails {
+            return Vec::new();
+        }
+
+        self.trails
+            .iter()
+            .flat_map(|trail| {
+                trail
+                    .iter()
+                    .zip(trail.iter().skip(1))
+                    .map(|(&a, &b)| Line::new(a, b, [0.25, 0.35, 0.5]).with_layer(-1))
+            })
+            .collect()
+    }
+
+    fn labels(&self) -> Vec<(Vec2, String)> {
+        self.planets
+            .iter()
+            .zip(&self.particles[1..])
+            .map(|(planet, p)| (p.position, planet.name.to_string()))
+            .collect()
+    }
+
+    fn quiescent(&self) -> bool {
+        self.frozen
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    engine::run_with(
+        Orbits::new(),
+        SimulationConfig::builder()
+            .title("Orbits")
+            .msaa_samples(4)
+            .build()?,
+    )?;
+
+    Ok(())
+}
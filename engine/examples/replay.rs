@@ -0,0 +1,179 @@
+//! Skeleton of a replay viewer: plays back a recorded particle snapshot CSV (the
+//! `particles_*.csv` files written by the simulator) instead of simulating anything.
+//!
+//! Playback follows the engine clock, so pause, single-step and time scale work unchanged;
+//! Left/Right seek by one recorded frame and the view idles once the last frame is reached.
+//! A real viewer would add colors, events and overlays on top of this.
+//!
+//! ```sh
+//! cargo run -p engine --example replay -- particles_tccd_500.csv
+//! ```
+
+use std::{fs, path::Path};
+
+use anyhow::{Context as _, bail};
+use engine::{
+    Bounds, Simulation, SimulationConfig, StepResult, context::Context, egui, particle::Particle,
+};
+use glam::Vec2;
+use winit::keyboard::KeyCode;
+
+/// One recorded frame: its time and every particle in it.
+struct Frame {
+    time_s: f32,
+    particles: Vec<Particle>,
+}
+
+struct Replay {
+    frames: Vec<Frame>,
+    current: usize,
+    /// Playback time, kept separately from frame times so time scale and fixed dt still apply.
+    time: f32,
+}
+
+impl Replay {
+    fn seek(&mut self, frame: usize) {
+        self.current = frame.min(self.frames.len() - 1);
+        self.time = self.frames[self.current].time_s;
+    }
+
+    fn at_end(&self) -> bool {
+        self.current + 1 == self.frames.len()
+    }
+}
+
+impl Simulation for Replay {
+    fn init(&mut self, _ctx: &mut Context, _bounds: Bounds) {
+        self.seek(0);
+    }
+
+    // Shows the last frame recorded at or before the playback time.
+    fn step(&mut self, _ctx: &mut Context, dt: f32, _bounds: Bounds) -> StepResult {
+        self.time += dt;
+
+        while !self.at_end() && self.frames[self.current + 1].time_s <= self.time {
+            self.current += 1;
+        }
+
+        StepResult::Continue
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.frames[self.current].particles
+    }
+
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        let mut frame = self.current;
+
+        ui.add(egui::Slider::new(&mut frame, 0..=self.frames.len() - 1).text("Frame"));
+
+        if frame != self.current {
+            self.seek(frame);
+        }
+    }
+
+    fn hud(&self) -> Vec<String> {
+        vec![format!(
+            "Frame {}/{}  t = {:.2} s",
+            self.current + 1,
+            self.frames.len(),
+            self.frames[self.current].time_s
+        )]
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::ArrowLeft => self.seek(self.current.saturating_sub(1)),
+            KeyCode::ArrowRight => self.seek(self.current + 1),
+            _ => {}
+        }
+    }
+
+    fn quiescent(&self) -> bool {
+        self.at_end()
+    }
+}
+
+/// Groups the snapshot rows by their `frame` column, which the recorder writes in order.
+fn load(path: &Path) -> anyhow::Result<Vec<Frame>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut lines = text.lines();
+    let header = lines
+        .next()
+        .context("empty file")?
+        .split(',')
+        .collect::<Vec<_>>();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|&h| h == name)
+            .with_context(|| format!("missing column '{name}'"))
+    };
+    let [frame, time_s, x, y, vx, vy, radius, mass] =
+        ["frame", "time_s", "x", "y", "vx", "vy", "radius", "mass"].map(column);
+    let (frame, time_s, x, y, vx, vy, radius, mass) =
+        (frame?, time_s?, x?, y?, vx?, vy?, radius?, mass?);
+
+    let mut frames: Vec<(u64, Frame)> = Vec::new();
+
+    for (n, line) in lines.enumerate() {
+        let fields = line.split(',').collect::<Vec<_>>();
+        let field = |i: usize| -> anyhow::Result<f32> {
+            fields
+                .get(i)
+                .context("short row")?
+                .parse()
+                .with_context(|| format!("row {}", n + 2))
+        };
+        let id = fields
+            .get(frame)
+            .context("short row")?
+            .parse::<u64>()
+            .with_context(|| format!("row {}", n + 2))?;
+        let particle = Particle::new(
+            Vec2::new(field(x)?, field(y)?),
+            Vec2::new(field(vx)?, field(vy)?),
+            field(radius)?,
+            field(mass)?,
+            [0.8, 0.8, 0.8],
+        );
+
+        match frames.last_mut() {
+            Some((last, f)) if *last == id => f.particles.push(particle),
+            _ => frames.push((
+                id,
+                Frame {
+                    time_s: field(time_s)?,
+                    particles: vec![particle],
+                },
+            )),
+        }
+    }
+
+    if frames.is_empty() {
+        bail!("{} has no rows", path.display());
+    }
+
+    Ok(frames.into_iter().map(|(_, f)| f).collect())
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let Some(path) = std::env::args().nth(1) else {
+        bail!("usage: replay <particles.csv>");
+    };
+
+    engine::run_with(
+        Replay {
+            frames: load(Path::new(&path))?,
+            current: 0,
+            time: 0.0,
+        },
+        SimulationConfig::builder()
+            .title(format!("Replay of {path}"))
+            .build()?,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Renderer stress test without any physics: animates N circles procedurally so upload
+//! bandwidth, draw cost and resize handling can be measured on their own.
+//!
+//! ```sh
+//! cargo run --release -p engine --example stress -- 50000
+//! ```
+
+use std::time::Instant;
+
+use engine::{
+    Bounds, Simulation, SimulationConfig, StepResult, color::hsl_to_rgb, context::Context, egui,
+    particle::Particle,
+};
+use glam::Vec2;
+
+const DEFAULT_COUNT: usize = 10_000;
+
+struct Stress {
+    particles: Vec<Particle>,
+    time: f32,
+    last_step_ms: f32,
+}
+
+impl Stress {
+    /// Spreads the circles over a grid that fills `bounds` and wobbles each one around its cell.
+    fn layout(&mut self, bounds: Bounds) {
+        let n = self.particles.len();
+        let cols = ((n as f32 * bounds.width / bounds.height).sqrt().ceil() as usize).max(1);
+        let rows = n.div_ceil(cols).max(1);
+        let cell = Vec2::new(bounds.width / cols as f32, bounds.height / rows as f32);
+        let origin = -Vec2::new(bounds.width, bounds.height) / 2.0 + cell / 2.0;
+        let t = self.time;
+
+        for (i, p) in self.particles.iter_mut().enumerate() {
+            let phase = i as f32 * 0.618;
+            let wobble = Vec2::new((t * 1.3 + phase).sin(), (t * 1.7 + phase * 2.0).cos());
+            let center = origin + cell * Vec2::new((i % cols) as f32, (i / cols) as f32);
+
+            p.position = center + wobble * cell * 0.25;
+            p.radius = 0.3 * cell.min_element() * (0.75 + 0.25 * (t + phase).sin());
+            p.color = hsl_to_rgb((phase * 60.0 + t * 40.0) % 360.0, 0.7, 0.55);
+        }
+    }
+}
+
+impl Simulation for Stress {
+    fn init(&mut self, _ctx: &mut Context, bounds: Bounds) {
+        self.layout(bounds);
+    }
+
+    fn step(&mut self, _ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult {
+        let start = Instant::now();
+
+        self.time += dt;
+        self.layout(bounds);
+        self.last_step_ms = start.elapsed().as_secs_f32() * 1000.0;
+        StepResult::Continue
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Circles: {}", self.particles.len()));
+        ui.label(format!("Layout: {:.3} ms", self.last_step_ms));
+    }
+
+    fn hud(&self) -> Vec<String> {
+        vec![format!("{} circles", self.particles.len())]
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let count = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => DEFAULT_COUNT,
+    };
+
+    engine::run_with(
+        Stress {
+            particles: vec![Particle::default(); count],
+            time: 0.0,
+            last_step_ms: 0.0,
+        },
+        SimulationConfig::builder()
+            .title(format!("Renderer stress test ({count} circles)"))
+            .window_size(1280, 720)
+            .dpi_scaling(true)
+            .run_unfocused(true)
+            .fps(1000)
+            .present_mode(engine::PresentMode::Immediate)
+            .build()?,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,1049 @@
+//! The windowed runner: the event loop, input handling and the per-frame step and draw.
+
+use std::{
+    borrow::Cow,
+    hash::{BuildHasher, RandomState},
+    iter, mem,
+    sync::Arc,
+    time::Duration,
+};
+
+use glam::Vec2;
+use web_time::{Instant, SystemTime};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{ModifiersState, PhysicalKey},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window, WindowAttributes, WindowId},
+};
+#[cfg(target_arch = "wasm32")]
+use winit::{
+    event_loop::EventLoopProxy,
+    platform::web::{EventLoopExtWebSys, WindowAttributesExtWebSys},
+};
+
+use crate::{
+    Bounds, Error, KeyCode, Simulation, SimulationConfig, StepResult,
+    camera::Camera,
+    capture::{ClipBuffer, FrameCapture},
+    color::ColorMode,
+    context::Context,
+    frame::{self, FrameClock, RealTime, RunStats, WorldBounds},
+    grid::Viewport,
+    heatmap::Heatmap,
+    line::Line,
+    overlay::{self, Overlay},
+    render::{RenderBackend, Renderer},
+    shape,
+    timestep::FixedTimestep,
+};
+const ZOOM_STEP: f32 = 1.1;
+const SELECTION_COLOR: [f32; 3] = [0.3, 0.6, 1.0];
+const RING_THICKNESS: f32 = 0.3;
+/// Share of the screen left empty around the bounds by the fit key (Home).
+const FIT_MARGIN: f32 = 0.05;
+const MIN_WINDOW_SIZE: LogicalSize<f64> = LogicalSize::new(320.0, 240.0);
+const HUD_MARGIN: Vec2 = Vec2::new(-8.0, 8.0);
+const LABEL_MARGIN: Vec2 = Vec2::new(8.0, 8.0);
+
+fn rect_outline(a: Vec2, b: Vec2, color: [f32; 3]) -> [Line; 4] {
+    let corners = [a, Vec2::new(b.x, a.y), b, Vec2::new(a.x, b.y)];
+
+    [0, 1, 2, 3].map(|k| Line::new(corners[k], corners[(k + 1) % 4], color))
+}
+
+fn dpi_scale(dpi_scaling: bool, scale_factor: f64) -> f32 {
+    if dpi_scaling {
+        scale_factor as f32
+    } else {
+        1.0
+    }
+}
+
+/// Clips the particles of a simulation without viewports of its own to locked bounds, leaving
+/// the rest of a window that no longer matches them empty.
+fn letterbox(viewports: Vec<Viewport>, world: WorldBounds, particles: usize) -> Vec<Viewport> {
+    if !viewports.is_empty() || !world.is_locked() {
+        return viewports;
+    }
+
+    let (hw, hh) = world.get().half_extents();
+
+    vec![Viewport {
+        min: Vec2::new(-hw, -hh),
+        max: Vec2::new(hw, hh),
+        instances: 0..particles as u32,
+    }]
+}
+
+/// Window size in the units the cursor is tracked in.
+fn screen_size(PhysicalSize { width, height }: PhysicalSize<u32>, dpi: f32) -> Vec2 {
+    Vec2::new(width as f32, height as f32) / dpi
+}
+
+fn bookmark_slot(code: KeyCode) -> Option<usize> {
+    const DIGITS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    DIGITS.iter().position(|&d| d == code)
+}
+
+/// A renderer created asynchronously for its window, delivered back to the event loop.
+#[cfg(target_arch = "wasm32")]
+struct RendererReady(Arc<Window>, Result<Renderer, Error>);
+
+#[cfg(target_arch = "wasm32")]
+type UserEvent = RendererReady;
+#[cfg(not(target_arch = "wasm32"))]
+type UserEvent = ();
+
+/// What [`run_with`] hands back once the window is closed: the simulation in its final state, for
+/// summaries or a validation pass, and how the run went. On the web the browser keeps driving the
+/// loop after `run_with` returns, so there is nothing to hand back.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Finished<S> {
+    pub simulation: S,
+    pub stats: RunStats,
+}
+#[cfg(target_arch = "wasm32")]
+pub type Finished<S> = std::marker::PhantomData<S>;
+
+/// Seed for runs without a configured one. `RandomState` is not random on every target, so the
+/// time is mixed in.
+fn random_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    RandomState::new().hash_one(now)
+}
+
+/// Window input the loop acts on, reduced from winit's events so it can be fed without a window.
+/// Positions are in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Resized(PhysicalSize<u32>),
+    /// A key press the UI did not take.
+    Key {
+        code: KeyCode,
+        repeat: bool,
+    },
+    Modifiers(ModifiersState),
+    /// The left button; a press the UI took counts as a release.
+    LeftButton {
+        pressed: bool,
+    },
+    CursorMoved(Vec2),
+    /// Wheel turn in lines, when the UI did not take it.
+    Wheel(f32),
+}
+
+impl Input {
+    fn from_event(event: &WindowEvent, consumed: bool) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) => Input::Resized(*size),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: ElementState::Pressed,
+                        repeat,
+                        ..
+                    },
+                ..
+            } if !consumed => Input::Key {
+                code: *code,
+                repeat: *repeat,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => Input::Modifiers(modifiers.state()),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => Input::LeftButton {
+                pressed: !consumed && *state == ElementState::Pressed,
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                Input::CursorMoved(Vec2::new(position.x as f32, position.y as f32))
+            }
+            WindowEvent::MouseWheel { delta, .. } if !consumed => Input::Wheel(match *delta {
+                MouseScrollDelta::LineDelta(_, y) => y,
+                MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
+            }),
+            _ => return None,
+        })
+    }
+}
+
+struct App<S: Simulation> {
+    window: Option<Arc<Window>>,
+    renderer: Option<Renderer>,
+    overlay: Option<Overlay>,
+    simulation: S,
+    clock: FrameClock,
+    config: SimulationConfig,
+
+    camera: Camera,
+    /// The view the last fit produced. Resizes only refit while the camera is still there.
+    fitted: Camera,
+    cursor: Vec2,
+    dragging: bool,
+    selection: Option<Vec2>,
+
+    ctx: Context,
+    step_once: bool,
+    show_hud: bool,
+    color_mode: ColorMode,
+    rings: bool,
+
+    modifiers: ModifiersState,
+    bookmarks: [Option<Camera>; 9],
+    path_time: f32,
+
+    timestep: Option<FixedTimestep>,
+    /// Physical pixels per world unit at camera scale 1.
+    dpi: f32,
+    /// Nothing changes until the next input, so frames are drawn at a low rate.
+    idle: bool,
+    /// Input arrived since the last upload.
+    dirty: bool,
+    /// The window is minimized or fully covered; frames step without rendering.
+    occluded: bool,
+    /// World size of the window, kept while minimized and across scale-factor changes.
+    world: WorldBounds,
+    /// Recent impact density, while shown.
+    heatmap: Option<Heatmap>,
+    realtime: RealTime,
+    stats: RunStats,
+    /// A step returned [`StepResult::Exit`]; the loop exits once the events are handled.
+    exit: bool,
+    /// Why the loop was ended early, returned from [`run_with`].
+    error: Option<Error>,
+    #[cfg(target_arch = "wasm32")]
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl<S: Simulation> App<S> {
+    fn new(
+        sim: S,
+        config: SimulationConfig,
+        ctx: Context,
+        #[cfg(target_arch = "wasm32")] proxy: EventLoopProxy<UserEvent>,
+    ) -> Self {
+        Self {
+            window: None,
+            renderer: None,
+            overlay: None,
+            simulation: sim,
+            clock: FrameClock::new(config.fps, Instant::now()),
+
+            camera: Camera::default(),
+            fitted: Camera::default(),
+            cursor: Vec2::ZERO,
+            dragging: false,
+            selection: None,
+
+            ctx,
+            step_once: false,
+            show_hud: true,
+            color_mode: ColorMode::default(),
+            rings: false,
+
+            modifiers: ModifiersState::default(),
+            bookmarks: [None; 9],
+            path_time: 0.0,
+
+            timestep: config.fixed_dt.map(FixedTimestep::new),
+            dpi: 1.0,
+            idle: false,
+            dirty: true,
+            occluded: false,
+            world: WorldBounds::new(Bounds {
+                width: config.width as f32,
+                height: config.height as f32,
+            }),
+            heatmap: config.heatmap.then(|| {
+                Heatmap::new(Bounds {
+                    width: config.width as f32,
+                    height: config.height as f32,
+                })
+            }),
+            realtime: RealTime::default(),
+            stats: RunStats::default(),
+            exit: false,
+            error: None,
+            #[cfg(target_arch = "wasm32")]
+            proxy,
+            config,
+        }
+    }
+}
+
+impl<S: Simulation> App<S> {
+    /// Advances the clock and, unless paused or unfocused, the simulation. Returns false, doing
+    /// nothing, before the window exists.
+    fn advance(&mut self) -> bool {
+        let Some(window) = self.window.as_ref() else {
+            return false;
+        };
+
+        self.world.update(window.inner_size(), self.dpi);
+
+        if self.simulation.poll() {
+            self.dirty = true;
+        }
+
+        let dt = self.clock.tick(Instant::now());
+
+        self.ctx.set_frame_time(Duration::from_secs_f32(dt));
+        self.stats.frames += 1;
+        self.stats.elapsed += Duration::from_secs_f32(dt);
+
+        let stepped = frame::should_step(
+            &self.ctx,
+            window.has_focus(),
+            self.config.run_unfocused,
+            &mut self.step_once,
+        );
+
+        if stepped {
+            let wall = dt;
+            // A low frame rate must not be mistaken for a stall.
+            let max_dt = self.config.max_dt.max(1.0 / self.config.fps as f32);
+
+            if dt > max_dt {
+                self.stats.dropped_frames += 1;
+                log::debug!("Dropped {:.0} ms after a stall", (dt - max_dt) * 1e3);
+            }
+
+            let dt = dt.min(max_dt) * self.ctx.timescale();
+
+            self.realtime.add(wall, dt);
+
+            if let Some(camera) = self
+                .config
+                .camera_path
+                .as_ref()
+                .and_then(|path| path.sample(self.path_time))
+            {
+                self.camera = camera;
+
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.set_camera(camera);
+                }
+            }
+
+            self.path_time += dt;
+
+            let step_start = Instant::now();
+            let result = match &mut self.timestep {
+                Some(timestep) => {
+                    timestep.advance(&mut self.simulation, &mut self.ctx, dt, self.world.get())
+                }
+                None => self
+                    .ctx
+                    .run_step(&mut self.simulation, dt, self.world.get()),
+            };
+
+            self.exit |= result == StepResult::Exit;
+
+            if let Some(renderer) = &self.renderer {
+                self.simulation
+                    .step_gpu(renderer.device(), renderer.queue(), dt);
+            }
+
+            self.stats.stepped_frames += 1;
+            self.stats.step_time += step_start.elapsed();
+
+            let impacts = self.simulation.take_impacts();
+
+            if let Some(heatmap) = &mut self.heatmap {
+                heatmap.fit(self.world.get());
+                heatmap.decay(dt);
+
+                for impact in impacts {
+                    heatmap.add(impact);
+                }
+            }
+        }
+
+        self.idle = frame::is_idle(&self.ctx, stepped, self.simulation.quiescent());
+
+        true
+    }
+
+    /// Finishes setup once the window has a renderer.
+    fn attach(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window: Arc<Window>,
+        renderer: Result<Renderer, Error>,
+    ) {
+        let mut renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                log::error!("Failed to create renderer: {e}");
+                self.error = Some(e);
+                event_loop.exit();
+                return;
+            }
+        };
+
+        renderer.set_dpi(self.dpi);
+        renderer.set_instance_capacity(self.config.instance_capacity);
+        self.world.update(window.inner_size(), self.dpi);
+
+        if self.config.lock_bounds {
+            self.world.lock();
+        }
+
+        self.simulation.init(&mut self.ctx, self.world.get());
+        self.simulation
+            .init_gpu(renderer.device(), renderer.queue());
+
+        renderer.upload_instances(self.simulation.particles());
+        renderer.set_clear_color(
+            self.config
+                .clear_color
+                .unwrap_or(self.config.theme.clear_color()),
+        );
+
+        // No filesystem or threads to write frames from in the browser.
+        if cfg!(target_arch = "wasm32")
+            && (self.config.capture_dir.is_some() || self.config.clip_frames > 0)
+        {
+            log::warn!("Frame capture is not supported on the web");
+        } else {
+            if let Some(dir) = &self.config.capture_dir {
+                match FrameCapture::new(dir.clone()) {
+                    Ok(capture) => renderer.set_capture(capture),
+                    Err(e) => log::error!("Failed to create {}: {e}", dir.display()),
+                }
+            }
+
+            if self.config.clip_frames > 0 {
+                renderer.set_clip(ClipBuffer::new(
+                    self.config.capture_dir.clone().unwrap_or_default(),
+                    self.config.clip_frames,
+                ));
+            }
+        }
+
+        self.overlay = Some(Overlay::new(&window, self.config.theme));
+        self.window = Some(window.clone());
+        self.renderer = Some(renderer);
+        self.clock.reset(Instant::now());
+    }
+
+    /// Acts on one input: camera moves, engine keys and what is forwarded to the simulation.
+    /// `size` is the window's current size.
+    fn input(&mut self, input: Input, size: PhysicalSize<u32>, renderer: &mut impl RenderBackend) {
+        let screen = screen_size(size, self.dpi);
+
+        match input {
+            Input::Resized(size) => {
+                renderer.resize(size);
+
+                // Locked bounds no longer match the window, so scale the view to fit them,
+                // unless the view was panned, zoomed or recalled since.
+                if self.world.is_locked()
+                    && self.camera == self.fitted
+                    && size.width > 0
+                    && size.height > 0
+                {
+                    let bounds = self.world.get();
+
+                    self.camera = Camera::fit(
+                        Vec2::new(bounds.width, bounds.height),
+                        Vec2::new(size.width as f32, size.height as f32) / self.dpi,
+                        0.0,
+                    );
+                    self.fitted = self.camera;
+                    renderer.set_camera(self.camera);
+                }
+            }
+            Input::Key { code, repeat } => match code {
+                KeyCode::Space if !repeat => {
+                    self.ctx.toggle_pause();
+                    log::info!(
+                        "{}",
+                        if self.ctx.is_paused() {
+                            "Paused"
+                        } else {
+                            "Resumed"
+                        }
+                    );
+                }
+                KeyCode::Period if self.ctx.is_paused() => self.step_once = true,
+                KeyCode::Equal | KeyCode::NumpadAdd => {
+                    self.ctx.speed_up();
+                    log::info!("Time scale {:.2}x", self.ctx.timescale());
+                }
+                KeyCode::Minus | KeyCode::NumpadSubtract => {
+                    self.ctx.slow_down();
+                    log::info!("Time scale {:.2}x", self.ctx.timescale());
+                }
+                KeyCode::KeyH if !repeat => self.show_hud = !self.show_hud,
+                KeyCode::KeyC if !repeat => self.color_mode = self.color_mode.next(),
+                KeyCode::Home if !repeat => {
+                    if let Some(bounds) = self.world.update(size, self.dpi) {
+                        self.camera =
+                            Camera::fit(Vec2::new(bounds.width, bounds.height), screen, FIT_MARGIN);
+                        self.fitted = self.camera;
+                        renderer.set_camera(self.camera);
+                    }
+                }
+                KeyCode::KeyF if !repeat => {
+                    if let Some(focus) = self.simulation.focus() {
+                        self.camera.offset = focus;
+                        renderer.set_camera(self.camera);
+                    }
+                }
+                KeyCode::KeyO if !repeat => {
+                    self.rings = !self.rings;
+                    renderer.set_outline_all(if self.rings { RING_THICKNESS } else { 0.0 });
+                }
+                code if !repeat && let Some(slot) = bookmark_slot(code) => {
+                    if self.modifiers.control_key() {
+                        self.bookmarks[slot] = Some(self.camera);
+                        log::info!(
+                            "Saved camera bookmark {} ({},{},{})",
+                            slot + 1,
+                            self.camera.offset.x,
+                            self.camera.offset.y,
+                            self.camera.scale
+                        );
+                    } else if let Some(camera) = self.bookmarks[slot] {
+                        self.camera = camera;
+                        renderer.set_camera(camera);
+                    }
+                }
+                KeyCode::KeyX if !repeat => renderer.save_clip(),
+                KeyCode::KeyM if !repeat => {
+                    self.heatmap = match self.heatmap {
+                        Some(_) => None,
+                        None => Some(Heatmap::new(self.world.get())),
+                    };
+                }
+                KeyCode::KeyR if !repeat => {
+                    self.ctx.toggle_recording();
+                    log::info!(
+                        "Recording {}",
+                        if self.ctx.is_recording() { "on" } else { "off" }
+                    );
+                }
+                code if !repeat => self.simulation.key_pressed(code),
+                _ => {}
+            },
+            Input::Modifiers(modifiers) => self.modifiers = modifiers,
+            Input::LeftButton { pressed } => {
+                let world = self.camera.screen_to_world(self.cursor, screen);
+
+                if let Some(start) = self.selection.take() {
+                    self.simulation
+                        .selected_rect(start.min(world), start.max(world));
+                }
+
+                if pressed && self.modifiers.shift_key() {
+                    self.selection = Some(world);
+                    self.dragging = false;
+                } else {
+                    self.dragging = pressed;
+
+                    if pressed {
+                        self.simulation.clicked(world);
+                    }
+                }
+            }
+            Input::CursorMoved(position) => {
+                let cursor = position / self.dpi;
+
+                if self.dragging {
+                    self.camera.pan(cursor - self.cursor);
+                    renderer.set_camera(self.camera);
+                }
+
+                self.cursor = cursor;
+            }
+            Input::Wheel(lines) => {
+                self.camera
+                    .zoom_at(ZOOM_STEP.powf(lines), self.cursor, screen);
+                renderer.set_camera(self.camera);
+            }
+        }
+    }
+
+    fn hidden(&self) -> bool {
+        self.occluded
+            || self
+                .window
+                .as_ref()
+                .is_some_and(|w| frame::bounds(w.inner_size(), self.dpi).is_none())
+    }
+}
+
+impl<S: Simulation> ApplicationHandler<UserEvent> for App<S> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
+        let monitor = monitors.get(self.config.monitor).or_else(|| {
+            if self.config.fullscreen {
+                log::warn!(
+                    "monitor {} not found ({} available), using the first one",
+                    self.config.monitor,
+                    monitors.len()
+                );
+            }
+
+            monitors.first()
+        });
+
+        let attributes = WindowAttributes::default()
+            .with_title(&self.config.title)
+            .with_min_inner_size(MIN_WINDOW_SIZE)
+            .with_inner_size(
+                if self.config.fullscreen
+                    && let Some(monitor) = monitor
+                {
+                    let size = monitor.size().to_logical::<f64>(monitor.scale_factor());
+                    LogicalSize::new(size.width * 0.9, size.height * 0.8)
+                } else {
+                    LogicalSize::new(self.config.width as f64, self.config.height as f64)
+                },
+            );
+        // Adds the canvas to the page body.
+        #[cfg(target_arch = "wasm32")]
+        let attributes = attributes.with_append(true);
+
+        if let Ok(window) = event_loop.create_window(attributes) {
+            if self.config.fullscreen {
+                window.set_fullscreen(Some(Fullscreen::Borderless(monitor.cloned())));
+            }
+
+            let window = Arc::new(window);
+
+            self.dpi = dpi_scale(self.config.dpi_scaling, window.scale_factor());
+
+            let renderer = Renderer::new(
+                window.clone(),
+                window.inner_size(),
+                self.config.msaa_samples,
+                self.config.backend,
+                self.config.present_mode,
+                self.config.srgb,
+                self.config.render_scale,
+            );
+
+            #[cfg(not(target_arch = "wasm32"))]
+            self.attach(event_loop, window, pollster::block_on(renderer));
+
+            // The browser can't block on the adapter, so the renderer comes back as an event.
+            #[cfg(target_arch = "wasm32")]
+            {
+                let proxy = self.proxy.clone();
+
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = proxy.send_event(RendererReady(window, renderer.await));
+                });
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let (Some(window), Some(renderer), Some(overlay)) = (
+            self.window.as_ref(),
+            self.renderer.as_mut(),
+            self.overlay.as_mut(),
+        ) else {
+            return;
+        };
+
+        let consumed = overlay.on_window_event(window, &event);
+
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            self.idle = false;
+            self.dirty = true;
+        }
+
+        if let Some(input) = Input::from_event(&event, consumed) {
+            let size = window.inner_size();
+
+            // Input handling needs the whole app, so the renderer is lent out meanwhile.
+            if let Some(mut renderer) = self.renderer.take() {
+                self.input(input, size, &mut renderer);
+                self.renderer = Some(renderer);
+            }
+
+            return;
+        }
+
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Occluded(occluded) => self.occluded = occluded,
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                mut inner_size_writer,
+            } => {
+                // Resize so the world keeps its size in world units at the new scale.
+                self.dpi = dpi_scale(self.config.dpi_scaling, scale_factor);
+                renderer.set_dpi(self.dpi);
+
+                let new_size = self.world.rescale(self.dpi);
+
+                if let Err(e) = inner_size_writer.request_inner_size(new_size) {
+                    log::error!("Failed to change inner size: {e}");
+                }
+
+                renderer.resize(new_size);
+            }
+            // Steps while hidden are driven from `about_to_wait` instead.
+            WindowEvent::RedrawRequested if self.hidden() => {}
+            WindowEvent::RedrawRequested => {
+                if !self.advance() {
+                    return;
+                }
+                let (Some(window), Some(renderer), Some(overlay)) = (
+                    self.window.as_ref(),
+                    self.renderer.as_mut(),
+                    self.overlay.as_mut(),
+                ) else {
+                    return;
+                };
+                let bounds = self.world.get();
+                let screen = screen_size(window.inner_size(), self.dpi);
+                let render_start = Instant::now();
+
+                // Idle frames only redraw the UI; the GPU still holds the last particles.
+                if !self.idle || mem::take(&mut self.dirty) {
+                    let particles = match &self.timestep {
+                        Some(timestep) => {
+                            timestep.interpolate(&self.ctx, self.simulation.particles(), bounds)
+                        }
+                        None => Cow::Borrowed(self.simulation.particles()),
+                    };
+
+                    let (scenery, mut lines) = shape::partition(&self.simulation.scenery());
+                    let instances = self.color_mode.apply(particles);
+                    // Scenery goes first so overlays on the same layer are drawn over it.
+                    let overlay_instances = match scenery.is_empty() {
+                        true => self.simulation.overlay_instances(),
+                        false => Cow::Owned(
+                            [scenery, self.simulation.overlay_instances().into_owned()].concat(),
+                        ),
+                    };
+
+                    renderer.set_viewports(letterbox(
+                        self.simulation.viewports(),
+                        self.world,
+                        self.simulation.particles().len(),
+                    ));
+                    renderer.upload_instances(&instances);
+                    renderer.upload_overlay(&overlay_instances);
+                    lines.extend(self.simulation.debug_lines());
+
+                    if let Some(start) = self.selection {
+                        let end = self.camera.screen_to_world(self.cursor, screen);
+
+                        lines.extend(rect_outline(start, end, SELECTION_COLOR));
+                    }
+
+                    renderer.upload_lines(&lines);
+                    renderer.upload_heatmap(self.heatmap.as_ref());
+                }
+
+                let frame = self.ctx.frame();
+
+                if self.show_hud {
+                    let hud = iter::once(format!("FPS {:.1}", frame.fps()))
+                        .chain(self.simulation.hud())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+
+                    renderer.draw_text(
+                        Vec2::new(screen.x, 0.0) + HUD_MARGIN,
+                        egui::Align2::RIGHT_TOP,
+                        hud,
+                    );
+
+                    for (world, text) in self.simulation.labels() {
+                        renderer.draw_text(
+                            self.camera.world_to_screen(world, screen) + LABEL_MARGIN,
+                            egui::Align2::LEFT_TOP,
+                            text,
+                        );
+                    }
+                }
+
+                let texts = renderer.take_texts();
+
+                let ui = overlay.run(window, |egui_ctx| {
+                    // Queued text is in logical pixels, egui in points.
+                    let scale = self.dpi / egui_ctx.pixels_per_point();
+
+                    for text in &texts {
+                        let pos = egui::pos2(text.pos.x * scale, text.pos.y * scale);
+
+                        overlay::draw_text(egui_ctx, pos, text.anchor, &text.text);
+                    }
+
+                    egui::Window::new("Debug").show(egui_ctx, |ui| {
+                        ui.label(format!("FPS: {:.1}", frame.fps()));
+                        ui.label(format!(
+                            "dt: {:.3} ms",
+                            frame.frame_time.as_secs_f64() * 1000.0
+                        ));
+                        ui.label(format!("Time scale: {:.2}x (+/-)", self.ctx.timescale()));
+                        ui.label(format!("Paused: {}", self.ctx.is_paused()));
+                        ui.label(format!("Recording: {}", self.ctx.is_recording()));
+                        ui.label(format!("Color mode: {} (C)", self.color_mode));
+                        ui.label(format!("Rings: {} (O)", self.rings));
+                        ui.label(format!(
+                            "Real-time factor: {:.2}x",
+                            self.realtime.factor().unwrap_or(0.0)
+                        ));
+                        ui.label(format!("Heatmap: {} (M)", self.heatmap.is_some()));
+                        ui.separator();
+
+                        self.simulation.debug_ui(ui);
+                    });
+
+                    self.simulation.ui(egui_ctx);
+                });
+
+                if let Err(err) = renderer.render(&ui) {
+                    use wgpu::SurfaceError::*;
+
+                    match err {
+                        Timeout => (),
+                        Lost | Outdated => {
+                            renderer.resize(window.inner_size());
+                        }
+                        OutOfMemory | Other => {
+                            log::error!("wgpu exiting: {err}");
+                            event_loop.exit();
+                        }
+                    }
+                }
+
+                self.ctx.set_render_time(render_start.elapsed());
+            }
+
+            _ => {}
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.simulation.on_exit();
+        log::info!("{}", self.realtime.summary());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        let RendererReady(window, renderer) = event;
+
+        self.attach(event_loop, window, renderer);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.exit {
+            event_loop.exit();
+            return;
+        }
+
+        match self.clock.next_frame(Instant::now(), self.idle) {
+            Some(next) => event_loop.set_control_flow(ControlFlow::WaitUntil(next)),
+            // Hidden windows may never get a redraw, so step here to keep runs and recordings
+            // going.
+            None if self.hidden() => {
+                self.advance();
+            }
+            None => {
+                if let Some(w) = &self.window {
+                    w.request_redraw();
+                }
+            }
+        }
+    }
+}
+
+/// Opens the window and runs `sim` until it is closed or a step asks to exit. A renderer that
+/// cannot be created ends the loop and is reported as the error.
+pub fn run_with<S: Simulation + 'static>(
+    sim: S,
+    config: SimulationConfig,
+) -> Result<Finished<S>, Error> {
+    let seed = config.seed.unwrap_or_else(random_seed);
+    let mut ctx = Context::default();
+
+    ctx.set_seed(seed);
+    log::info!("Seed {seed}");
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let app = App::new(
+        sim,
+        config,
+        ctx,
+        #[cfg(target_arch = "wasm32")]
+        event_loop.create_proxy(),
+    );
+
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut app = app;
+
+        event_loop.run_app(&mut app)?;
+
+        match app.error {
+            Some(e) => Err(e),
+            None => Ok(Finished {
+                simulation: app.simulation,
+                stats: app.stats,
+            }),
+        }
+    }
+
+    // Returns at once; the browser drives the loop from requestAnimationFrame and timers.
+    #[cfg(target_arch = "wasm32")]
+    {
+        event_loop.spawn_app(app);
+
+        Ok(std::marker::PhantomData)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{particle::Particle, render::NullBackend};
+
+    const SIZE: PhysicalSize<u32> = PhysicalSize::new(800, 600);
+
+    /// Keeps what the loop forwarded to it.
+    #[derive(Default)]
+    struct Probe {
+        keys: Vec<KeyCode>,
+        clicks: Vec<Vec2>,
+    }
+
+    impl Simulation for Probe {
+        fn init(&mut self, _ctx: &mut Context, _bounds: Bounds) {}
+
+        fn step(&mut self, _ctx: &mut Context, _dt: f32, _bounds: Bounds) -> StepResult {
+            StepResult::Continue
+        }
+
+        fn particles(&self) -> &[Particle] {
+            &[]
+        }
+
+        fn key_pressed(&mut self, key: KeyCode) {
+            self.keys.push(key);
+        }
+
+        fn clicked(&mut self, world: Vec2) {
+            self.clicks.push(world);
+        }
+    }
+
+    fn app() -> App<Probe> {
+        let config = SimulationConfig::builder()
+            .window_size(SIZE.width, SIZE.height)
+            .build()
+            .unwrap();
+
+        App::new(Probe::default(), config, Context::default())
+    }
+
+    fn key(code: KeyCode) -> Input {
+        Input::Key {
+            code,
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn keys_drive_the_clock_and_reach_the_simulation() {
+        let (mut app, mut backend) = (app(), NullBackend::default());
+
+        app.input(key(KeyCode::Space), SIZE, &mut backend);
+        assert!(app.ctx.is_paused());
+
+        app.input(
+            Input::Key {
+                code: KeyCode::Space,
+                repeat: true,
+            },
+            SIZE,
+            &mut backend,
+        );
+        app.input(key(KeyCode::Period), SIZE, &mut backend);
+        assert!(app.ctx.is_paused() && app.step_once);
+
+        app.input(key(KeyCode::KeyO), SIZE, &mut backend);
+        app.input(key(KeyCode::KeyX), SIZE, &mut backend);
+        app.input(key(KeyCode::KeyQ), SIZE, &mut backend);
+
+        assert_eq!(backend.outline_all, RING_THICKNESS);
+        assert_eq!(backend.clips_saved, 1);
+        assert_eq!(app.simulation.keys, [KeyCode::KeyQ]);
+    }
+
+    #[test]
+    fn locked_bounds_refit_on_resize_until_the_view_moves() {
+        let (mut app, mut backend) = (app(), NullBackend::default());
+        let double = PhysicalSize::new(1600, 1200);
+
+        app.world.lock();
+        app.input(Input::Resized(double), double, &mut backend);
+
+        assert_eq!(backend.size, double);
+        assert_eq!(backend.camera.scale, 2.0);
+
+        app.input(
+            Input::CursorMoved(Vec2::new(800.0, 600.0)),
+            double,
+            &mut backend,
+        );
+        app.input(Input::Wheel(1.0), double, &mut backend);
+
+        let zoomed = backend.camera;
+
+        app.input(Input::Resized(SIZE), SIZE, &mut backend);
+
+        assert_eq!(backend.size, SIZE);
+        assert_eq!(backend.camera, zoomed);
+    }
+
+    #[test]
+    fn clicks_arrive_in_world_coordinates() {
+        let (mut app, mut backend) = (app(), NullBackend::default());
+
+        app.input(
+            Input::CursorMoved(Vec2::new(500.0, 200.0)),
+            SIZE,
+            &mut backend,
+        );
+        app.input(Input::LeftButton { pressed: true }, SIZE, &mut backend);
+        app.input(Input::LeftButton { pressed: false }, SIZE, &mut backend);
+
+        assert_eq!(app.simulation.clicks, [Vec2::new(100.0, 100.0)]);
+    }
+}
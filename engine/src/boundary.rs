@@ -0,0 +1,662 @@
+use std::{fmt, str::FromStr};
+
+use glam::Vec2;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::{Bounds, particle::Particle, trajectory};
+
+pub trait BoundaryCondition: Send + Sync {
+    /// Earliest time in `[0, dt]` at which the particle, accelerating at `accel`, touches the
+    /// boundary.
+    fn toi(&self, p: &Particle, accel: Vec2, bounds: &Bounds, dt: f32) -> Option<f32>;
+
+    /// Reflects a particle that is touching the boundary.
+    fn resolve(&self, p: &mut Particle, bounds: &Bounds) -> Option<Contact>;
+
+    /// Pushes a particle that ended a step outside the domain back in.
+    fn clamp(&self, p: &mut Particle, bounds: &Bounds);
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool;
+
+    /// Closed polyline of the container in world coordinates.
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2>;
+
+    /// Size of the domain if particles leaving one edge re-enter the opposite one.
+    fn period(&self, _bounds: &Bounds) -> Option<Vec2> {
+        None
+    }
+}
+
+/// Shortest of the displacements `d` stands for in a domain repeating every `period`.
+pub fn minimum_image(d: Vec2, period: Vec2) -> Vec2 {
+    d - period * (d / period).round()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub wall: Wall,
+    pub normal: Vec2,
+    pub vn_before: f32,
+    pub vn_after: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wall {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Corner,
+    Rim,
+    Edge(usize),
+    Obstacle(usize),
+}
+
+pub struct ReflectiveBox;
+pub struct Periodic;
+pub struct Open;
+pub struct Circular;
+
+pub struct Polygon {
+    vertices: Vec<Vec2>,
+}
+
+impl BoundaryCondition for ReflectiveBox {
+    fn toi(&self, p: &Particle, accel: Vec2, bounds: &Bounds, dt: f32) -> Option<f32> {
+        let (hw, hh) = bounds.half_extents();
+        let pos = p.position;
+        let vel = p.velocity;
+        let r = p.radius;
+
+        let (x_min, x_max) = (-hw + r, hw - r);
+        let (y_min, y_max) = (-hh + r, hh - r);
+
+        if accel != Vec2::ZERO {
+            let h = 0.5 * accel;
+
+            // Depth past each wall along its axis; any of them can be reached by falling back.
+            return [
+                [pos.x - x_max, vel.x, h.x],
+                [x_min - pos.x, -vel.x, -h.x],
+                [pos.y - y_max, vel.y, h.y],
+                [y_min - pos.y, -vel.y, -h.y],
+            ]
+            .into_iter()
+            .filter_map(|depth| trajectory::first_contact(&depth.map(f64::from), dt))
+            .min_by(f32::total_cmp);
+        }
+
+        let mut t_min = f32::INFINITY;
+
+        if vel.x > 0.0 {
+            let t = (x_max - pos.x) / vel.x;
+
+            if t >= 0.0 && t <= dt {
+                t_min = t_min.min(t);
+            }
+        } else if vel.x < 0.0 {
+            let t = (x_min - pos.x) / vel.x;
+
+            if t >= 0.0 && t <= dt {
+                t_min = t_min.min(t);
+            }
+        }
+
+        if vel.y > 0.0 {
+            let t = (y_max - pos.y) / vel.y;
+
+            if t >= 0.0 && t <= dt {
+                t_min = t_min.min(t);
+            }
+        } else if vel.y < 0.0 {
+            let t = (y_min - pos.y) / vel.y;
+
+            if t >= 0.0 && t <= dt {
+                t_min = t_min.min(t);
+            }
+        }
+
+        match t_min.is_finite() {
+            true => Some(t_min),
+            false => None,
+        }
+    }
+
+    fn resolve(&self, p: &mut Particle, bounds: &Bounds) -> Option<Contact> {
+        let (hw, hh) = bounds.half_extents();
+
+        let (x_min, x_max) = (-hw + p.radius, hw - p.radius);
+        let (y_min, y_max) = (-hh + p.radius, hh - p.radius);
+
+        let n = if p.position.x <= x_min {
+            Vec2::new(-1.0, 0.0)
+        } else if p.position.x >= x_max {
+            Vec2::new(1.0, 0.0)
+        } else if p.position.y <= y_min {
+            Vec2::new(0.0, -1.0)
+        } else {
+            Vec2::new(0.0, 1.0)
+        };
+
+        let vn_before = p.velocity.dot(n);
+
+        if p.position.x <= x_min && p.velocity.x < 0.0 {
+            p.position.x = x_min;
+            p.velocity.x *= -1.0;
+        } else if p.position.x >= x_max && p.velocity.x > 0.0 {
+            p.position.x = x_max;
+            p.velocity.x *= -1.0;
+        }
+
+        if p.position.y <= y_min && p.velocity.y < 0.0 {
+            p.position.y = y_min;
+            p.velocity.y *= -1.0;
+        } else if p.position.y >= y_max && p.velocity.y > 0.0 {
+            p.position.y = y_max;
+            p.velocity.y *= -1.0;
+        }
+
+        let vn_after = p.velocity.dot(n);
+        let on_x = p.position.x <= x_min || p.position.x >= x_max;
+        let on_y = p.position.y <= y_min || p.position.y >= y_max;
+        let wall = if on_x && on_y {
+            Wall::Corner
+        } else if p.position.x <= x_min {
+            Wall::Left
+        } else if p.position.x >= x_max {
+            Wall::Right
+        } else if p.position.y <= y_min {
+            Wall::Bottom
+        } else {
+            Wall::Top
+        };
+
+        Some(Contact {
+            wall,
+            normal: n,
+            vn_before,
+            vn_after,
+        })
+    }
+
+    fn clamp(&self, p: &mut Particle, bounds: &Bounds) {
+        let (hw, hh) = bounds.half_extents();
+
+        let (x_min, x_max) = (-hw + p.radius, hw - p.radius);
+        let (y_min, y_max) = (-hh + p.radius, hh - p.radius);
+
+        if p.position.x < x_min {
+            p.position.x = x_min;
+            p.velocity.x *= -1.0;
+        } else if p.position.x > x_max {
+            p.position.x = x_max;
+            p.velocity.x *= -1.0;
+        }
+
+        if p.position.y < y_min {
+            p.position.y = y_min;
+            p.velocity.y *= -1.0;
+        } else if p.position.y > y_max {
+            p.position.y = y_max;
+            p.velocity.y *= -1.0;
+        }
+    }
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool {
+        let (hw, hh) = bounds.half_extents();
+
+        p.position.x.abs() <= hw - p.radius && p.position.y.abs() <= hh - p.radius
+    }
+
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2> {
+        box_outline(bounds)
+    }
+}
+
+impl BoundaryCondition for Periodic {
+    fn toi(&self, _p: &Particle, _accel: Vec2, _bounds: &Bounds, _dt: f32) -> Option<f32> {
+        None
+    }
+
+    fn resolve(&self, _p: &mut Particle, _bounds: &Bounds) -> Option<Contact> {
+        None
+    }
+
+    fn clamp(&self, p: &mut Particle, bounds: &Bounds) {
+        let (hw, hh) = bounds.half_extents();
+
+        p.position.x = (p.position.x + hw).rem_euclid(bounds.width) - hw;
+        p.position.y = (p.position.y + hh).rem_euclid(bounds.height) - hh;
+    }
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool {
+        let (hw, hh) = bounds.half_extents();
+
+        (-hw..hw).contains(&p.position.x) && (-hh..hh).contains(&p.position.y)
+    }
+
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2> {
+        box_outline(bounds)
+    }
+
+    fn period(&self, bounds: &Bounds) -> Option<Vec2> {
+        Some(Vec2::new(bounds.width, bounds.height))
+    }
+}
+
+impl BoundaryCondition for Open {
+    fn toi(&self, _p: &Particle, _accel: Vec2, _bounds: &Bounds, _dt: f32) -> Option<f32> {
+        None
+    }
+
+    fn resolve(&self, _p: &mut Particle, _bounds: &Bounds) -> Option<Contact> {
+        None
+    }
+
+    fn clamp(&self, _p: &mut Particle, _bounds: &Bounds) {}
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool {
+        let (hw, hh) = bounds.half_extents();
+
+        p.position.x.abs() <= hw && p.position.y.abs() <= hh
+    }
+
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2> {
+        box_outline(bounds)
+    }
+}
+
+impl Circular {
+    /// The circle inscribed in the window bounds.
+    fn radius(bounds: &Bounds) -> f32 {
+        let (hw, hh) = bounds.half_extents();
+
+        hw.min(hh)
+    }
+}
+
+impl BoundaryCondition for Circular {
+    fn toi(&self, p: &Particle, accel: Vec2, bounds: &Bounds, dt: f32) -> Option<f32> {
+        let reach = Self::radius(bounds) - p.radius;
+
+        if accel != Vec2::ZERO {
+            let (x, v, h) = (
+                p.position.as_dvec2(),
+                p.velocity.as_dvec2(),
+                0.5 * accel.as_dvec2(),
+            );
+            let reach = reach as f64;
+
+            // |x + v t + h t²|² - reach², the squared distance past the rim.
+            return trajectory::first_contact(
+                &[
+                    x.dot(x) - reach * reach,
+                    2.0 * x.dot(v),
+                    v.dot(v) + 2.0 * x.dot(h),
+                    2.0 * v.dot(h),
+                    h.dot(h),
+                ],
+                dt,
+            );
+        }
+
+        let a = p.velocity.dot(p.velocity);
+        let b = 2.0 * p.position.dot(p.velocity);
+        let c = p.position.dot(p.position) - reach * reach;
+
+        if a <= 1e-12 {
+            return None;
+        }
+
+        let disc = b * b - 4.0 * a * c;
+
+        if disc < 0.0 {
+            return None;
+        }
+
+        let t = (-b + disc.sqrt()) / (2.0 * a);
+
+        match t >= 0.0 && t <= dt {
+            true => Some(t),
+            false => None,
+        }
+    }
+
+    fn resolve(&self, p: &mut Particle, bounds: &Bounds) -> Option<Contact> {
+        let dist = p.position.length();
+
+        if dist == 0.0 {
+            return None;
+        }
+
+        let n = p.position / dist;
+        let vn_before = p.velocity.dot(n);
+
+        if vn_before > 0.0 {
+            p.velocity -= 2.0 * vn_before * n;
+        }
+
+        p.position = n * dist.min(Self::radius(bounds) - p.radius);
+
+        Some(Contact {
+            wall: Wall::Rim,
+            normal: n,
+            vn_before,
+            vn_after: p.velocity.dot(n),
+        })
+    }
+
+    fn clamp(&self, p: &mut Particle, bounds: &Bounds) {
+        let reach = Self::radius(bounds) - p.radius;
+        let dist = p.position.length();
+
+        if dist > reach {
+            let n = p.position / dist;
+            let vn = p.velocity.dot(n);
+
+            p.position = n * reach;
+
+            if vn > 0.0 {
+                p.velocity -= 2.0 * vn * n;
+            }
+        }
+    }
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool {
+        p.position.length() <= Self::radius(bounds) - p.radius
+    }
+
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2> {
+        const SEGMENTS: usize = 64;
+
+        let r = Self::radius(bounds);
+
+        (0..SEGMENTS)
+            .map(|k| Vec2::from_angle(k as f32 * std::f32::consts::TAU / SEGMENTS as f32) * r)
+            .collect()
+    }
+}
+
+impl Polygon {
+    /// `vertices` are counter-clockwise, convex and given in units of the half extents, so the
+    /// container stretches with the window like the box does.
+    pub fn new(vertices: Vec<Vec2>) -> Self {
+        Self { vertices }
+    }
+
+    pub fn regular(sides: usize) -> Self {
+        let sides = sides.max(3);
+
+        Self::new(
+            (0..sides)
+                .map(|k| Vec2::from_angle(k as f32 * std::f32::consts::TAU / sides as f32))
+                .collect(),
+        )
+    }
+
+    /// Outward unit normal and plane offset of every edge, scaled to the bounds.
+    fn edges(&self, bounds: &Bounds) -> impl Iterator<Item = (Vec2, f32)> + '_ {
+        let (hw, hh) = bounds.half_extents();
+        let scale = Vec2::new(hw, hh);
+        let n = self.vertices.len();
+
+        (0..n).map(move |k| {
+            let a = self.vertices[k] * scale;
+            let b = self.vertices[(k + 1) % n] * scale;
+            let normal = (b - a).perp().normalize() * -1.0;
+
+            (normal, normal.dot(a))
+        })
+    }
+}
+
+impl BoundaryCondition for Polygon {
+    fn toi(&self, p: &Particle, accel: Vec2, bounds: &Bounds, dt: f32) -> Option<f32> {
+        if accel != Vec2::ZERO {
+            return self
+                .edges(bounds)
+                .filter_map(|(n, c)| {
+                    let depth = [
+                        p.position.dot(n) - (c - p.radius),
+                        p.velocity.dot(n),
+                        0.5 * accel.dot(n),
+                    ];
+
+                    trajectory::first_contact(&depth.map(f64::from), dt)
+                })
+                .min_by(f32::total_cmp);
+        }
+
+        self.edges(bounds)
+            .filter_map(|(n, c)| {
+                let vn = p.velocity.dot(n);
+
+                if vn <= 0.0 {
+                    return None;
+                }
+
+                let t = (c - p.radius - p.position.dot(n)) / vn;
+
+                (t >= 0.0 && t <= dt).then_some(t)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    fn resolve(&self, p: &mut Particle, bounds: &Bounds) -> Option<Contact> {
+        let (k, (n, c)) = self
+            .edges(bounds)
+            .enumerate()
+            .filter(|(_, (n, _))| p.velocity.dot(*n) > 0.0)
+            .max_by(|(_, (na, ca)), (_, (nb, cb))| {
+                (p.position.dot(*na) - ca).total_cmp(&(p.position.dot(*nb) - cb))
+            })?;
+
+        let vn_before = p.velocity.dot(n);
+        let overshoot = p.position.dot(n) - (c - p.radius);
+
+        p.velocity -= 2.0 * vn_before * n;
+
+        if overshoot > 0.0 {
+            p.position -= overshoot * n;
+        }
+
+        Some(Contact {
+            wall: Wall::Edge(k),
+            normal: n,
+            vn_before,
+            vn_after: p.velocity.dot(n),
+        })
+    }
+
+    fn clamp(&self, p: &mut Particle, bounds: &Bounds) {
+        for (n, c) in self.edges(bounds) {
+            let overshoot = p.position.dot(n) - (c - p.radius);
+
+            if overshoot > 0.0 {
+                p.position -= overshoot * n;
+
+                let vn = p.velocity.dot(n);
+
+                if vn > 0.0 {
+                    p.velocity -= 2.0 * vn * n;
+                }
+            }
+        }
+    }
+
+    fn contains(&self, p: &Particle, bounds: &Bounds) -> bool {
+        self.edges(bounds)
+            .all(|(n, c)| p.position.dot(n) <= c - p.radius)
+    }
+
+    fn outline(&self, bounds: &Bounds) -> Vec<Vec2> {
+        let (hw, hh) = bounds.half_extents();
+
+        self.vertices
+            .iter()
+            .map(|v| *v * Vec2::new(hw, hh))
+            .collect()
+    }
+}
+
+fn box_outline(bounds: &Bounds) -> Vec<Vec2> {
+    let (hw, hh) = bounds.half_extents();
+
+    vec![
+        Vec2::new(-hw, -hh),
+        Vec2::new(hw, -hh),
+        Vec2::new(hw, hh),
+        Vec2::new(-hw, hh),
+    ]
+}
+
+impl fmt::Display for Wall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wall::Left => f.write_str("left"),
+            Wall::Right => f.write_str("right"),
+            Wall::Top => f.write_str("top"),
+            Wall::Bottom => f.write_str("bottom"),
+            Wall::Corner => f.write_str("corner"),
+            Wall::Rim => f.write_str("rim"),
+            Wall::Edge(id) => write!(f, "edge_{id}"),
+            Wall::Obstacle(id) => write!(f, "obstacle_{id}"),
+        }
+    }
+}
+
+impl FromStr for Wall {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let indexed = |prefix: &str| s.strip_prefix(prefix).and_then(|id| id.parse().ok());
+
+        match s {
+            "left" => Ok(Wall::Left),
+            "right" => Ok(Wall::Right),
+            "top" => Ok(Wall::Top),
+            "bottom" => Ok(Wall::Bottom),
+            "corner" => Ok(Wall::Corner),
+            "rim" => Ok(Wall::Rim),
+            _ => indexed("edge_")
+                .map(Wall::Edge)
+                .or_else(|| indexed("obstacle_").map(Wall::Obstacle))
+                .ok_or_else(|| format!("unknown wall `{s}`")),
+        }
+    }
+}
+
+impl Serialize for Wall {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Wall {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particle(position: Vec2, velocity: Vec2) -> Particle {
+        Particle::new(position, velocity, 5.0, 1.0, [1.0; 3])
+    }
+
+    const BOUNDS: Bounds = Bounds {
+        width: 200.0,
+        height: 100.0,
+    };
+
+    #[test]
+    fn wall_labels_round_trip() {
+        for wall in [
+            Wall::Left,
+            Wall::Right,
+            Wall::Top,
+            Wall::Bottom,
+            Wall::Corner,
+            Wall::Rim,
+            Wall::Edge(3),
+            Wall::Obstacle(12),
+        ] {
+            assert_eq!(wall.to_string().parse::<Wall>(), Ok(wall));
+        }
+
+        assert_eq!(Wall::Obstacle(12).to_string(), "obstacle_12");
+        assert!("obstacle_x".parse::<Wall>().is_err());
+        assert!("Left".parse::<Wall>().is_err());
+    }
+
+    #[test]
+    fn box_and_square_polygon_agree() {
+        let square = Polygon::new(vec![
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ]);
+        let p = particle(Vec2::new(50.0, 10.0), Vec2::new(100.0, 0.0));
+
+        let t_box = ReflectiveBox.toi(&p, Vec2::ZERO, &BOUNDS, 1.0).unwrap();
+        let t_poly = square.toi(&p, Vec2::ZERO, &BOUNDS, 1.0).unwrap();
+
+        assert!((t_box - 0.45).abs() < 1e-5);
+        assert!((t_box - t_poly).abs() < 1e-5);
+    }
+
+    #[test]
+    fn circular_reflects_along_radius() {
+        let mut p = particle(Vec2::new(0.0, 40.0), Vec2::new(0.0, 10.0));
+        let t = Circular.toi(&p, Vec2::ZERO, &BOUNDS, 1.0).unwrap();
+
+        assert!((t - 0.5).abs() < 1e-5);
+
+        p.position += p.velocity * t;
+        let contact = Circular.resolve(&mut p, &BOUNDS).unwrap();
+
+        assert_eq!(contact.wall, Wall::Rim);
+        assert!((p.velocity - Vec2::new(0.0, -10.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn falling_particles_reach_the_floor() {
+        let p = particle(Vec2::new(0.0, 40.0), Vec2::ZERO);
+        let gravity = Vec2::new(0.0, -20.0);
+        let square = Polygon::regular(4);
+
+        // 85 down to the floor at y = -45: 10 t² = 85.
+        let t = ReflectiveBox.toi(&p, gravity, &BOUNDS, 5.0).unwrap();
+
+        assert!((t - 8.5f32.sqrt()).abs() < 1e-4, "{t}");
+        assert!(ReflectiveBox.toi(&p, gravity, &BOUNDS, 2.0).is_none());
+        assert!(square.toi(&p, gravity, &BOUNDS, 5.0).is_some());
+
+        // The bottom of the inscribed circle is as far down as the floor.
+        let t = Circular.toi(&p, gravity, &BOUNDS, 5.0).unwrap();
+
+        assert!((t - 8.5f32.sqrt()).abs() < 1e-4, "{t}");
+    }
+
+    #[test]
+    fn periodic_wraps_into_domain() {
+        let mut p = particle(Vec2::new(105.0, -55.0), Vec2::ZERO);
+
+        Periodic.clamp(&mut p, &BOUNDS);
+
+        assert!((p.position - Vec2::new(-95.0, 45.0)).length() < 1e-4);
+        assert!(Periodic.contains(&p, &BOUNDS));
+
+        // Neighbors across the seam are close, not a domain apart.
+        let period = Periodic.period(&BOUNDS).unwrap();
+        let d = minimum_image(Vec2::new(190.0, -20.0), period);
+
+        assert!((d - Vec2::new(-10.0, -20.0)).length() < 1e-4);
+        assert_eq!(ReflectiveBox.period(&BOUNDS), None);
+    }
+}
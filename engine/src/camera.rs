@@ -0,0 +1,210 @@
+use std::{fs, path::Path};
+
+use glam::Vec2;
+
+use crate::Error;
+
+const MIN_SCALE: f32 = 0.05;
+const MAX_SCALE: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub offset: Vec2,
+    pub scale: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Converts a cursor position (physical pixels, origin top-left, y down) into world
+    /// coordinates (origin at the window center, y up).
+    pub fn screen_to_world(&self, cursor: Vec2, screen: Vec2) -> Vec2 {
+        self.offset + Self::centered(cursor, screen) / self.scale
+    }
+
+    /// Inverse of [`Camera::screen_to_world`].
+    pub fn world_to_screen(&self, world: Vec2, screen: Vec2) -> Vec2 {
+        let centered = (world - self.offset) * self.scale;
+
+        Vec2::new(centered.x + screen.x / 2.0, screen.y / 2.0 - centered.y)
+    }
+
+    /// Moves the view by a cursor delta in physical pixels, so the world follows the cursor.
+    pub fn pan(&mut self, delta: Vec2) {
+        self.offset -= Vec2::new(delta.x, -delta.y) / self.scale;
+    }
+
+    /// Multiplies the scale by `factor`, keeping the world point under the cursor fixed.
+    pub fn zoom_at(&mut self, factor: f32, cursor: Vec2, screen: Vec2) {
+        let anchor = self.screen_to_world(cursor, screen);
+
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = anchor - Self::centered(cursor, screen) / self.scale;
+    }
+
+    /// View centered on the origin that shows a `size` world box with `margin` (as a fraction of
+    /// the screen) to spare.
+    pub fn fit(size: Vec2, screen: Vec2, margin: f32) -> Self {
+        let scale = (screen / size).min_element() * (1.0 - margin);
+
+        Self {
+            offset: Vec2::ZERO,
+            scale: scale.clamp(MIN_SCALE, MAX_SCALE),
+        }
+    }
+
+    #[inline]
+    fn centered(cursor: Vec2, screen: Vec2) -> Vec2 {
+        Vec2::new(cursor.x - screen.x / 2.0, screen.y / 2.0 - cursor.y)
+    }
+}
+
+/// Camera keyframes played back over time, loaded from a CSV of `time_s,offset_x,offset_y,scale`
+/// rows sorted by time. A header line is allowed.
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    keys: Vec<(f32, Camera)>,
+}
+
+impl CameraPath {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let fail = |message: String| Err(Error::CameraPath(message));
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return fail(format!("reading camera path {}: {e}", path.display())),
+        };
+        let mut keys = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let fields = line
+                .split(',')
+                .map(|f| f.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>();
+
+            match fields.as_deref() {
+                Ok(&[time, x, y, scale]) => keys.push((
+                    time,
+                    Camera {
+                        offset: Vec2::new(x, y),
+                        scale,
+                    },
+                )),
+                _ if n == 0 || line.trim().is_empty() => continue,
+                _ => {
+                    return fail(format!(
+                        "{}:{}: expected time_s,offset_x,offset_y,scale",
+                        path.display(),
+                        n + 1
+                    ));
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return fail(format!("camera path {} has no keyframes", path.display()));
+        }
+
+        if let Some((time, camera)) = keys
+            .iter()
+            .find(|(_, c)| c.scale <= 0.0 || !c.scale.is_finite())
+        {
+            return fail(format!(
+                "camera path {} has scale {} at {time} s; scales must be positive and finite",
+                path.display(),
+                camera.scale
+            ));
+        }
+
+        if keys.windows(2).any(|w| w[1].0 < w[0].0) {
+            return fail(format!(
+                "camera path {} is not sorted by time",
+                path.display()
+            ));
+        }
+
+        Ok(Self { keys })
+    }
+
+    /// Camera at time `t`, or `None` once the last keyframe has passed.
+    pub fn sample(&self, t: f32) -> Option<Camera> {
+        let (last_t, last) = *self.keys.last()?;
+
+        if t > last_t {
+            return None;
+        }
+
+        let i = self.keys.partition_point(|(kt, _)| *kt <= t);
+
+        if i == 0 {
+            return Some(self.keys[0].1);
+        }
+
+        let (t0, a) = self.keys[i - 1];
+        let Some(&(t1, b)) = self.keys.get(i) else {
+            return Some(last);
+        };
+        let s = (t - t0) / (t1 - t0);
+
+        // Zoom is interpolated geometrically so it feels uniform.
+        Some(Camera {
+            offset: a.offset.lerp(b.offset, s),
+            scale: a.scale * (b.scale / a.scale).powf(s),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, text: &str) -> Result<CameraPath, Error> {
+        let path = std::env::temp_dir().join(format!("tccd-{name}-{}.csv", std::process::id()));
+
+        fs::write(&path, text).unwrap();
+
+        let result = CameraPath::load(&path);
+
+        fs::remove_file(path).unwrap();
+        result
+    }
+
+    #[test]
+    fn loads_and_interpolates_keyframes() {
+        let path = load(
+            "path-ok",
+            "time_s,offset_x,offset_y,scale\n0,0,0,1\n2,10,0,4\n",
+        )
+        .unwrap();
+        let mid = path.sample(1.0).unwrap();
+
+        assert_eq!(mid.offset, Vec2::new(5.0, 0.0));
+        assert_eq!(mid.scale, 2.0);
+        assert_eq!(path.sample(3.0), None);
+    }
+
+    #[test]
+    fn rejects_non_positive_scales() {
+        for scale in ["0", "-1", "NaN"] {
+            let text = format!("0,0,0,1\n1,0,0,{scale}\n");
+
+            assert!(matches!(
+                load("path-scale", &text),
+                Err(Error::CameraPath(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_and_unsorted_files() {
+        for text in ["", "time_s\n", "0,0,0,1\n1,0\n", "1,0,0,1\n0,0,0,1\n"] {
+            assert!(matches!(load("path-bad", text), Err(Error::CameraPath(_))));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::{
+    Delay, Frame, ImageResult, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+use web_time::Instant;
+use wgpu::*;
+
+/// GPU-to-CPU copy of a single 8-bit RGBA/BGRA texture.
+pub struct Readback {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_row: u32,
+    bgra: bool,
+}
+
+impl Readback {
+    pub fn supports(format: TextureFormat) -> bool {
+        matches!(
+            format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        )
+    }
+
+    /// Records a copy of `texture` into a mappable buffer. The copy only happens once the
+    /// encoder is submitted.
+    pub fn encode(device: &Device, encoder: &mut CommandEncoder, texture: &Texture) -> Self {
+        let (width, height) = (texture.width(), texture.height());
+        let padded_row =
+            (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row * height) as BufferAddress,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            buffer,
+            width,
+            height,
+            padded_row,
+            bgra: matches!(
+                texture.format(),
+                TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+            ),
+        }
+    }
+
+    /// Blocks until the copy has completed and returns the pixels as RGBA.
+    pub fn read(self, device: &Device) -> Option<RgbaImage> {
+        let slice = self.buffer.slice(..);
+
+        slice.map_async(MapMode::Read, |_| {});
+
+        if let Err(e) = device.poll(PollType::wait_indefinitely()) {
+            log::error!("Failed to read back frame: {e}");
+            return None;
+        }
+
+        let row = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row * self.height as usize);
+
+        {
+            let data = slice.get_mapped_range();
+
+            for chunk in data.chunks(self.padded_row as usize) {
+                pixels.extend_from_slice(&chunk[..row]);
+            }
+        }
+
+        self.buffer.unmap();
+
+        if self.bgra {
+            pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+        }
+
+        RgbaImage::from_raw(self.width, self.height, pixels)
+    }
+}
+
+/// Writes captured frames as numbered PNGs on a background thread.
+pub struct FrameCapture {
+    dir: PathBuf,
+    next_index: u64,
+    sender: Option<Sender<(PathBuf, RgbaImage)>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameCapture {
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let (sender, receiver) = mpsc::channel::<(PathBuf, RgbaImage)>();
+        let worker = thread::spawn(move || {
+            for (path, image) in receiver {
+                if let Err(e) = image.save(&path) {
+                    log::error!("Failed to write {}: {e}", path.display());
+                }
+            }
+        });
+
+        Ok(Self {
+            dir,
+            next_index: 0,
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    pub fn submit(&mut self, image: RgbaImage) {
+        let path = self.dir.join(format!("frame_{:06}.png", self.next_index));
+
+        self.next_index += 1;
+
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((path, image));
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.sender.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+
+        log::info!(
+            "Captured {} frames to {}",
+            self.next_index,
+            self.dir.display()
+        );
+    }
+}
+
+/// Holds the last `capacity` rendered frames, written out as a looping GIF on request.
+pub struct ClipBuffer {
+    dir: PathBuf,
+    capacity: usize,
+    frames: VecDeque<(Instant, RgbaImage)>,
+    writers: Vec<JoinHandle<()>>,
+}
+
+impl ClipBuffer {
+    pub fn new(dir: PathBuf, capacity: usize) -> Self {
+        Self {
+            dir,
+            capacity: capacity.max(1),
+            frames: VecDeque::with_capacity(capacity),
+            writers: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, image: RgbaImage) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back((Instant::now(), image));
+    }
+
+    /// Encodes the buffered frames on a background thread, each shown for as long as it was
+    /// on screen. The buffer keeps filling meanwhile.
+    pub fn save(&mut self) {
+        if self.frames.len() < 2 {
+            log::warn!("Not enough frames buffered for a clip yet");
+            return;
+        }
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.dir.join(format!("clip_{stamp}.gif"));
+        let frames = self.frames.iter().cloned().collect::<Vec<_>>();
+
+        self.writers.retain(|w| !w.is_finished());
+        self.writers
+            .push(thread::spawn(move || match write_gif(&path, frames) {
+                Ok(()) => log::info!("Saved clip to {}", path.display()),
+                Err(e) => log::error!("Failed to write {}: {e}", path.display()),
+            }));
+    }
+}
+
+impl Drop for ClipBuffer {
+    fn drop(&mut self) {
+        for writer in self.writers.drain(..) {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn write_gif(path: &Path, frames: Vec<(Instant, RgbaImage)>) -> ImageResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    let times = frames.iter().map(|&(t, _)| t).collect::<Vec<_>>();
+    // The last frame has no successor, so it lasts as long as the one before it.
+    let last = times[times.len() - 1] - times[times.len() - 2];
+
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    for (k, (shown, image)) in frames.into_iter().enumerate() {
+        let duration = times.get(k + 1).map_or(last, |&t| t - shown);
+
+        encoder.encode_frame(Frame::from_parts(
+            image,
+            0,
+            0,
+            Delay::from_saturating_duration(duration),
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use image::{AnimationDecoder, Rgba, codecs::gif::GifDecoder};
+
+    use super::*;
+
+    #[test]
+    fn clip_keeps_only_the_latest_frames() {
+        let dir = std::env::temp_dir().join(format!("tccd-clip-{}", std::process::id()));
+        let mut clip = ClipBuffer::new(dir.clone(), 2);
+
+        for shade in [0, 128, 255] {
+            clip.push(RgbaImage::from_pixel(4, 4, Rgba([shade, 0, 0, 255])));
+        }
+
+        clip.save();
+        drop(clip);
+
+        let path = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+        let decoder = GifDecoder::new(BufReader::new(File::open(path).unwrap())).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].buffer().get_pixel(0, 0)[0] > 100);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
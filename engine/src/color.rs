@@ -0,0 +1,78 @@
+use std::{borrow::Cow, fmt};
+
+use crate::particle::Particle;
+
+/// How particles are colored when drawn. Only affects rendering; simulations keep their own
+/// colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Original,
+    Speed,
+    Energy,
+}
+
+impl ColorMode {
+    pub fn next(self) -> Self {
+        match self {
+            ColorMode::Original => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Energy,
+            ColorMode::Energy => ColorMode::Original,
+        }
+    }
+
+    /// Maps each particle's value onto a blue-to-red ramp. Values are normalized against twice
+    /// the frame mean, so a single outlier does not wash out everything else.
+    pub fn apply<'a>(&self, particles: Cow<'a, [Particle]>) -> Cow<'a, [Particle]> {
+        let value: fn(&Particle) -> f32 = match self {
+            ColorMode::Original => return particles,
+            ColorMode::Speed => |p| p.velocity.length(),
+            ColorMode::Energy => |p| 0.5 * p.mass * p.velocity.length_squared(),
+        };
+
+        if particles.is_empty() {
+            return particles;
+        }
+
+        let mean = particles.iter().map(value).sum::<f32>() / particles.len() as f32;
+        let scale = if mean > 0.0 { 0.5 / mean } else { 0.0 };
+        let mut particles = particles.into_owned();
+
+        for p in &mut particles {
+            let t = (value(p) * scale).clamp(0.0, 1.0);
+
+            p.color = hsl_to_rgb(240.0 * (1.0 - t), 0.9, 0.55);
+        }
+
+        Cow::Owned(particles)
+    }
+}
+
+impl fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColorMode::Original => "original",
+            ColorMode::Speed => "speed",
+            ColorMode::Energy => "energy",
+        })
+    }
+}
+
+/// `hue` in degrees, `saturation` and `lightness` in `0..=1`.
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let c = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = lightness - c / 2.0;
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [r + m, g + m, b + m]
+}
@@ -0,0 +1,219 @@
+use std::{iter, mem};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{Error, particle::Particle};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Body {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    dt: f32,
+    count: u32,
+    _pad: [f32; 2],
+}
+
+/// Experimental GPU-resident particle state: positions and velocities live in a storage buffer
+/// and ballistic motion is integrated by a compute shader. The CPU copy is only refreshed by an
+/// explicit `read_back`, so callers can skip it on frames without collisions.
+pub struct GpuParticles {
+    device: Device,
+    queue: Queue,
+    pipeline: ComputePipeline,
+    bind_group: BindGroup,
+    bodies: Buffer,
+    params: Buffer,
+    staging: Buffer,
+    count: u32,
+}
+
+impl GpuParticles {
+    /// Creates a headless device and uploads `particles`.
+    pub async fn new(particles: &[Particle]) -> Result<Self, Error> {
+        let instance = Instance::new(&InstanceDescriptor::default());
+        let adapter = match instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+        {
+            Ok(adapter) => adapter,
+            Err(_) => {
+                instance
+                    .request_adapter(&RequestAdapterOptions {
+                        power_preference: PowerPreference::LowPower,
+                        compatible_surface: None,
+                        force_fallback_adapter: true,
+                    })
+                    .await?
+            }
+        };
+
+        log::info!("Compute adapter {:?}", adapter.get_info().name);
+
+        let (device, queue) = adapter
+            .request_device(&DeviceDescriptor {
+                label: Some("Compute Device"),
+                required_limits: Limits::downlevel_defaults(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self::with_device(device, queue, particles))
+    }
+
+    /// Uploads `particles` to an existing device, e.g. the one handed to
+    /// [`crate::Simulation::init_gpu`].
+    pub fn with_device(device: Device, queue: Queue, particles: &[Particle]) -> Self {
+        let size = (particles.len().max(1) * mem::size_of::<Body>()) as BufferAddress;
+        let bodies = device.create_buffer(&BufferDescriptor {
+            label: Some("Body Buffer"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&BufferDescriptor {
+            label: Some("Body Staging Buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Integrate Params"),
+            contents: bytemuck::bytes_of(&Params {
+                dt: 0.0,
+                count: particles.len() as u32,
+                _pad: [0.0; 2],
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(include_wgsl!("compute.wgsl"));
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Integrate Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: Some("integrate"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Integrate Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: bodies.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        });
+
+        let gpu = Self {
+            device,
+            queue,
+            pipeline,
+            bind_group,
+            bodies,
+            params,
+            staging,
+            count: particles.len() as u32,
+        };
+
+        gpu.upload(particles);
+        gpu
+    }
+
+    /// Replaces the GPU state, e.g. after the CPU resolved collisions.
+    pub fn upload(&self, particles: &[Particle]) {
+        let bodies = particles
+            .iter()
+            .take(self.count as usize)
+            .map(|p| Body {
+                position: p.position.into(),
+                velocity: p.velocity.into(),
+            })
+            .collect::<Vec<_>>();
+
+        self.queue
+            .write_buffer(&self.bodies, 0, bytemuck::cast_slice(&bodies));
+    }
+
+    /// Moves every particle along its velocity for `dt` seconds without waiting for the GPU.
+    pub fn integrate(&self, dt: f32) {
+        self.queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params {
+                dt,
+                count: self.count,
+                _pad: [0.0; 2],
+            }),
+        );
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Integrate Encoder"),
+            });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Integrate Pass"),
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+    }
+
+    /// Blocks until all submitted work is done and copies positions and velocities into
+    /// `particles`.
+    pub fn read_back(&self, particles: &mut [Particle]) -> Result<(), Error> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Body Readback Encoder"),
+            });
+
+        encoder.copy_buffer_to_buffer(&self.bodies, 0, &self.staging, 0, self.staging.size());
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let slice = self.staging.slice(..);
+
+        slice.map_async(MapMode::Read, |_| {});
+        self.device.poll(PollType::wait_indefinitely())?;
+
+        {
+            let data = slice.get_mapped_range();
+            let bodies: &[Body] = bytemuck::cast_slice(&data);
+
+            for (p, b) in particles.iter_mut().zip(bodies) {
+                p.position = b.position.into();
+                p.velocity = b.velocity.into();
+            }
+        }
+
+        self.staging.unmap();
+
+        Ok(())
+    }
+}
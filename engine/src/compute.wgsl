@@ -0,0 +1,22 @@
+struct Body {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+};
+
+struct Params {
+    dt: f32,
+    count: u32,
+    _pad: vec2<f32>,
+};
+
+@group(0) @binding(0) var<storage, read_write> bodies: array<Body>;
+@group(0) @binding(1) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+
+    bodies[id.x].position += bodies[id.x].velocity * params.dt;
+}
@@ -0,0 +1,310 @@
+use std::path::PathBuf;
+
+use crate::{
+    Backend, Error, PresentMode, Theme, camera::CameraPath, instance::DEFAULT_INSTANCE_CAPACITY,
+};
+
+/// Like `anyhow::ensure!`, failing with [`Error::InvalidConfig`].
+macro_rules! ensure {
+    ($cond:expr, $($message:tt)+) => {
+        if !$cond {
+            return Err(Error::InvalidConfig(format!($($message)+)));
+        }
+    };
+}
+
+/// Largest window or render target side the builder accepts, in pixels.
+const MAX_WINDOW_SIZE: u32 = 16_384;
+const MAX_RENDER_SCALE: u32 = 4;
+/// Keeps the instance buffer within wgpu's default 256 MiB buffer size limit.
+const MAX_INSTANCE_CAPACITY: usize = 4_000_000;
+
+/// How [`crate::run_with`] sets up the window, renderer and clock. Build one with
+/// [`SimulationConfig::builder`].
+pub struct SimulationConfig {
+    pub(crate) title: String,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) fullscreen: bool,
+    pub(crate) monitor: usize,
+    pub(crate) dpi_scaling: bool,
+    pub(crate) run_unfocused: bool,
+    pub(crate) fps: u64,
+    pub(crate) msaa_samples: u32,
+    pub(crate) render_scale: u32,
+    pub(crate) instance_capacity: usize,
+    pub(crate) capture_dir: Option<PathBuf>,
+    pub(crate) clip_frames: usize,
+    pub(crate) backend: Backend,
+    pub(crate) present_mode: PresentMode,
+    pub(crate) fixed_dt: Option<f32>,
+    pub(crate) max_dt: f32,
+    pub(crate) camera_path: Option<CameraPath>,
+    pub(crate) theme: Theme,
+    pub(crate) clear_color: Option<[f32; 3]>,
+    pub(crate) srgb: bool,
+    pub(crate) heatmap: bool,
+    pub(crate) lock_bounds: bool,
+    pub(crate) seed: Option<u64>,
+}
+
+/// An 800x600 window at 60 fps with every optional feature off. A frame advances the simulation
+/// by at most 0.1 s.
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            title: "Simulation".to_string(),
+            width: 800,
+            height: 600,
+            fullscreen: false,
+            monitor: 0,
+            dpi_scaling: false,
+            run_unfocused: false,
+            fps: 60,
+            msaa_samples: 1,
+            render_scale: 1,
+            instance_capacity: DEFAULT_INSTANCE_CAPACITY,
+            capture_dir: None,
+            clip_frames: 0,
+            backend: Backend::default(),
+            present_mode: PresentMode::default(),
+            fixed_dt: None,
+            max_dt: 0.1,
+            camera_path: None,
+            theme: Theme::default(),
+            clear_color: None,
+            srgb: false,
+            heatmap: false,
+            lock_bounds: false,
+            seed: None,
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn builder() -> SimulationConfigBuilder {
+        SimulationConfigBuilder(Self::default())
+    }
+}
+
+/// Starts from [`SimulationConfig::default`]; [`SimulationConfigBuilder::build`] checks the
+/// result.
+pub struct SimulationConfigBuilder(SimulationConfig);
+
+impl SimulationConfigBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.0.title = title.into();
+        self
+    }
+
+    /// Initial window size in logical pixels, ignored in fullscreen.
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.0.width = width;
+        self.0.height = height;
+        self
+    }
+
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.0.fullscreen = fullscreen;
+        self
+    }
+
+    /// Monitor index used for fullscreen, falling back to the first one.
+    pub fn monitor(mut self, monitor: usize) -> Self {
+        self.0.monitor = monitor;
+        self
+    }
+
+    /// Measure the world in logical pixels so particles keep their physical size across monitors
+    /// with different scale factors. Otherwise one world unit is one physical pixel.
+    pub fn dpi_scaling(mut self, dpi_scaling: bool) -> Self {
+        self.0.dpi_scaling = dpi_scaling;
+        self
+    }
+
+    /// Keep stepping (and recording) while the window does not have focus.
+    pub fn run_unfocused(mut self, run_unfocused: bool) -> Self {
+        self.0.run_unfocused = run_unfocused;
+        self
+    }
+
+    pub fn fps(mut self, fps: u64) -> Self {
+        self.0.fps = fps;
+        self
+    }
+
+    pub fn msaa_samples(mut self, samples: u32) -> Self {
+        self.0.msaa_samples = samples;
+        self
+    }
+
+    /// Draw the scene at this multiple of the window resolution and box-filter it down, for
+    /// smoother small particles in captures. One draws at window resolution.
+    pub fn render_scale(mut self, scale: u32) -> Self {
+        self.0.render_scale = scale;
+        self
+    }
+
+    /// Particles the renderer allocates room for; any beyond are simulated but not drawn. Size it
+    /// to the particle count to save GPU memory on small runs.
+    pub fn instance_capacity(mut self, capacity: usize) -> Self {
+        self.0.instance_capacity = capacity;
+        self
+    }
+
+    pub fn capture_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.0.capture_dir = dir;
+        self
+    }
+
+    /// Keep this many of the latest frames to save as a GIF with X, into the capture directory
+    /// or else the working directory. Zero disables clips.
+    pub fn clip_frames(mut self, frames: usize) -> Self {
+        self.0.clip_frames = frames;
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.0.backend = backend;
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.0.present_mode = present_mode;
+        self
+    }
+
+    /// Step the simulation with this constant dt instead of the render frame time, interpolating
+    /// rendered positions between steps.
+    pub fn fixed_dt(mut self, dt: Option<f32>) -> Self {
+        self.0.fixed_dt = dt;
+        self
+    }
+
+    /// Most wall-clock seconds one frame advances the simulation by. Time beyond it, e.g. after
+    /// the window was dragged or the process stalled, is dropped rather than handed to a single
+    /// `step`. Applied before the time scale and never shorter than one frame at `fps`.
+    pub fn max_dt(mut self, dt: f32) -> Self {
+        self.0.max_dt = dt;
+        self
+    }
+
+    /// Keyframed camera path played from the start of the run.
+    pub fn camera_path(mut self, path: Option<CameraPath>) -> Self {
+        self.0.camera_path = path;
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.0.theme = theme;
+        self
+    }
+
+    /// Overrides the theme's background color.
+    pub fn clear_color(mut self, color: Option<[f32; 3]>) -> Self {
+        self.0.clear_color = color;
+        self
+    }
+
+    /// Render to an sRGB surface; colors are converted to linear in the shader so they look the
+    /// same as on the default non-sRGB surface.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.0.srgb = srgb;
+        self
+    }
+
+    /// Start with the collision heatmap shown; M toggles it.
+    pub fn heatmap(mut self, heatmap: bool) -> Self {
+        self.0.heatmap = heatmap;
+        self
+    }
+
+    /// Fix the bounds at the window size they start with. Later resizes scale the view to fit
+    /// them and leave the area around them empty instead of moving the walls.
+    pub fn lock_bounds(mut self, lock: bool) -> Self {
+        self.0.lock_bounds = lock;
+        self
+    }
+
+    /// Seed handed to the simulation through [`crate::context::Context::seed`]; a random one is
+    /// picked and logged if unset.
+    pub fn seed(mut self, seed: Option<u64>) -> Self {
+        self.0.seed = seed;
+        self
+    }
+
+    pub fn build(self) -> Result<SimulationConfig, Error> {
+        let config = self.0;
+
+        ensure!(config.fps > 0, "fps must be positive");
+        ensure!(
+            (1..=MAX_WINDOW_SIZE).contains(&config.width)
+                && (1..=MAX_WINDOW_SIZE).contains(&config.height),
+            "window size {}x{} is outside 1..={MAX_WINDOW_SIZE}",
+            config.width,
+            config.height
+        );
+        ensure!(
+            config.msaa_samples.is_power_of_two() && config.msaa_samples <= 16,
+            "MSAA sample count must be 1, 2, 4, 8 or 16, got {}",
+            config.msaa_samples
+        );
+        ensure!(
+            (1..=MAX_RENDER_SCALE).contains(&config.render_scale),
+            "render scale must be in 1..={MAX_RENDER_SCALE}, got {}",
+            config.render_scale
+        );
+        ensure!(
+            (1..=MAX_INSTANCE_CAPACITY).contains(&config.instance_capacity),
+            "instance capacity must be in 1..={MAX_INSTANCE_CAPACITY}, got {}",
+            config.instance_capacity
+        );
+        ensure!(
+            config.fixed_dt.is_none_or(|dt| dt.is_finite() && dt > 0.0),
+            "fixed dt must be positive, got {:?}",
+            config.fixed_dt
+        );
+        ensure!(
+            config.max_dt.is_finite() && config.max_dt > 0.0,
+            "max dt must be positive, got {}",
+            config.max_dt
+        );
+        ensure!(
+            config.backend.is_available(),
+            "the {:?} backend is not available on this platform",
+            config.backend
+        );
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = SimulationConfig::builder().build().unwrap();
+
+        assert_eq!((config.width, config.height, config.fps), (800, 600, 60));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let build = |b: SimulationConfigBuilder| b.build().is_err();
+
+        assert!(build(SimulationConfig::builder().fps(0)));
+        assert!(matches!(
+            SimulationConfig::builder().fps(0).build(),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(build(SimulationConfig::builder().window_size(0, 600)));
+        assert!(build(SimulationConfig::builder().msaa_samples(3)));
+        assert!(build(SimulationConfig::builder().render_scale(5)));
+        assert!(build(SimulationConfig::builder().instance_capacity(0)));
+        assert!(build(SimulationConfig::builder().fixed_dt(Some(-0.1))));
+        assert!(build(SimulationConfig::builder().max_dt(0.0)));
+        assert!(!build(SimulationConfig::builder().msaa_samples(4)));
+    }
+}
@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use crate::{Bounds, Simulation, StepResult};
+
+/// Where a step falls in the run, as seen from inside [`crate::Simulation::step`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInfo {
+    /// Steps finished before this one, so the first step is 0.
+    pub frame_index: u64,
+    /// Simulated time before this step, i.e. the sum of every earlier step's dt.
+    pub elapsed: Duration,
+    pub paused: bool,
+    pub time_scale: f32,
+    /// Wall-clock time since the previous frame, as in [`Context::frame_time`].
+    pub frame_time: Duration,
+}
+
+impl FrameInfo {
+    /// Frames per second at the current frame time, zero before the first frame.
+    pub fn fps(&self) -> f32 {
+        match self.frame_time.is_zero() {
+            true => 0.0,
+            false => 1.0 / self.frame_time.as_secs_f32(),
+        }
+    }
+}
+
+/// Time scales stepped through with [`Context::speed_up`] and [`Context::slow_down`].
+const TIMESCALES: [f32; 8] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Paused from the keyboard.
+    user_paused: bool,
+    /// Paused by the simulation through [`Context::pause`].
+    simulation_paused: bool,
+    time_scale: f32,
+    recording: bool,
+    seed: u64,
+    frame_index: u64,
+    elapsed: Duration,
+    frame_time: Duration,
+    render_time: Duration,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            user_paused: false,
+            simulation_paused: false,
+            time_scale: 1.0,
+            recording: true,
+            seed: 0,
+            frame_index: 0,
+            elapsed: Duration::ZERO,
+            frame_time: Duration::ZERO,
+            render_time: Duration::ZERO,
+        }
+    }
+}
+
+impl Context {
+    /// Pauses on the simulation's behalf, until the user or the simulation resumes.
+    pub fn pause(&mut self) {
+        self.simulation_paused = true;
+    }
+
+    /// Clears both the user's and the simulation's pause.
+    pub fn resume(&mut self) {
+        self.user_paused = false;
+        self.simulation_paused = false;
+    }
+
+    /// The user's pause key: resumes whoever paused, otherwise pauses.
+    pub fn toggle_pause(&mut self) {
+        match self.is_paused() {
+            true => self.resume(),
+            false => self.user_paused = true,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.user_paused || self.simulation_paused
+    }
+
+    /// Sets the multiplier applied to the wall-clock dt handed to `Simulation::step`.
+    pub fn set_timescale(&mut self, scale: f32) {
+        self.time_scale = scale.max(0.0);
+    }
+
+    pub fn timescale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Moves to the next faster preset time scale, if any.
+    pub fn speed_up(&mut self) {
+        if let Some(&scale) = TIMESCALES.iter().find(|&&s| s > self.time_scale) {
+            self.time_scale = scale;
+        }
+    }
+
+    /// Moves to the next slower preset time scale, if any.
+    pub fn slow_down(&mut self) {
+        if let Some(&scale) = TIMESCALES.iter().rev().find(|&&s| s < self.time_scale) {
+            self.time_scale = scale;
+        }
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = true;
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recording = false;
+    }
+
+    pub fn toggle_recording(&mut self) {
+        self.recording = !self.recording;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Seed for the simulation's random numbers: the configured one, or a random one the engine
+    /// logs at startup so the run can be repeated.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn frame(&self) -> FrameInfo {
+        FrameInfo {
+            frame_index: self.frame_index,
+            elapsed: self.elapsed,
+            paused: self.is_paused(),
+            time_scale: self.time_scale,
+            frame_time: self.frame_time,
+        }
+    }
+
+    /// Steps `simulation` by `dt` and counts the step into [`Context::frame`]. Everything that
+    /// drives a simulation steps it through here, so no driver can forget the count.
+    pub fn run_step<S: Simulation + ?Sized>(
+        &mut self,
+        simulation: &mut S,
+        dt: f32,
+        bounds: Bounds,
+    ) -> StepResult {
+        let result = simulation.step(self, dt, bounds);
+
+        self.count_step(dt);
+        result
+    }
+
+    fn count_step(&mut self, dt: f32) {
+        self.frame_index += 1;
+        self.elapsed += Duration::from_secs_f64(dt.max(0.0) as f64);
+    }
+
+    /// Wall-clock time since the previous frame. While the steps of a fixed-timestep frame run,
+    /// each sees an equal share of it.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Set by the engine before stepping; headless drivers set it themselves.
+    pub fn set_frame_time(&mut self, time: Duration) {
+        self.frame_time = time;
+    }
+
+    /// Time the previous frame spent uploading and drawing, zero without a window. Shared out
+    /// between the steps of a fixed-timestep frame like [`Context::frame_time`].
+    pub fn render_time(&self) -> Duration {
+        self.render_time
+    }
+
+    pub fn set_render_time(&mut self, time: Duration) {
+        self.render_time = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_resumes_a_simulation_pause() {
+        let mut ctx = Context::default();
+
+        ctx.pause();
+        ctx.toggle_pause();
+        assert!(!ctx.is_paused());
+
+        ctx.toggle_pause();
+        ctx.pause();
+        ctx.toggle_pause();
+        assert!(!ctx.is_paused());
+
+        ctx.toggle_pause();
+        assert!(ctx.is_paused());
+    }
+
+    #[test]
+    fn steps_through_preset_timescales() {
+        let mut ctx = Context::default();
+
+        ctx.slow_down();
+        assert_eq!(ctx.timescale(), 0.5);
+
+        ctx.set_timescale(0.3);
+        ctx.slow_down();
+        ctx.slow_down();
+        assert_eq!(ctx.timescale(), 0.1);
+
+        ctx.set_timescale(12.0);
+        ctx.speed_up();
+        ctx.speed_up();
+        assert_eq!(ctx.timescale(), 16.0);
+    }
+}
@@ -0,0 +1,32 @@
+struct Params {
+	scale: u32,
+	_pad0: u32,
+	_pad1: u32,
+	_pad2: u32,
+};
+
+@group(0) @binding(0) var src: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> P: Params;
+
+// One triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+	let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+
+	return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Box filter: every target pixel is the mean of its `scale` x `scale` source block.
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+	let base = vec2<u32>(pos.xy) * P.scale;
+	var sum = vec4<f32>(0.0);
+
+	for (var y = 0u; y < P.scale; y++) {
+		for (var x = 0u; x < P.scale; x++) {
+			sum += textureLoad(src, base + vec2<u32>(x, y), 0);
+		}
+	}
+
+	return sum / f32(P.scale * P.scale);
+}
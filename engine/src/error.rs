@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// Everything the engine's public API can fail with, so callers can tell a missing GPU (and
+/// e.g. fall back to a headless run) from a broken event loop or a bad setting.
+#[derive(Debug)]
+pub enum Error {
+    /// The window cannot be rendered to.
+    SurfaceCreation(wgpu::CreateSurfaceError),
+    /// Not even a software adapter is available.
+    AdapterNotFound(wgpu::RequestAdapterError),
+    DeviceRequest(wgpu::RequestDeviceError),
+    /// Waiting for the GPU failed, e.g. because the device was lost.
+    Poll(wgpu::PollError),
+    EventLoop(winit::error::EventLoopError),
+    /// A [`crate::SimulationConfigBuilder`] setting is out of range.
+    InvalidConfig(String),
+    /// A [`crate::camera::CameraPath`] file cannot be read or parsed.
+    CameraPath(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SurfaceCreation(e) => write!(f, "failed to create a surface: {e}"),
+            Error::AdapterNotFound(e) => write!(f, "no GPU adapter found: {e}"),
+            Error::DeviceRequest(e) => write!(f, "failed to request a GPU device: {e}"),
+            Error::Poll(e) => write!(f, "failed to wait for the GPU: {e}"),
+            Error::EventLoop(e) => write!(f, "event loop failed: {e}"),
+            Error::InvalidConfig(message) => write!(f, "invalid configuration: {message}"),
+            Error::CameraPath(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SurfaceCreation(e) => Some(e),
+            Error::AdapterNotFound(e) => Some(e),
+            Error::DeviceRequest(e) => Some(e),
+            Error::Poll(e) => Some(e),
+            Error::EventLoop(e) => Some(e),
+            Error::InvalidConfig(_) | Error::CameraPath(_) => None,
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Error::SurfaceCreation(e)
+    }
+}
+
+impl From<wgpu::RequestAdapterError> for Error {
+    fn from(e: wgpu::RequestAdapterError) -> Self {
+        Error::AdapterNotFound(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::DeviceRequest(e)
+    }
+}
+
+impl From<wgpu::PollError> for Error {
+    fn from(e: wgpu::PollError) -> Self {
+        Error::Poll(e)
+    }
+}
+
+impl From<winit::error::EventLoopError> for Error {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        Error::EventLoop(e)
+    }
+}
@@ -0,0 +1,356 @@
+//! Event-loop decisions that do not need a window or GPU: frame pacing, whether a frame steps
+//! the simulation, and the bounds a window size maps to.
+
+use std::time::Duration;
+
+use web_time::Instant;
+use winit::dpi::PhysicalSize;
+
+use crate::{Bounds, context::Context};
+
+/// Redraw rate while nothing is changing.
+const IDLE_FPS: f64 = 4.0;
+
+pub struct FrameClock {
+    interval: Duration,
+    idle_interval: Duration,
+    last_frame: Instant,
+}
+
+impl FrameClock {
+    pub fn new(fps: u64, now: Instant) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / fps.max(1) as f64),
+            idle_interval: Duration::from_secs_f64(1.0 / IDLE_FPS),
+            last_frame: now,
+        }
+    }
+
+    /// `None` when a frame is due, otherwise the instant the next one is. Idle frames are spaced
+    /// at `IDLE_FPS` unless the regular rate is already lower.
+    pub fn next_frame(&self, now: Instant, idle: bool) -> Option<Instant> {
+        let interval = match idle {
+            true => self.interval.max(self.idle_interval),
+            false => self.interval,
+        };
+        let due = self.last_frame + interval;
+
+        (now < due).then_some(due)
+    }
+
+    /// Starts a frame at `now`, returning the seconds since the previous one.
+    pub fn tick(&mut self, now: Instant) -> f32 {
+        let dt = now.saturating_duration_since(self.last_frame).as_secs_f32();
+
+        self.last_frame = now;
+        dt
+    }
+
+    /// Restarts timing without producing a long first dt, e.g. once the window exists.
+    pub fn reset(&mut self, now: Instant) {
+        self.last_frame = now;
+    }
+}
+
+/// Whether this frame advances the simulation. A pending single step is consumed.
+pub fn should_step(
+    ctx: &Context,
+    focused: bool,
+    run_unfocused: bool,
+    step_once: &mut bool,
+) -> bool {
+    if !(focused || run_unfocused) {
+        return false;
+    }
+
+    !ctx.is_paused() || std::mem::take(step_once)
+}
+
+/// Whether nothing will change until the next input: paused (and not single-stepping this
+/// frame) or the simulation reports itself quiescent.
+pub fn is_idle(ctx: &Context, stepped: bool, quiescent: bool) -> bool {
+    (ctx.is_paused() && !stepped) || quiescent
+}
+
+/// Simulation bounds for a window of `size` physical pixels at `dpi` physical pixels per world
+/// unit, or `None` while it is minimized.
+pub fn bounds(PhysicalSize { width, height }: PhysicalSize<u32>, dpi: f32) -> Option<Bounds> {
+    (width > 0 && height > 0).then_some(Bounds {
+        width: width as f32 / dpi,
+        height: height as f32 / dpi,
+    })
+}
+
+/// World-space size of the window. Follows resizes, but keeps its value when the scale factor
+/// changes: the window is resized to match, and rounding that size to whole pixels must not
+/// move the walls. Once locked it ignores resizes too.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldBounds {
+    bounds: Bounds,
+    /// Physical size requested by the last scale-factor change, while the window still has it.
+    pinned: Option<PhysicalSize<u32>>,
+    locked: bool,
+}
+
+impl WorldBounds {
+    pub fn new(bounds: Bounds) -> Self {
+        Self {
+            bounds,
+            pinned: None,
+            locked: false,
+        }
+    }
+
+    pub fn get(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Keeps the current bounds from now on, whatever size the window takes.
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Bounds for a window of `size` at `dpi`. While minimized the last bounds are kept and
+    /// `None` is returned.
+    pub fn update(&mut self, size: PhysicalSize<u32>, dpi: f32) -> Option<Bounds> {
+        if self.pinned == Some(size) || self.locked {
+            return (size.width > 0 && size.height > 0).then_some(self.bounds);
+        }
+
+        let bounds = bounds(size, dpi)?;
+
+        self.pinned = None;
+        self.bounds = bounds;
+
+        Some(bounds)
+    }
+
+    /// Physical size that keeps the current bounds at the new `dpi`.
+    pub fn rescale(&mut self, dpi: f32) -> PhysicalSize<u32> {
+        let size = PhysicalSize::new(
+            (self.bounds.width * dpi).round().max(1.0) as u32,
+            (self.bounds.height * dpi).round().max(1.0) as u32,
+        );
+
+        self.pinned = Some(size);
+        size
+    }
+}
+
+/// Simulated against wall-clock time over the frames that stepped, so pauses do not count.
+#[derive(Debug, Default)]
+pub struct RealTime {
+    sim_s: f64,
+    wall_s: f64,
+}
+
+impl RealTime {
+    pub fn add(&mut self, wall_s: f32, sim_s: f32) {
+        self.wall_s += wall_s as f64;
+        self.sim_s += sim_s as f64;
+    }
+
+    /// Simulated seconds per wall-clock second, once anything has run.
+    pub fn factor(&self) -> Option<f64> {
+        (self.wall_s > 0.0).then(|| self.sim_s / self.wall_s)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Simulated {:.2} s in {:.2} s of wall time ({:.3}x real time)",
+            self.sim_s,
+            self.wall_s,
+            self.factor().unwrap_or(0.0)
+        )
+    }
+}
+
+/// Totals over a windowed run, handed back from [`crate::run_with`] so benchmark scripts do not
+/// have to parse the log.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunStats {
+    /// Frames the window went through, stepped or not.
+    pub frames: u64,
+    /// Wall time covered by those frames.
+    pub elapsed: Duration,
+    /// Frames that advanced the simulation.
+    pub stepped_frames: u64,
+    /// Wall time spent in the CPU and GPU steps of those frames.
+    pub step_time: Duration,
+    /// Stepped frames that came after a stall longer than the max dt, whose time was dropped.
+    pub dropped_frames: u64,
+}
+
+impl RunStats {
+    pub fn average_fps(&self) -> f64 {
+        match self.elapsed.is_zero() {
+            true => 0.0,
+            false => self.frames as f64 / self.elapsed.as_secs_f64(),
+        }
+    }
+
+    pub fn average_step_time(&self) -> Duration {
+        self.step_time.div_f64(self.stepped_frames.max(1) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_stats_average_over_their_frames() {
+        let stats = RunStats {
+            frames: 120,
+            elapsed: Duration::from_secs(2),
+            stepped_frames: 100,
+            step_time: Duration::from_millis(500),
+            dropped_frames: 0,
+        };
+
+        assert_eq!(stats.average_fps(), 60.0);
+        assert_eq!(stats.average_step_time(), Duration::from_millis(5));
+        assert_eq!(RunStats::default().average_fps(), 0.0);
+    }
+
+    #[test]
+    fn clock_throttles_to_fps() {
+        let start = Instant::now();
+        let clock = FrameClock::new(50, start);
+
+        assert_eq!(
+            clock.next_frame(start + Duration::from_millis(5), false),
+            Some(start + Duration::from_millis(20))
+        );
+        assert_eq!(
+            clock.next_frame(start + Duration::from_millis(20), false),
+            None
+        );
+    }
+
+    #[test]
+    fn idle_clock_slows_down() {
+        let start = Instant::now();
+        let clock = FrameClock::new(50, start);
+
+        assert_eq!(
+            clock.next_frame(start + Duration::from_millis(20), true),
+            Some(start + Duration::from_millis(250))
+        );
+        assert_eq!(
+            FrameClock::new(1, start).next_frame(start, true),
+            Some(start + Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn idle_when_paused_or_at_rest() {
+        let mut ctx = Context::default();
+
+        assert!(!is_idle(&ctx, true, false));
+        assert!(is_idle(&ctx, true, true));
+
+        ctx.pause();
+
+        assert!(is_idle(&ctx, false, false));
+        assert!(!is_idle(&ctx, true, false), "a single step must be drawn");
+    }
+
+    #[test]
+    fn tick_measures_dt_between_frames() {
+        let start = Instant::now();
+        let mut clock = FrameClock::new(30, start);
+
+        assert!((clock.tick(start + Duration::from_millis(40)) - 0.04).abs() < 1e-6);
+        assert!((clock.tick(start + Duration::from_millis(50)) - 0.01).abs() < 1e-6);
+        assert_eq!(clock.tick(start), 0.0);
+    }
+
+    #[test]
+    fn zero_fps_does_not_panic() {
+        let start = Instant::now();
+
+        assert_eq!(
+            FrameClock::new(0, start).next_frame(start, false),
+            Some(start + Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn unfocused_window_pauses_unless_configured() {
+        let ctx = Context::default();
+        let mut step_once = false;
+
+        assert!(should_step(&ctx, true, false, &mut step_once));
+        assert!(!should_step(&ctx, false, false, &mut step_once));
+        assert!(should_step(&ctx, false, true, &mut step_once));
+    }
+
+    #[test]
+    fn paused_steps_once_per_request() {
+        let mut ctx = Context::default();
+        let mut step_once = true;
+
+        ctx.pause();
+
+        assert!(!should_step(&ctx, false, false, &mut step_once));
+        assert!(step_once, "an unfocused frame must not eat the single step");
+        assert!(should_step(&ctx, true, false, &mut step_once));
+        assert!(!should_step(&ctx, true, false, &mut step_once));
+    }
+
+    #[test]
+    fn resize_maps_to_bounds() {
+        let b = bounds(PhysicalSize::new(1280, 720), 1.0).unwrap();
+        let hidpi = bounds(PhysicalSize::new(1280, 720), 2.0).unwrap();
+
+        assert_eq!((b.width, b.height), (1280.0, 720.0));
+        assert_eq!((hidpi.width, hidpi.height), (640.0, 360.0));
+        assert!(bounds(PhysicalSize::new(0, 720), 1.0).is_none());
+    }
+
+    #[test]
+    fn world_bounds_survive_scale_changes() {
+        let mut world = WorldBounds::new(Bounds {
+            width: 0.0,
+            height: 0.0,
+        });
+
+        world.update(PhysicalSize::new(1001, 601), 1.0);
+
+        // 1001 x 1.25 rounds, so recomputing from the new size would shift the walls.
+        let size = world.rescale(1.25);
+        let kept = world.update(size, 1.25).unwrap();
+
+        assert_eq!(size, PhysicalSize::new(1251, 751));
+        assert_eq!((kept.width, kept.height), (1001.0, 601.0));
+
+        // Minimizing keeps them too; a real resize afterwards takes over again.
+        assert!(world.update(PhysicalSize::new(0, 0), 1.25).is_none());
+        assert_eq!(world.get().width, 1001.0);
+
+        let resized = world.update(PhysicalSize::new(1500, 750), 1.25).unwrap();
+
+        assert_eq!((resized.width, resized.height), (1200.0, 600.0));
+    }
+
+    #[test]
+    fn locked_bounds_ignore_resizes() {
+        let mut world = WorldBounds::new(Bounds {
+            width: 0.0,
+            height: 0.0,
+        });
+
+        world.update(PhysicalSize::new(800, 600), 1.0);
+        world.lock();
+
+        let kept = world.update(PhysicalSize::new(1920, 1080), 1.0).unwrap();
+
+        assert_eq!((kept.width, kept.height), (800.0, 600.0));
+        assert!(world.update(PhysicalSize::new(0, 0), 1.0).is_none());
+    }
+}
@@ -0,0 +1,400 @@
+use std::{borrow::Cow, ops::Range};
+
+use glam::Vec2;
+
+#[cfg(feature = "render")]
+use crate::KeyCode;
+use crate::{
+    Bounds, Simulation, StepResult, context::Context, line::Line, particle::Particle, shape::Shape,
+};
+
+const DIVIDER_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// A world-space rectangle that a range of instances is clipped to when drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub instances: Range<u32>,
+}
+
+/// Runs several simulations of the same kind at once, each in its own cell of a grid that fills
+/// the window. Like [`crate::split::SplitScreen`] they share the camera, pause state and time
+/// step; each cell is clipped to its own viewport and labelled with its name and HUD. Cell `i` is
+/// initialized with the run's seed plus `i`.
+pub struct Grid<S> {
+    cells: Vec<(String, S)>,
+    bounds: Bounds,
+    particles: Vec<Particle>,
+    viewports: Vec<Viewport>,
+}
+
+impl<S: Simulation> Grid<S> {
+    pub fn new(cells: Vec<(String, S)>) -> Self {
+        Self {
+            cells,
+            bounds: Bounds {
+                width: 0.0,
+                height: 0.0,
+            },
+            particles: Vec::new(),
+            viewports: Vec::new(),
+        }
+    }
+
+    pub fn into_cells(self) -> Vec<(String, S)> {
+        self.cells
+    }
+
+    /// Columns and rows for the cells, keeping each cell as close to square as possible.
+    fn shape(&self) -> (usize, usize) {
+        shape(self.cells.len(), self.bounds)
+    }
+
+    fn cell_bounds(&self) -> Bounds {
+        let (cols, rows) = self.shape();
+
+        Bounds {
+            width: self.bounds.width / cols as f32,
+            height: self.bounds.height / rows as f32,
+        }
+    }
+
+    /// World position of cell `i`'s origin.
+    fn center(&self, i: usize) -> Vec2 {
+        let (cols, _) = self.shape();
+        let cell = self.cell_bounds();
+        let (col, row) = ((i % cols) as f32, (i / cols) as f32);
+
+        Vec2::new(
+            -self.bounds.width / 2.0 + (col + 0.5) * cell.width,
+            self.bounds.height / 2.0 - (row + 0.5) * cell.height,
+        )
+    }
+
+    /// Index of the cell containing `world`, if any.
+    fn cell_at(&self, world: Vec2) -> Option<usize> {
+        let half = Vec2::new(self.cell_bounds().width, self.cell_bounds().height) / 2.0;
+
+        (0..self.cells.len()).find(|&i| {
+            let d = (world - self.center(i)).abs();
+            d.x <= half.x && d.y <= half.y
+        })
+    }
+
+    fn collect_particles(&mut self) {
+        let cell = self.cell_bounds();
+        let half = Vec2::new(cell.width, cell.height) / 2.0;
+
+        self.particles.clear();
+        self.viewports.clear();
+
+        for i in 0..self.cells.len() {
+            let center = self.center(i);
+            let start = self.particles.len() as u32;
+
+            self.particles
+                .extend(self.cells[i].1.particles().iter().map(|p| Particle {
+                    position: p.position + center,
+                    ..*p
+                }));
+            self.viewports.push(Viewport {
+                min: center - half,
+                max: center + half,
+                instances: start..self.particles.len() as u32,
+            });
+        }
+    }
+}
+
+fn shape(count: usize, bounds: Bounds) -> (usize, usize) {
+    let count = count.max(1);
+    let aspect = bounds.width / bounds.height;
+    let cols = ((count as f32 * aspect).sqrt().round() as usize).clamp(1, count);
+
+    (cols, count.div_ceil(cols))
+}
+
+impl<S: Simulation> Simulation for Grid<S> {
+    fn init(&mut self, ctx: &mut Context, bounds: Bounds) {
+        self.bounds = bounds;
+
+        let cell = self.cell_bounds();
+        let seed = ctx.seed();
+
+        for (i, (_, sim)) in self.cells.iter_mut().enumerate() {
+            ctx.set_seed(seed.wrapping_add(i as u64));
+            sim.init(ctx, cell);
+        }
+
+        ctx.set_seed(seed);
+        self.collect_particles();
+    }
+
+    fn step(&mut self, ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult {
+        self.bounds = bounds;
+
+        let cell = self.cell_bounds();
+        let mut result = StepResult::Continue;
+
+        for (_, sim) in &mut self.cells {
+            result = result.or(sim.step(ctx, dt, cell));
+        }
+
+        self.collect_particles();
+        result
+    }
+
+    #[cfg(feature = "render")]
+    fn init_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        for (_, sim) in &mut self.cells {
+            sim.init_gpu(device, queue);
+        }
+    }
+
+    #[cfg(feature = "render")]
+    fn step_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, dt: f32) {
+        for (_, sim) in &mut self.cells {
+            sim.step_gpu(device, queue, dt);
+        }
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn poll(&mut self) -> bool {
+        let mut changed = false;
+
+        for (_, sim) in &mut self.cells {
+            changed |= sim.poll();
+        }
+
+        if changed {
+            self.collect_particles();
+        }
+
+        changed
+    }
+
+    #[cfg(feature = "render")]
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        for (name, sim) in &mut self.cells {
+            ui.collapsing(name.as_str(), |ui| sim.debug_ui(ui));
+        }
+    }
+
+    #[cfg(feature = "render")]
+    fn ui(&mut self, ctx: &egui::Context) {
+        for (_, sim) in &mut self.cells {
+            sim.ui(ctx);
+        }
+    }
+
+    #[cfg(feature = "render")]
+    fn key_pressed(&mut self, key: KeyCode) {
+        for (_, sim) in &mut self.cells {
+            sim.key_pressed(key);
+        }
+    }
+
+    fn clicked(&mut self, world: Vec2) {
+        if let Some(i) = self.cell_at(world) {
+            let center = self.center(i);
+            self.cells[i].1.clicked(world - center);
+        }
+    }
+
+    fn selected_rect(&mut self, min: Vec2, max: Vec2) {
+        if let Some(i) = self.cell_at((min + max) / 2.0) {
+            let center = self.center(i);
+            self.cells[i].1.selected_rect(min - center, max - center);
+        }
+    }
+
+    fn focus(&self) -> Option<Vec2> {
+        self.cells
+            .iter()
+            .enumerate()
+            .find_map(|(i, (_, sim))| sim.focus().map(|f| f + self.center(i)))
+    }
+
+    fn take_impacts(&mut self) -> Vec<Vec2> {
+        let mut impacts = Vec::new();
+
+        for i in 0..self.cells.len() {
+            let center = self.center(i);
+
+            impacts.extend(
+                self.cells[i]
+                    .1
+                    .take_impacts()
+                    .into_iter()
+                    .map(|p| p + center),
+            );
+        }
+
+        impacts
+    }
+
+    fn quiescent(&self) -> bool {
+        self.cells.iter().all(|(_, sim)| sim.quiescent())
+    }
+
+    fn on_exit(&mut self) {
+        for (_, sim) in &mut self.cells {
+            sim.on_exit();
+        }
+    }
+
+    fn overlay_instances(&self) -> Cow<'_, [Particle]> {
+        Cow::Owned(
+            self.cells
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (_, sim))| {
+                    let center = self.center(i);
+
+                    sim.overlay_instances()
+                        .iter()
+                        .map(|p| Particle {
+                            position: p.position + center,
+                            ..*p
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+        )
+    }
+
+    fn scenery(&self) -> Vec<Shape> {
+        self.cells
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (_, sim))| {
+                let center = self.center(i);
+
+                sim.scenery().into_iter().map(move |s| s.translated(center))
+            })
+            .collect()
+    }
+
+    fn debug_lines(&self) -> Vec<Line> {
+        let (cols, rows) = self.shape();
+        let cell = self.cell_bounds();
+        let (half_w, half_h) = self.bounds.half_extents();
+        let mut lines = Vec::new();
+
+        for (i, (_, sim)) in self.cells.iter().enumerate() {
+            let center = self.center(i);
+
+            lines.extend(sim.debug_lines().into_iter().map(|l| Line {
+                start: l.start + center,
+                end: l.end + center,
+                ..l
+            }));
+        }
+
+        for col in 1..cols {
+            let x = -half_w + col as f32 * cell.width;
+            lines.push(Line::new(
+                Vec2::new(x, -half_h),
+                Vec2::new(x, half_h),
+                DIVIDER_COLOR,
+            ));
+        }
+
+        for row in 1..rows {
+            let y = half_h - row as f32 * cell.height;
+            lines.push(Line::new(
+                Vec2::new(-half_w, y),
+                Vec2::new(half_w, y),
+                DIVIDER_COLOR,
+            ));
+        }
+
+        lines
+    }
+
+    fn viewports(&self) -> Vec<Viewport> {
+        self.viewports.clone()
+    }
+
+    fn labels(&self) -> Vec<(Vec2, String)> {
+        let cell = self.cell_bounds();
+        let top_left = Vec2::new(-cell.width, cell.height) / 2.0;
+
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, (name, sim))| {
+                let text = std::iter::once(name.clone())
+                    .chain(sim.hud())
+                    .collect::<Vec<_>>();
+
+                (self.center(i) + top_left, text.join("\n"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_follows_window_aspect() {
+        let wide = Bounds {
+            width: 1600.0,
+            height: 900.0,
+        };
+        let square = Bounds {
+            width: 800.0,
+            height: 800.0,
+        };
+
+        assert_eq!(shape(1, wide), (1, 1));
+        assert_eq!(shape(4, square), (2, 2));
+        assert_eq!(shape(6, wide), (3, 2));
+        assert_eq!(shape(3, square), (2, 2));
+        assert_eq!(shape(2, wide), (2, 1));
+    }
+
+    /// Remembers the seed it was initialized with.
+    struct Seeded(u64);
+
+    impl Simulation for Seeded {
+        fn init(&mut self, ctx: &mut Context, _bounds: Bounds) {
+            self.0 = ctx.seed();
+        }
+
+        fn step(&mut self, _ctx: &mut Context, _dt: f32, _bounds: Bounds) -> StepResult {
+            StepResult::Continue
+        }
+
+        fn particles(&self) -> &[Particle] {
+            &[]
+        }
+    }
+
+    #[test]
+    fn seeds_cells_consecutively() {
+        let cells = (0..3).map(|i| (i.to_string(), Seeded(0))).collect();
+        let mut grid = Grid::new(cells);
+        let mut ctx = Context::default();
+
+        ctx.set_seed(u64::MAX);
+        grid.init(
+            &mut ctx,
+            Bounds {
+                width: 300.0,
+                height: 100.0,
+            },
+        );
+
+        let seeds: Vec<_> = grid.into_cells().iter().map(|(_, sim)| sim.0).collect();
+
+        assert_eq!(seeds, [u64::MAX, 0, 1]);
+        assert_eq!(ctx.seed(), u64::MAX);
+    }
+}
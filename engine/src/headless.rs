@@ -0,0 +1,115 @@
+//! Steps a [`Simulation`] without a window or GPU, e.g. for batch runs and soak tests. Unlike
+//! the windowed runner, it advances by a constant dt regardless of wall time.
+
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::{Bounds, Simulation, StepResult, context::Context};
+
+pub struct Headless<S> {
+    simulation: S,
+    ctx: Context,
+    bounds: Bounds,
+    dt: f32,
+    last_step: Option<Instant>,
+}
+
+impl<S: Simulation> Headless<S> {
+    /// Seeds the context with `seed` and initializes `simulation` for `bounds`.
+    pub fn new(mut simulation: S, bounds: Bounds, dt: f32, seed: u64) -> Self {
+        let mut ctx = Context::default();
+
+        ctx.set_seed(seed);
+        simulation.init(&mut ctx, bounds);
+
+        Self {
+            simulation,
+            ctx,
+            bounds,
+            dt,
+            last_step: None,
+        }
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        // The previous step's time stands in for this one's, as a frame's does in a window.
+        let frame_time = self.last_step.map_or(Duration::ZERO, |t| t.elapsed());
+
+        self.ctx.set_frame_time(frame_time);
+        self.last_step = Some(Instant::now());
+
+        self.ctx
+            .run_step(&mut self.simulation, self.dt, self.bounds)
+    }
+
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    pub fn simulation_mut(&mut self) -> &mut S {
+        &mut self.simulation
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Calls `on_exit` and hands back the simulation in its final state.
+    pub fn finish(mut self) -> S {
+        self.simulation.on_exit();
+        self.simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::Particle;
+
+    /// Counts its steps and remembers the seed it was initialized with.
+    #[derive(Default)]
+    struct Counter {
+        seed: u64,
+        steps: usize,
+    }
+
+    impl Simulation for Counter {
+        fn init(&mut self, ctx: &mut Context, _bounds: Bounds) {
+            self.seed = ctx.seed();
+        }
+
+        fn step(&mut self, _ctx: &mut Context, _dt: f32, _bounds: Bounds) -> StepResult {
+            self.steps += 1;
+            StepResult::Continue
+        }
+
+        fn particles(&self) -> &[Particle] {
+            &[]
+        }
+    }
+
+    #[test]
+    fn steps_by_a_constant_dt() {
+        let bounds = Bounds {
+            width: 100.0,
+            height: 100.0,
+        };
+        let mut run = Headless::new(Counter::default(), bounds, 0.25, 7);
+
+        for _ in 0..3 {
+            run.step();
+        }
+
+        assert_eq!(run.context().frame().frame_index, 3);
+        assert_eq!(run.context().frame().elapsed.as_secs_f32(), 0.75);
+
+        let sim = run.finish();
+
+        assert_eq!((sim.seed, sim.steps), (7, 3));
+    }
+}
@@ -0,0 +1,314 @@
+//! Collision density drawn as a translucent layer under the particles.
+
+use glam::Vec2;
+use wgpu::*;
+
+use crate::{Bounds, color::hsl_to_rgb};
+
+/// World units covered by one density cell along each axis, before capping.
+const CELL_SIZE: f32 = 10.0;
+const MAX_CELLS: usize = 256;
+/// Sim seconds for a cell to lose half its density.
+const HALF_LIFE: f32 = 2.0;
+/// Opacity of the densest cell.
+const MAX_ALPHA: f32 = 0.6;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    half_extents: [f32; 2],
+    _pad: [f32; 2],
+}
+
+/// Low-resolution grid over the bounds counting recent impacts, decaying over time.
+pub struct Heatmap {
+    bounds: Bounds,
+    cols: usize,
+    rows: usize,
+    /// Row-major from the top-left corner, matching texture rows.
+    density: Vec<f32>,
+}
+
+impl Heatmap {
+    pub fn new(bounds: Bounds) -> Self {
+        let cells = |extent: f32| ((extent / CELL_SIZE).ceil() as usize).clamp(1, MAX_CELLS);
+        let (cols, rows) = (cells(bounds.width), cells(bounds.height));
+
+        Self {
+            bounds,
+            cols,
+            rows,
+            density: vec![0.0; cols * rows],
+        }
+    }
+
+    /// Starts over when the bounds change size, since old impacts no longer line up.
+    pub fn fit(&mut self, bounds: Bounds) {
+        if bounds.width != self.bounds.width || bounds.height != self.bounds.height {
+            *self = Self::new(bounds);
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.cols as u32, self.rows as u32)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Counts an impact at a world position; positions outside the bounds are dropped.
+    pub fn add(&mut self, world: Vec2) {
+        let (hw, hh) = self.bounds.half_extents();
+        let col = (world.x + hw) / self.bounds.width * self.cols as f32;
+        let row = (hh - world.y) / self.bounds.height * self.rows as f32;
+
+        if col >= 0.0 && row >= 0.0 && (col as usize) < self.cols && (row as usize) < self.rows {
+            self.density[row as usize * self.cols + col as usize] += 1.0;
+        }
+    }
+
+    pub fn decay(&mut self, dt: f32) {
+        let factor = 0.5f32.powf(dt / HALF_LIFE);
+
+        for d in &mut self.density {
+            *d *= factor;
+        }
+    }
+
+    /// sRGB-encoded colors on the same blue-to-red ramp as the color modes, normalized against
+    /// the densest cell, with empty cells fully transparent.
+    pub fn rgba(&self) -> Vec<[u8; 4]> {
+        let peak = self.density.iter().copied().fold(0.0, f32::max);
+        let scale = if peak > 0.0 { 1.0 / peak } else { 0.0 };
+
+        self.density
+            .iter()
+            .map(|&d| {
+                let t = d * scale;
+                let [r, g, b] = hsl_to_rgb(240.0 * (1.0 - t), 0.9, 0.55);
+
+                [r, g, b, t * MAX_ALPHA].map(|c| (c * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+/// GPU side of [`Heatmap`]: a texture stretched over the bounds, bilinearly filtered.
+pub struct HeatmapLayer {
+    pipeline: RenderPipeline,
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    params: Buffer,
+    target: Option<(Texture, BindGroup)>,
+}
+
+impl HeatmapLayer {
+    pub fn new(
+        device: &Device,
+        globals_bgl: &BindGroupLayout,
+        format: TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("heatmap.wgsl"));
+        let bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Heatmap BGL"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Heatmap Layout"),
+            bind_group_layouts: &[globals_bgl, &bgl],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::render::create_pipeline(
+            device,
+            "heatmap pipeline",
+            &layout,
+            crate::render::Stages::main(&shader),
+            &[],
+            format,
+            sample_count,
+        );
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Heatmap Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let params = device.create_buffer(&BufferDescriptor {
+            label: Some("Heatmap Params"),
+            size: size_of::<Params>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bgl,
+            sampler,
+            params,
+            target: None,
+        }
+    }
+
+    /// Copies the heatmap to the GPU, or stops drawing it when `None`.
+    pub fn upload(&mut self, device: &Device, queue: &Queue, heatmap: Option<&Heatmap>) {
+        let Some(heatmap) = heatmap else {
+            self.target = None;
+            return;
+        };
+
+        let (width, height) = heatmap.size();
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        if self
+            .target
+            .as_ref()
+            .is_none_or(|(texture, _)| texture.size() != size)
+        {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("Heatmap"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Heatmap BG"),
+                layout: &self.bgl,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: self.params.as_entire_binding(),
+                    },
+                ],
+            });
+
+            self.target = Some((texture, bind_group));
+        }
+
+        let (hw, hh) = heatmap.bounds().half_extents();
+
+        queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params {
+                half_extents: [hw, hh],
+                _pad: [0.0; 2],
+            }),
+        );
+
+        if let Some((texture, _)) = &self.target {
+            queue.write_texture(
+                texture.as_image_copy(),
+                bytemuck::cast_slice(&heatmap.rgba()),
+                TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: None,
+                },
+                size,
+            );
+        }
+    }
+
+    /// Expects the globals at group 0.
+    pub fn draw(&self, pass: &mut RenderPass) {
+        if let Some((_, bind_group)) = &self.target {
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(1, bind_group, &[]);
+            pass.draw(0..6, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Bounds = Bounds {
+        width: 100.0,
+        height: 50.0,
+    };
+
+    #[test]
+    fn impacts_land_in_their_cell() {
+        let mut heatmap = Heatmap::new(BOUNDS);
+
+        assert_eq!(heatmap.size(), (10, 5));
+
+        heatmap.add(Vec2::new(-45.0, 20.0));
+        heatmap.add(Vec2::new(45.0, -20.0));
+        heatmap.add(Vec2::new(45.0, -20.0));
+        heatmap.add(Vec2::new(60.0, 0.0));
+
+        let rgba = heatmap.rgba();
+
+        // Top-left is row 0; the bottom-right cell is the peak.
+        assert_eq!(rgba[0][3], (0.5 * MAX_ALPHA * 255.0).round() as u8);
+        assert_eq!(rgba[49][3], (MAX_ALPHA * 255.0).round() as u8);
+        assert_eq!(rgba.iter().filter(|c| c[3] > 0).count(), 2);
+    }
+
+    #[test]
+    fn density_halves_every_half_life() {
+        let mut heatmap = Heatmap::new(BOUNDS);
+
+        heatmap.add(Vec2::ZERO);
+        heatmap.decay(HALF_LIFE);
+        heatmap.decay(HALF_LIFE);
+
+        assert!((heatmap.density.iter().sum::<f32>() - 0.25).abs() < 1e-6);
+
+        heatmap.fit(Bounds {
+            width: 200.0,
+            ..BOUNDS
+        });
+
+        assert_eq!(heatmap.size(), (20, 5));
+        assert_eq!(heatmap.density.iter().sum::<f32>(), 0.0);
+    }
+}
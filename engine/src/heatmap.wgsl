@@ -0,0 +1,63 @@
+struct Globals {
+	screen_wh: vec2<f32>,
+	offset: vec2<f32>,
+	scale: f32,
+	// 1.0 when rendering to an sRGB surface, which expects linear colors.
+	srgb_target: f32,
+	outline_all: f32,
+	_pad1: f32,
+};
+@group(0) @binding(0) var<uniform> U: Globals;
+
+struct Params {
+	half_extents: vec2<f32>,
+	_pad: vec2<f32>,
+};
+@group(1) @binding(0) var density: texture_2d<f32>;
+@group(1) @binding(1) var density_sampler: sampler;
+@group(1) @binding(2) var<uniform> P: Params;
+
+struct VSOut {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+};
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+	let lo = c / 12.92;
+	let hi = pow((c + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+
+	return select(hi, lo, c <= vec3<f32>(0.04045));
+}
+
+fn px_to_ndc(px: vec2<f32>) -> vec2<f32> {
+	let half = 0.5 * U.screen_wh;
+	let view = (px - U.offset) * U.scale;
+
+	return vec2<f32>(view.x / half.x, view.y / half.y);
+}
+
+// Two triangles covering the bounds; texture row 0 is the top edge.
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> VSOut {
+	var corners = array<vec2<f32>, 6>(
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, -1.0),
+		vec2<f32>(1.0, 1.0),
+		vec2<f32>(-1.0, 1.0),
+	);
+	let corner = corners[i];
+	var out: VSOut;
+
+	out.clip_position = vec4<f32>(px_to_ndc(corner * P.half_extents), 0.0, 1.0);
+	out.uv = vec2<f32>(0.5 + 0.5 * corner.x, 0.5 - 0.5 * corner.y);
+	return out;
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+	let c = textureSample(density, density_sampler, in.uv);
+
+	return vec4<f32>(select(c.rgb, srgb_to_linear(c.rgb), U.srgb_target > 0.5), c.a);
+}
@@ -0,0 +1,80 @@
+//! The per-instance data the particle pipeline draws circles from, shared by everything that
+//! uploads particles to the GPU.
+
+#[cfg(feature = "render")]
+use std::mem;
+
+#[cfg(feature = "render")]
+use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
+
+use crate::particle::Particle;
+
+/// Particles the renderer has room for unless [`crate::SimulationConfigBuilder::instance_capacity`]
+/// says otherwise.
+pub const DEFAULT_INSTANCE_CAPACITY: usize = 50_000;
+/// Overlay instances the renderer starts with room for; it grows as more are uploaded.
+pub const DEFAULT_OVERLAY_CAPACITY: usize = 10_000;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub pos: [f32; 2],
+    pub radius: f32,
+    pub outline: f32,
+    pub color: [f32; 3],
+    pub alpha: f32,
+}
+
+impl InstanceRaw {
+    #[cfg(feature = "render")]
+    pub fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceRaw>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as u64,
+                    shader_location: 2,
+                    format: VertexFormat::Float32,
+                },
+                VertexAttribute {
+                    offset: (mem::size_of::<[f32; 2]>()
+                        + mem::size_of::<f32>()
+                        + mem::size_of::<f32>()) as u64,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: (mem::size_of::<[f32; 2]>() + mem::size_of::<f32>()) as u64,
+                    shader_location: 4,
+                    format: VertexFormat::Float32,
+                },
+                VertexAttribute {
+                    offset: (mem::size_of::<[f32; 2]>()
+                        + mem::size_of::<f32>()
+                        + mem::size_of::<f32>()
+                        + mem::size_of::<[f32; 3]>()) as u64,
+                    shader_location: 5,
+                    format: VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+impl From<&Particle> for InstanceRaw {
+    fn from(p: &Particle) -> Self {
+        Self {
+            pos: [p.position.x, p.position.y],
+            radius: p.radius,
+            outline: p.outline,
+            color: p.color,
+            alpha: p.alpha,
+        }
+    }
+}
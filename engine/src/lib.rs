@@ -1,24 +1,68 @@
+pub mod boundary;
+#[cfg(feature = "render")]
+pub mod camera;
+pub mod color;
+#[cfg(feature = "render")]
+pub mod compute;
+#[cfg(feature = "render")]
+pub mod config;
+pub mod context;
+pub mod grid;
+pub mod headless;
+pub mod instance;
+pub mod line;
 pub mod particle;
-
+#[cfg(feature = "render")]
+pub mod render;
+pub mod shape;
+pub mod split;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod threaded;
+pub mod trajectory;
+
+#[cfg(feature = "render")]
+mod app;
+#[cfg(feature = "render")]
+mod capture;
+#[cfg(feature = "render")]
+mod error;
+#[cfg(feature = "render")]
+mod frame;
+#[cfg(feature = "render")]
+mod heatmap;
+#[cfg(feature = "render")]
 mod mesh;
-mod render;
-
-use std::{
-    sync::Arc,
-    time::{Duration, Instant},
-};
-
-use winit::{
-    application::ApplicationHandler,
-    dpi::{LogicalSize, PhysicalSize},
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    monitor::MonitorHandle,
-    window::{Fullscreen, Window, WindowAttributes, WindowId},
-};
-
-use crate::{particle::Particle, render::Renderer};
-
+#[cfg(feature = "render")]
+mod overlay;
+#[cfg(feature = "render")]
+mod supersample;
+#[cfg(feature = "render")]
+mod timestep;
+
+#[cfg(feature = "render")]
+pub use app::{Finished, run_with};
+#[cfg(feature = "render")]
+pub use config::{SimulationConfig, SimulationConfigBuilder};
+#[cfg(feature = "render")]
+pub use egui;
+#[cfg(feature = "render")]
+pub use error::Error;
+#[cfg(feature = "render")]
+pub use frame::RunStats;
+#[cfg(feature = "render")]
+pub use wgpu;
+#[cfg(feature = "render")]
+pub use winit::keyboard::KeyCode;
+
+use std::borrow::Cow;
+
+use glam::Vec2;
+
+use crate::{context::Context, grid::Viewport, line::Line, particle::Particle, shape::Shape};
+
+/// Size of the simulated domain in world units, centered on the origin. It follows the window
+/// size but not its scale factor.
+#[derive(Debug, Clone, Copy)]
 pub struct Bounds {
     pub width: f32,
     pub height: f32,
@@ -30,167 +74,171 @@ impl Bounds {
     }
 }
 
-pub struct SimulationConfig {
-    pub fullscreen: bool,
-    pub fps: u64,
+#[cfg(feature = "render")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
 }
 
-pub trait Simulation {
-    fn init(&mut self, bounds: Bounds);
-    fn step(&mut self, dt: f32, bounds: Bounds);
-    fn particles(&self) -> &[Particle];
+#[cfg(feature = "render")]
+impl Theme {
+    pub fn clear_color(self) -> [f32; 3] {
+        match self {
+            Theme::Dark => [0.02, 0.02, 0.03],
+            Theme::Light => [1.0, 1.0, 1.0],
+        }
+    }
 }
 
-pub fn run_with<S: Simulation + 'static>(sim: S, config: SimulationConfig) -> anyhow::Result<()> {
-    pub struct App<S: Simulation> {
-        window: Option<Arc<Window>>,
-        renderer: Option<Renderer>,
-        simulation: S,
-        last_frame: Instant,
-        config: SimulationConfig,
-    }
+/// Graphics API used by the renderer. `Auto` lets wgpu pick whatever the platform supports.
+#[cfg(feature = "render")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
 
-    impl<S: Simulation> ApplicationHandler for App<S> {
-        fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-            let monitors: Vec<MonitorHandle> = event_loop.available_monitors().collect();
-
-            if let Ok(window) = event_loop.create_window(
-                WindowAttributes::default()
-                    .with_title("Particle Simulation")
-                    .with_inner_size(
-                        if self.config.fullscreen
-                            && let Some(monitor) = monitors.first()
-                        {
-                            let size = monitor.size();
-                            LogicalSize::new(size.width as f64 * 0.9, size.height as f64 * 0.8)
-                        } else {
-                            LogicalSize::new(800.0, 600.0)
-                        },
-                    ),
-            ) {
-                if self.config.fullscreen {
-                    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
-                }
-
-                let window = Arc::new(window);
-                let size = window.inner_size();
-                let Ok(mut renderer) =
-                    pollster::block_on(async { Renderer::new(window.clone(), size).await })
-                else {
-                    log::error!("Failed to create renderer");
-                    event_loop.exit();
-                    return;
-                };
-
-                self.simulation.init(Bounds {
-                    width: size.width as f32,
-                    height: size.height as f32,
-                });
-
-                renderer.upload_instances(self.simulation.particles());
-
-                self.window = Some(window.clone());
-                self.renderer = Some(renderer);
-                self.last_frame = Instant::now();
-            }
+#[cfg(feature = "render")]
+impl Backend {
+    /// Whether wgpu is built with this backend for the target platform.
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Auto | Backend::Gl => true,
+            Backend::Vulkan => cfg!(not(any(target_arch = "wasm32", target_vendor = "apple"))),
+            Backend::Dx12 => cfg!(windows),
+            Backend::Metal => cfg!(target_vendor = "apple"),
         }
+    }
+}
 
-        fn window_event(
-            &mut self,
-            event_loop: &ActiveEventLoop,
-            _window_id: WindowId,
-            event: WindowEvent,
-        ) {
-            let (Some(window), Some(renderer)) = (self.window.as_ref(), self.renderer.as_mut())
-            else {
-                return;
-            };
-
-            match event {
-                WindowEvent::CloseRequested => event_loop.exit(),
-                WindowEvent::Resized(size) => renderer.resize(size),
-                WindowEvent::ScaleFactorChanged {
-                    mut inner_size_writer,
-                    ..
-                } => {
-                    let new_size = window.inner_size();
-
-                    if let Err(e) = inner_size_writer.request_inner_size(new_size) {
-                        log::error!("Failed to change inner size: {e}");
-                    }
-
-                    renderer.resize(new_size);
-                }
-                WindowEvent::RedrawRequested => {
-                    log::info!("FPS: {}", 1.0 / (self.last_frame.elapsed().as_secs_f32()));
-
-                    let PhysicalSize { width, height } = window.inner_size();
-
-                    if width == 0 || height == 0 {
-                        return;
-                    }
-
-                    let now = Instant::now();
-                    let dt = (now - self.last_frame).as_secs_f32();
-                    let bounds = Bounds {
-                        width: width as f32,
-                        height: height as f32,
-                    };
-
-                    self.last_frame = now;
-
-                    if window.has_focus() {
-                        self.simulation.step(dt, bounds);
-                    }
-
-                    renderer.upload_instances(self.simulation.particles());
-
-                    if let Err(err) = renderer.render() {
-                        use wgpu::SurfaceError::*;
-
-                        match err {
-                            Timeout => (),
-                            Lost | Outdated => {
-                                renderer.resize(window.inner_size());
-                            }
-                            OutOfMemory | Other => {
-                                log::error!("wgpu exiting: {err}");
-                                event_loop.exit();
-                            }
-                        }
-                    }
-                }
-
-                _ => {}
-            }
-        }
+/// How frames are presented. `Fifo` is vsynced and always available; the others fall back to it
+/// when the surface does not support them.
+#[cfg(feature = "render")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    #[default]
+    Fifo,
+    Mailbox,
+    Immediate,
+}
 
-        fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-            let now = Instant::now();
-
-            if now - self.last_frame >= Duration::from_millis(1000 / self.config.fps) {
-                if let Some(w) = &self.window {
-                    w.request_redraw();
-                }
-            } else {
-                event_loop.set_control_flow(ControlFlow::WaitUntil(
-                    self.last_frame + Duration::from_millis(1000 / self.config.fps),
-                ));
-            }
+/// What the engine should do after a step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StepResult {
+    #[default]
+    Continue,
+    /// Close the window and return from `run_with`, e.g. once a batch run has its frames.
+    Exit,
+}
+
+impl StepResult {
+    /// Combines the results of simulations run together; any of them can end the run.
+    pub fn or(self, other: Self) -> Self {
+        match self {
+            Self::Exit => Self::Exit,
+            Self::Continue => other,
         }
     }
+}
+
+pub trait Simulation {
+    fn init(&mut self, ctx: &mut Context, bounds: Bounds);
+
+    /// Advances the simulation by `dt`. Drivers call this through [`Context::run_step`], which
+    /// also counts the step into [`Context::frame`]; wrappers stepping an inner simulation
+    /// within their own step call it directly, as the outer step is counted already.
+    fn step(&mut self, ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult;
+    fn particles(&self) -> &[Particle];
+
+    /// Called once after `init` with the renderer's device and queue, so the simulation can set
+    /// up its own GPU resources (e.g. [`compute::GpuParticles::with_device`]) on the same device.
+    #[cfg(feature = "render")]
+    fn init_gpu(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue) {}
+
+    /// Called after the CPU step(s) of every frame that advances the simulation, to record and
+    /// submit compute passes. `dt` is the time advanced this frame.
+    #[cfg(feature = "render")]
+    fn step_gpu(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue, _dt: f32) {}
 
-    let event_loop = EventLoop::new()?;
-    let mut app = App {
-        window: None,
-        renderer: None,
-        simulation: sim,
-        last_frame: Instant::now(),
-        config,
-    };
+    #[cfg(feature = "render")]
+    fn debug_ui(&mut self, _ui: &mut egui::Ui) {}
 
-    event_loop.set_control_flow(ControlFlow::Poll);
-    event_loop.run_app(&mut app)?;
+    /// Draws windows of the simulation's own next to the engine's debug window, e.g. live
+    /// controls. Simulations drawn side by side must give their windows distinct ids.
+    #[cfg(feature = "render")]
+    fn ui(&mut self, _ctx: &egui::Context) {}
+
+    /// Extra lines shown in the on-screen HUD below the FPS counter.
+    fn hud(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Called for key presses the engine does not handle itself.
+    #[cfg(feature = "render")]
+    fn key_pressed(&mut self, _key: KeyCode) {}
+
+    /// Called with the world position of a left click.
+    fn clicked(&mut self, _world: Vec2) {}
+
+    /// Called with the world-space corners of a Shift+drag selection rectangle.
+    fn selected_rect(&mut self, _min: Vec2, _max: Vec2) {}
+
+    /// World position the focus key (F) centers the view on, typically the selected particle.
+    fn focus(&self) -> Option<Vec2> {
+        None
+    }
+
+    /// Line segments drawn over the particles, in world coordinates.
+    fn debug_lines(&self) -> Vec<Line> {
+        Vec::new()
+    }
+
+    /// Walls, obstacles and other shapes drawn with the particles every frame, in world
+    /// coordinates. Unlike `debug_lines`, these are part of the scene.
+    fn scenery(&self) -> Vec<Shape> {
+        Vec::new()
+    }
+
+    /// Instances drawn over the particles from a buffer of their own, e.g. ghosts of predicted
+    /// states. Like scenery they are never clipped to viewports or recolored.
+    fn overlay_instances(&self) -> Cow<'_, [Particle]> {
+        Cow::Borrowed(&[])
+    }
+
+    /// World-space rectangles that ranges of `particles()` are clipped to. Empty draws everything
+    /// unclipped.
+    fn viewports(&self) -> Vec<Viewport> {
+        Vec::new()
+    }
+
+    /// Text drawn with its top-left corner at a world position, hidden along with the HUD.
+    fn labels(&self) -> Vec<(Vec2, String)> {
+        Vec::new()
+    }
+
+    /// World positions of the collisions since the last call, accumulated into the heatmap.
+    fn take_impacts(&mut self) -> Vec<Vec2> {
+        Vec::new()
+    }
+
+    /// Whether stepping would not change anything, e.g. every particle has come to rest. The
+    /// window then redraws at a low idle rate until the next input.
+    fn quiescent(&self) -> bool {
+        false
+    }
+
+    /// Called every frame before drawing, stepped or not. Returns whether the drawn state changed
+    /// outside `step`, e.g. on a worker thread, so an idle window still redraws it.
+    fn poll(&mut self) -> bool {
+        false
+    }
 
-    Ok(())
+    /// Called once before the event loop returns, e.g. to flush buffered output.
+    fn on_exit(&mut self) {}
 }
@@ -0,0 +1,78 @@
+#[cfg(feature = "render")]
+use std::mem;
+
+use glam::Vec2;
+#[cfg(feature = "render")]
+use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
+
+pub const MAX_LINES: usize = 50_000;
+
+#[derive(Default, Copy, Clone, Debug, PartialEq)]
+pub struct Line {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub color: [f32; 3],
+    /// Negative layers are drawn under the particles, the rest over them. Within each side,
+    /// higher layers are drawn later.
+    pub layer: i32,
+}
+
+impl Line {
+    pub fn new(start: Vec2, end: Vec2, color: [f32; 3]) -> Self {
+        Self {
+            start,
+            end,
+            color,
+            layer: 0,
+        }
+    }
+
+    pub fn with_layer(self, layer: i32) -> Self {
+        Self { layer, ..self }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineRaw {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    pub color: [f32; 3],
+    pub _pad0: f32,
+}
+
+impl LineRaw {
+    #[cfg(feature = "render")]
+    pub fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<LineRaw>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as u64,
+                    shader_location: 2,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttribute {
+                    offset: (2 * mem::size_of::<[f32; 2]>()) as u64,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+
+    pub fn from_line(l: &Line) -> Self {
+        Self {
+            start: l.start.into(),
+            end: l.end.into(),
+            color: l.color,
+            _pad0: 0.0,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+struct Globals {
+	screen_wh: vec2<f32>, 
+	offset: vec2<f32>,
+	scale: f32,
+	// 1.0 when rendering to an sRGB surface, which expects linear colors.
+	srgb_target: f32,
+	// Minimum ring thickness for every particle, as a fraction of the radius.
+	outline_all: f32,
+	_pad1: f32,
+};
+@group(0) @binding(0) var<uniform> U: Globals;
+
+// Screen-space thickness, independent of zoom.
+const HALF_WIDTH_PX: f32 = 0.75;
+
+struct VSOut {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) v_color: vec3<f32>,
+};
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+	let lo = c / 12.92;
+	let hi = pow((c + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+
+	return select(hi, lo, c <= vec3<f32>(0.04045));
+}
+
+fn px_to_ndc(px: vec2<f32>) -> vec2<f32> {
+	let half = 0.5 * U.screen_wh;
+	let view = (px - U.offset) * U.scale;
+
+	return vec2<f32>(view.x / half.x, view.y / half.y);
+}
+
+@vertex
+fn vs_main(
+	@location(0) quad_pos: vec2<f32>,
+	@location(1) i_start_px: vec2<f32>,
+	@location(2) i_end_px: vec2<f32>,
+	@location(3) i_color: vec3<f32>,
+) -> VSOut {
+	var out: VSOut;
+
+	let along = i_end_px - i_start_px;
+	let len = length(along);
+	let dir = select(vec2<f32>(1.0, 0.0), along / len, len > 0.0);
+	let normal = vec2<f32>(-dir.y, dir.x);
+
+	let t = 0.5 * (quad_pos.x + 1.0);
+	let world_px = i_start_px + along * t + normal * quad_pos.y * HALF_WIDTH_PX / U.scale;
+
+	out.clip_position = vec4<f32>(px_to_ndc(world_px), 0.0, 1.0);
+	out.v_color = select(i_color, srgb_to_linear(i_color), U.srgb_target > 0.5);
+	return out;
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+	return vec4<f32>(in.v_color, 1.0);
+}
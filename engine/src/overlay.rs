@@ -0,0 +1,73 @@
+use winit::{event::WindowEvent, window::Window};
+
+use crate::Theme;
+
+pub struct UiFrame {
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures_delta: egui::TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+pub struct Overlay {
+    state: egui_winit::State,
+}
+
+impl Overlay {
+    pub fn new(window: &Window, theme: Theme) -> Self {
+        let ctx = egui::Context::default();
+
+        ctx.set_theme(match theme {
+            Theme::Dark => egui::Theme::Dark,
+            Theme::Light => egui::Theme::Light,
+        });
+
+        let state = egui_winit::State::new(
+            ctx,
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            None,
+        );
+
+        Self { state }
+    }
+
+    /// Feeds a window event to egui, returning whether egui consumed it.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    pub fn run(&mut self, window: &Window, build: impl FnMut(&egui::Context)) -> UiFrame {
+        let input = self.state.take_egui_input(window);
+        let ctx = self.state.egui_ctx().clone();
+        let output = ctx.run(input, build);
+
+        self.state
+            .handle_platform_output(window, output.platform_output);
+
+        UiFrame {
+            primitives: ctx.tessellate(output.shapes, output.pixels_per_point),
+            textures_delta: output.textures_delta,
+            pixels_per_point: output.pixels_per_point,
+        }
+    }
+}
+
+/// Paints `text` anchored at `pos` (in points) above every egui window.
+pub fn draw_text(ctx: &egui::Context, pos: egui::Pos2, anchor: egui::Align2, text: &str) {
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("hud"),
+    ));
+    let font = egui::FontId::monospace(14.0);
+
+    painter.text(
+        pos + egui::vec2(1.0, 1.0),
+        anchor,
+        text,
+        font.clone(),
+        egui::Color32::BLACK,
+    );
+    painter.text(pos, anchor, text, font, egui::Color32::WHITE);
+}
@@ -1,18 +1,27 @@
-use std::mem;
+use std::ops::Range;
 
 use glam::Vec2;
-use wgpu::{BufferAddress, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
-
-pub const MAX_INSTANCES: usize = 50_000;
 
 #[repr(C)]
-#[derive(Default, Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct Particle {
     pub position: Vec2,
     pub velocity: Vec2,
     pub radius: f32,
     pub mass: f32,
     pub color: [f32; 3],
+    /// Ring thickness as a fraction of the radius; zero draws a filled disc.
+    pub outline: f32,
+    /// Opacity, blended over whatever is drawn underneath; one is opaque.
+    pub alpha: f32,
+    /// Particles on higher layers are drawn over lower ones; equal layers keep their order.
+    pub layer: i32,
+}
+
+impl Default for Particle {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO, Vec2::ZERO, 0.0, 0.0, [0.0; 3])
+    }
 }
 
 impl Particle {
@@ -23,54 +32,91 @@ impl Particle {
             radius,
             mass,
             color,
+            outline: 0.0,
+            alpha: 1.0,
+            layer: 0,
         }
     }
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct InstanceRaw {
-    pub pos: [f32; 2],
-    pub radius: f32,
-    pub _pad0: f32,
-    pub color: [f32; 3],
-    pub _pad1: f32,
+/// Stable-sorts every range of `particles` by layer. Ranges are sorted separately so instances
+/// stay inside their viewport.
+pub fn sort_by_layer(particles: &mut [Particle], ranges: impl IntoIterator<Item = Range<usize>>) {
+    for range in ranges {
+        particles[range].sort_by_key(|p| p.layer);
+    }
+}
+
+/// A stretch of particles on one layer, inside the `group`-th range given to [`layer_runs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerRun {
+    pub layer: i32,
+    pub group: usize,
+    pub range: Range<usize>,
 }
 
-impl InstanceRaw {
-    pub fn desc() -> VertexBufferLayout<'static> {
-        VertexBufferLayout {
-            array_stride: mem::size_of::<InstanceRaw>() as BufferAddress,
-            step_mode: VertexStepMode::Instance,
-            attributes: &[
-                VertexAttribute {
-                    offset: 0,
-                    shader_location: 1,
-                    format: VertexFormat::Float32x2,
-                },
-                VertexAttribute {
-                    offset: mem::size_of::<[f32; 2]>() as u64,
-                    shader_location: 2,
-                    format: VertexFormat::Float32,
-                },
-                VertexAttribute {
-                    offset: (mem::size_of::<[f32; 2]>()
-                        + mem::size_of::<f32>()
-                        + mem::size_of::<f32>()) as u64,
-                    shader_location: 3,
-                    format: VertexFormat::Float32x3,
-                },
-            ],
+/// Splits every range of layer-sorted `particles` into runs of equal layer, in order.
+pub fn layer_runs(
+    particles: &[Particle],
+    ranges: impl IntoIterator<Item = Range<usize>>,
+) -> Vec<LayerRun> {
+    let mut runs = Vec::new();
+
+    for (group, range) in ranges.into_iter().enumerate() {
+        let mut start = range.start;
+
+        while start < range.end {
+            let layer = particles[start].layer;
+            let len = particles[start..range.end]
+                .iter()
+                .take_while(|p| p.layer == layer)
+                .count();
+
+            runs.push(LayerRun {
+                layer,
+                group,
+                range: start..start + len,
+            });
+            start += len;
         }
     }
 
-    pub fn from_particle(p: &Particle) -> Self {
-        Self {
-            pos: [p.position.x, p.position.y],
-            radius: p.radius,
-            _pad0: 0.0,
-            color: p.color,
-            _pad1: 0.0,
-        }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_sort_within_ranges() {
+        let mut particles = [(0, 1), (1, 0), (2, 0), (3, 1), (4, -1)].map(|(id, layer)| Particle {
+            radius: id as f32,
+            layer,
+            ..Default::default()
+        });
+
+        sort_by_layer(&mut particles, [0..3, 3..5]);
+
+        let ids = particles.map(|p| p.radius as i32);
+        assert_eq!(ids, [1, 2, 0, 4, 3]);
+    }
+
+    #[test]
+    fn runs_split_at_layers_and_ranges() {
+        let particles = [0, 0, 1, 1, 1, 2].map(|layer| Particle {
+            layer,
+            ..Default::default()
+        });
+        let runs = layer_runs(&particles, [0..3, 3..3, 3..6]);
+        let runs: Vec<_> = runs
+            .iter()
+            .map(|r| (r.layer, r.group, r.range.clone()))
+            .collect();
+
+        assert_eq!(
+            runs,
+            [(0, 0, 0..2), (1, 0, 2..3), (1, 2, 3..5), (2, 2, 5..6)]
+        );
     }
 }
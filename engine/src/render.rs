@@ -1,98 +1,305 @@
+use std::borrow::Cow;
 use std::mem;
+use std::ops::Range;
 use std::{iter, sync::Arc};
 
+use glam::Vec2;
+use image::RgbaImage;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
 use winit::{dpi::PhysicalSize, window::Window};
 
+use crate::camera::Camera;
+use crate::capture::{ClipBuffer, FrameCapture, Readback};
+use crate::grid::Viewport;
+use crate::heatmap::{Heatmap, HeatmapLayer};
+use crate::instance::{DEFAULT_INSTANCE_CAPACITY, DEFAULT_OVERLAY_CAPACITY, InstanceRaw};
+use crate::line::{Line, LineRaw, MAX_LINES};
 use crate::mesh::{QUAD_INDICES, QUAD_VERTICES, QuadVertex};
-use crate::particle::{InstanceRaw, MAX_INSTANCES, Particle};
+use crate::overlay::UiFrame;
+use crate::particle::{LayerRun, Particle, layer_runs, sort_by_layer};
+use crate::shape;
+use crate::supersample::Supersample;
+use crate::{Backend, Error, PresentMode, Simulation};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Globals {
     screen_wh: [f32; 2],
-    _pad: [f32; 2],
+    offset: [f32; 2],
+    scale: f32,
+    srgb_target: f32,
+    outline_all: f32,
+    _pad: f32,
+}
+
+impl Globals {
+    fn new(config: &SurfaceConfiguration, camera: &Camera, outline_all: f32, dpi: f32) -> Self {
+        Self {
+            screen_wh: [config.width as f32 / dpi, config.height as f32 / dpi],
+            offset: camera.offset.into(),
+            scale: camera.scale,
+            srgb_target: if config.format.is_srgb() { 1.0 } else { 0.0 },
+            outline_all,
+            _pad: 0.0,
+        }
+    }
+}
+
+/// What the event loop changes on a renderer in response to input. [`Renderer`] draws with
+/// wgpu; [`NullBackend`] only keeps the state, so input handling can run without a window or GPU.
+pub trait RenderBackend {
+    fn resize(&mut self, size: PhysicalSize<u32>);
+
+    fn set_camera(&mut self, camera: Camera);
+
+    fn set_dpi(&mut self, dpi: f32);
+
+    fn set_outline_all(&mut self, thickness: f32);
+
+    fn save_clip(&mut self);
+}
+
+/// A backend that draws nothing and remembers the last value of every setting.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NullBackend {
+    pub size: PhysicalSize<u32>,
+    pub camera: Camera,
+    pub dpi: f32,
+    pub outline_all: f32,
+    pub clips_saved: usize,
+}
+
+impl RenderBackend for NullBackend {
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.size = size;
+    }
+
+    fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    fn set_dpi(&mut self, dpi: f32) {
+        self.dpi = dpi;
+    }
+
+    fn set_outline_all(&mut self, thickness: f32) {
+        self.outline_all = thickness;
+    }
+
+    fn save_clip(&mut self) {
+        self.clips_saved += 1;
+    }
+}
+
+/// Text queued with [`Renderer::draw_text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    /// In logical pixels from the top-left corner of the window.
+    pub pos: Vec2,
+    pub anchor: egui::Align2,
+    pub text: String,
 }
 
 pub struct Renderer {
     device: Device,
-    surface: Surface<'static>,
+    /// `None` for headless renderers.
+    surface: Option<Surface<'static>>,
     config: SurfaceConfiguration,
     queue: Queue,
     pipeline: RenderPipeline,
+    line_pipeline: RenderPipeline,
+
+    sample_count: u32,
+    msaa_view: Option<TextureView>,
+    supersample: Supersample,
 
     quad_vb: Buffer,
     quad_ib: Buffer,
 
+    camera: Camera,
+    /// Ring thickness applied to every particle, on top of their own.
+    outline_all: f32,
+    /// Physical pixels per logical pixel; the world is measured in logical pixels.
+    dpi: f32,
     globals_buffer: Buffer,
     globals_bg: BindGroup,
 
     instance_buffer: Buffer,
+    /// Particles `instance_buffer` has room for; any beyond are not drawn.
+    instance_capacity: usize,
     num_instances: usize,
+    /// Uploaded particles by layer; `group` indexes `viewports`, if there are any.
+    instance_runs: Vec<LayerRun>,
+    viewports: Vec<Viewport>,
+    /// Scenery and [`crate::Simulation::overlay_instances`], drawn over the particles of their
+    /// own layer and under those of higher ones.
+    overlay_buffer: Buffer,
+    overlay_capacity: usize,
+    overlay_runs: Vec<LayerRun>,
+
+    line_buffer: Buffer,
+    num_lines: usize,
+    /// Lines `..lines_below` are drawn under the particles.
+    lines_below: usize,
+
+    heatmap: HeatmapLayer,
+
+    egui_renderer: egui_wgpu::Renderer,
+    /// Drawn over the UI by the next frame, then cleared.
+    texts: Vec<Text>,
+    capture: Option<FrameCapture>,
+    clip: Option<ClipBuffer>,
+    clear_color: Color,
+}
+
+impl From<Backend> for Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Auto => Backends::all(),
+            Backend::Vulkan => Backends::VULKAN,
+            Backend::Dx12 => Backends::DX12,
+            Backend::Metal => Backends::METAL,
+            Backend::Gl => Backends::GL,
+        }
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+impl RenderBackend for Renderer {
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        Renderer::resize(self, size);
+    }
+
+    fn set_camera(&mut self, camera: Camera) {
+        Renderer::set_camera(self, camera);
+    }
+
+    fn set_dpi(&mut self, dpi: f32) {
+        Renderer::set_dpi(self, dpi);
+    }
+
+    fn set_outline_all(&mut self, thickness: f32) {
+        Renderer::set_outline_all(self, thickness);
+    }
+
+    fn save_clip(&mut self) {
+        Renderer::save_clip(self);
+    }
 }
 
 impl Renderer {
     pub async fn new(
         window: Arc<Window>,
         PhysicalSize { width, height }: PhysicalSize<u32>,
-    ) -> anyhow::Result<Self> {
+        sample_count: u32,
+        backend: Backend,
+        present_mode: PresentMode,
+        srgb: bool,
+        render_scale: u32,
+    ) -> Result<Self, Error> {
         let instance = Instance::new(&InstanceDescriptor {
-            backends: wgpu::Backends::VULKAN,
+            backends: backend.into(),
             ..Default::default()
         });
         let surface = instance.create_surface(window)?;
-
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await?;
-
-        let (device, queue) = adapter
-            .request_device(&DeviceDescriptor {
-                label: Some("WGPU Device"),
-                required_features: Features::empty(),
-                required_limits: Limits {
-                    max_texture_dimension_2d: 4096,
-                    ..Limits::downlevel_defaults()
-                },
-                memory_hints: MemoryHints::default(),
-                trace: Trace::Off,
-            })
-            .await?;
+        let adapter = request_adapter(&instance, Some(&surface)).await?;
+        let (device, queue) = request_device(&adapter).await?;
 
         let caps = surface.get_capabilities(&adapter);
         let format = caps
             .formats
             .iter()
             .copied()
-            .find(|f| !f.is_srgb())
+            .find(|f| f.is_srgb() == srgb)
             .unwrap_or(caps.formats[0]);
 
         let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
+            usage: if caps.usages.contains(TextureUsages::COPY_SRC) {
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+            } else {
+                TextureUsages::RENDER_ATTACHMENT
+            },
             format,
             width,
             height,
-            present_mode: if caps.present_modes.contains(&PresentMode::Fifo) {
-                PresentMode::Fifo
-            } else {
-                caps.present_modes[0]
-            },
+            present_mode: select_present_mode(&caps.present_modes, present_mode.into()),
             alpha_mode: caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
         surface.configure(&device, &config);
 
-        let globals = Globals {
-            screen_wh: [width as f32, height as f32],
-            _pad: [0.0; 2],
+        Ok(Self::build(
+            device,
+            queue,
+            &adapter,
+            Some(surface),
+            config,
+            sample_count,
+            render_scale,
+        ))
+    }
+
+    /// A renderer without a window, for [`Renderer::render_to_image`] in headless runs.
+    pub async fn headless(
+        PhysicalSize { width, height }: PhysicalSize<u32>,
+        sample_count: u32,
+        backend: Backend,
+        render_scale: u32,
+    ) -> Result<Self, Error> {
+        let instance = Instance::new(&InstanceDescriptor {
+            backends: backend.into(),
+            ..Default::default()
+        });
+        let adapter = request_adapter(&instance, None).await?;
+        let (device, queue) = request_device(&adapter).await?;
+
+        // Stands in for the surface configuration; colors match the default non-sRGB surface.
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format: TextureFormat::Rgba8Unorm,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
         };
 
+        Ok(Self::build(
+            device,
+            queue,
+            &adapter,
+            None,
+            config,
+            sample_count,
+            render_scale,
+        ))
+    }
+
+    fn build(
+        device: Device,
+        queue: Queue,
+        adapter: &Adapter,
+        surface: Option<Surface<'static>>,
+        config: SurfaceConfiguration,
+        sample_count: u32,
+        render_scale: u32,
+    ) -> Self {
+        let format = config.format;
+        let camera = Camera::default();
+        let globals = Globals::new(&config, &camera, 0.0, 1.0);
+
         let raw_size = mem::size_of::<Globals>() as BufferAddress;
         let aligned_size = (raw_size + 15) & !15;
         let globals_buffer = device.create_buffer(&BufferDescriptor {
@@ -126,7 +333,43 @@ impl Renderer {
             }],
         });
 
-        let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
+        let sample_count = if adapter
+            .get_texture_format_features(format)
+            .flags
+            .sample_count_supported(sample_count)
+        {
+            sample_count
+        } else {
+            log::warn!("{sample_count}x MSAA is not supported for {format:?}, using 1x");
+            1
+        };
+
+        // Circles are cut out with `discard`, so edges only benefit from MSAA when the fragment
+        // shader runs per sample.
+        let per_sample = sample_count > 1
+            && adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(DownlevelFlags::MULTISAMPLED_SHADING);
+        // Adapters without it reject a module that merely mentions per-sample inputs, so those
+        // entry points only join the module where they can run.
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: ShaderSource::Wgsl(match per_sample {
+                true => [include_str!("shader.wgsl"), include_str!("sample.wgsl")]
+                    .concat()
+                    .into(),
+                false => include_str!("shader.wgsl").into(),
+            }),
+        });
+        let stages = match per_sample {
+            true => Stages {
+                module: &shader,
+                vertex: "vs_sample",
+                fragment: "fs_sample",
+            },
+            false => Stages::main(&shader),
+        };
 
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Pipeline Layout"),
@@ -134,34 +377,33 @@ impl Renderer {
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[QuadVertex::desc(), InstanceRaw::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(ColorTargetState {
-                    format,
-                    blend: Some(BlendState::ALPHA_BLENDING),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                ..Default::default()
-            },
-            multisample: MultisampleState::default(),
-            depth_stencil: None,
-            multiview: None,
-            cache: None,
-        });
+        let pipeline = create_pipeline(
+            &device,
+            "pipeline",
+            &pipeline_layout,
+            stages,
+            &[QuadVertex::desc(), InstanceRaw::desc()],
+            format,
+            sample_count,
+        );
+
+        let line_shader = device.create_shader_module(include_wgsl!("line.wgsl"));
+        let line_pipeline = create_pipeline(
+            &device,
+            "line pipeline",
+            &pipeline_layout,
+            Stages::main(&line_shader),
+            &[QuadVertex::desc(), LineRaw::desc()],
+            format,
+            sample_count,
+        );
+
+        let heatmap = HeatmapLayer::new(&device, &globals_bgl, format, sample_count);
+
+        let mut supersample = Supersample::new(&device, format, render_scale);
+        supersample.resize(&device, &queue, &config);
+
+        let msaa_view = create_msaa_view(&device, &config, supersample.scale(), sample_count);
 
         let quad_vb = DeviceExt::create_buffer_init(
             &device,
@@ -180,62 +422,329 @@ impl Renderer {
             },
         );
 
-        let instance_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Instance Buffer"),
-            size: (MAX_INSTANCES * mem::size_of::<InstanceRaw>()) as u64,
+        let instance_buffer =
+            create_instance_buffer(&device, "Instance Buffer", DEFAULT_INSTANCE_CAPACITY);
+        let overlay_buffer =
+            create_instance_buffer(&device, "Overlay Instance Buffer", DEFAULT_OVERLAY_CAPACITY);
+
+        let line_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Line Buffer"),
+            size: (MAX_LINES * mem::size_of::<LineRaw>()) as u64,
             usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        Ok(Self {
+        let egui_renderer =
+            egui_wgpu::Renderer::new(&device, format, egui_wgpu::RendererOptions::default());
+
+        Self {
             device,
             surface,
             config,
             queue,
             pipeline,
+            line_pipeline,
+
+            sample_count,
+            msaa_view,
+            supersample,
 
             quad_vb,
             quad_ib,
 
+            camera,
+            outline_all: 0.0,
+            dpi: 1.0,
             globals_buffer,
             globals_bg,
 
             instance_buffer,
+            instance_capacity: DEFAULT_INSTANCE_CAPACITY,
             num_instances: 0,
-        })
+            instance_runs: Vec::new(),
+            viewports: Vec::new(),
+            overlay_buffer,
+            overlay_capacity: DEFAULT_OVERLAY_CAPACITY,
+            overlay_runs: Vec::new(),
+
+            line_buffer,
+            num_lines: 0,
+            lines_below: 0,
+
+            heatmap,
+
+            egui_renderer,
+            texts: Vec::new(),
+            capture: None,
+            clip: None,
+            clear_color: Color::BLACK,
+        }
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &Queue {
+        &self.queue
     }
 
     pub fn resize(&mut self, PhysicalSize { width, height }: PhysicalSize<u32>) {
+        // Minimized windows report a zero size, which cannot back a surface.
+        if width == 0 || height == 0 {
+            return;
+        }
+
         self.config.width = width;
         self.config.height = height;
-        self.surface.configure(&self.device, &self.config);
 
-        let globals = Globals {
-            screen_wh: [width as f32, height as f32],
-            _pad: [0.0; 2],
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+
+        self.supersample
+            .resize(&self.device, &self.queue, &self.config);
+        self.msaa_view = create_msaa_view(
+            &self.device,
+            &self.config,
+            self.supersample.scale(),
+            self.sample_count,
+        );
+        self.write_globals();
+    }
+
+    fn can_capture(&self) -> bool {
+        let supported = self.config.usage.contains(TextureUsages::COPY_SRC)
+            && Readback::supports(self.config.format);
+
+        if !supported {
+            log::error!(
+                "Surface format {:?} cannot be captured, frame capture disabled",
+                self.config.format
+            );
+        }
+
+        supported
+    }
+
+    pub(crate) fn set_capture(&mut self, capture: FrameCapture) {
+        if self.can_capture() {
+            self.capture = Some(capture);
+        }
+    }
+
+    pub(crate) fn set_clip(&mut self, clip: ClipBuffer) {
+        if self.can_capture() {
+            self.clip = Some(clip);
+        }
+    }
+
+    /// Writes the buffered frames as a GIF, if clips are enabled.
+    pub fn save_clip(&mut self) {
+        match &mut self.clip {
+            Some(clip) => clip.save(),
+            None => log::warn!("Clips are disabled; set a clip length to buffer frames"),
+        }
+    }
+
+    /// `color` is sRGB-encoded, like particle colors.
+    pub fn set_clear_color(&mut self, color: [f32; 3]) {
+        let [r, g, b] = match self.config.format.is_srgb() {
+            true => color.map(srgb_to_linear),
+            false => color,
         };
 
+        self.clear_color = Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        };
+    }
+
+    /// Draws every particle as a ring of at least `thickness` (a fraction of its radius); zero
+    /// leaves each particle's own outline.
+    pub fn set_outline_all(&mut self, thickness: f32) {
+        self.outline_all = thickness;
+        self.write_globals();
+    }
+
+    pub fn set_dpi(&mut self, dpi: f32) {
+        self.dpi = dpi;
+        self.write_globals();
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.write_globals();
+    }
+
+    /// Queues `text` for the next frame, anchored at `pos` in logical pixels from the top-left
+    /// corner of the window.
+    pub fn draw_text(&mut self, pos: Vec2, anchor: egui::Align2, text: impl Into<String>) {
+        self.texts.push(Text {
+            pos,
+            anchor,
+            text: text.into(),
+        });
+    }
+
+    /// The text queued since the last call.
+    pub(crate) fn take_texts(&mut self) -> Vec<Text> {
+        mem::take(&mut self.texts)
+    }
+
+    /// Reallocates the instance buffer for `capacity` particles. The particles have to be
+    /// uploaded again afterwards.
+    pub fn set_instance_capacity(&mut self, capacity: usize) {
+        self.instance_buffer = create_instance_buffer(&self.device, "Instance Buffer", capacity);
+        self.instance_capacity = capacity;
+        self.num_instances = 0;
+        self.instance_runs.clear();
+    }
+
+    fn write_globals(&self) {
+        let globals = Globals::new(&self.config, &self.camera, self.outline_all, self.dpi);
+
         self.queue
             .write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&globals));
     }
 
+    /// Call after [`Renderer::set_viewports`], since layers are sorted within each viewport.
     pub fn upload_instances(&mut self, particles: &[Particle]) {
-        self.num_instances = particles.len().min(MAX_INSTANCES);
+        self.num_instances = particles.len().min(self.instance_capacity);
+
+        let mut particles = Cow::Borrowed(&particles[..self.num_instances]);
+        let n = self.num_instances;
+        let ranges: Vec<_> = match self.viewports.is_empty() {
+            true => iter::once(0..n).collect(),
+            false => self
+                .viewports
+                .iter()
+                .map(|v| (v.instances.start as usize).min(n)..(v.instances.end as usize).min(n))
+                .collect(),
+        };
+
+        if particles.iter().any(|p| p.layer != 0) {
+            sort_by_layer(particles.to_mut(), ranges.clone());
+        }
+
+        self.instance_runs = layer_runs(&particles, ranges);
 
         let data = particles
             .iter()
-            .map(InstanceRaw::from_particle)
+            .map(InstanceRaw::from)
             .collect::<Vec<InstanceRaw>>();
 
-        self.queue.write_buffer(
-            &self.instance_buffer,
-            0,
-            bytemuck::cast_slice(&data[..self.num_instances]),
-        );
+        self.queue
+            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&data));
+    }
+
+    /// Grows the overlay buffer when `instances` do not fit.
+    pub fn upload_overlay(&mut self, instances: &[Particle]) {
+        if instances.len() > self.overlay_capacity {
+            self.overlay_capacity = instances.len().next_power_of_two();
+            self.overlay_buffer = create_instance_buffer(
+                &self.device,
+                "Overlay Instance Buffer",
+                self.overlay_capacity,
+            );
+        }
+
+        let mut instances = Cow::Borrowed(instances);
+
+        if instances.iter().any(|p| p.layer != 0) {
+            let n = instances.len();
+            sort_by_layer(instances.to_mut(), iter::once(0..n));
+        }
+
+        self.overlay_runs = layer_runs(&instances, iter::once(0..instances.len()));
+
+        let data = instances
+            .iter()
+            .map(InstanceRaw::from)
+            .collect::<Vec<InstanceRaw>>();
+
+        self.queue
+            .write_buffer(&self.overlay_buffer, 0, bytemuck::cast_slice(&data));
+    }
+
+    pub fn set_viewports(&mut self, viewports: Vec<Viewport>) {
+        self.viewports = viewports;
+    }
+
+    /// Uploads everything `simulation` draws as it is, without the window's color modes,
+    /// interpolation or letterboxing; for headless captures.
+    pub fn upload_simulation(&mut self, simulation: &impl Simulation) {
+        let (scenery, mut lines) = shape::partition(&simulation.scenery());
+
+        lines.extend(simulation.debug_lines());
+        self.set_viewports(simulation.viewports());
+        self.upload_instances(simulation.particles());
+        self.upload_overlay(&[scenery, simulation.overlay_instances().into_owned()].concat());
+        self.upload_lines(&lines);
+    }
+
+    /// Scissor rectangle of a viewport under the current camera in render target pixels, clamped
+    /// to the target, or `None` when it is off screen.
+    fn scissor(&self, viewport: &Viewport) -> Option<[u32; 4]> {
+        let [width, height] = self.target_size();
+        let pixels = self.dpi * self.supersample.scale() as f32;
+        let size = Vec2::new(width as f32, height as f32);
+        let screen = size / pixels;
+        let a = self.camera.world_to_screen(viewport.min, screen) * pixels;
+        let b = self.camera.world_to_screen(viewport.max, screen) * pixels;
+        let min = a.min(b).floor().clamp(Vec2::ZERO, size);
+        let max = a.max(b).ceil().clamp(Vec2::ZERO, size);
+
+        (max.x > min.x && max.y > min.y).then_some([
+            min.x as u32,
+            min.y as u32,
+            (max.x - min.x) as u32,
+            (max.y - min.y) as u32,
+        ])
+    }
+
+    /// Size of the texture the scene is drawn into, which is larger than the surface when
+    /// supersampling.
+    fn target_size(&self) -> [u32; 2] {
+        let scale = self.supersample.scale();
+
+        [self.config.width * scale, self.config.height * scale]
     }
 
-    pub fn render(&self) -> Result<(), SurfaceError> {
-        let frame = self.surface.get_current_texture()?;
+    pub fn upload_lines(&mut self, lines: &[Line]) {
+        self.num_lines = lines.len().min(MAX_LINES);
+
+        let mut lines = Cow::Borrowed(&lines[..self.num_lines]);
+
+        if lines.iter().any(|l| l.layer != 0) {
+            lines.to_mut().sort_by_key(|l| l.layer);
+        }
+
+        self.lines_below = lines.partition_point(|l| l.layer < 0);
+
+        let data = lines
+            .iter()
+            .map(LineRaw::from_line)
+            .collect::<Vec<LineRaw>>();
+
+        self.queue
+            .write_buffer(&self.line_buffer, 0, bytemuck::cast_slice(&data));
+    }
+
+    /// Draws the heatmap under everything else, or nothing when `None`.
+    pub(crate) fn upload_heatmap(&mut self, heatmap: Option<&Heatmap>) {
+        self.heatmap.upload(&self.device, &self.queue, heatmap);
+    }
+
+    /// Draws the scene and UI to the window. Headless renderers draw nothing; see
+    /// [`Renderer::render_to_image`].
+    pub(crate) fn render(&mut self, ui: &UiFrame) -> Result<(), SurfaceError> {
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+        let frame = surface.get_current_texture()?;
         let view = frame.texture.create_view(&TextureViewDescriptor::default());
 
         let mut encoder = self
@@ -244,19 +753,92 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        self.draw_scene(&mut encoder, &view);
+
+        // Copy before the UI pass so captured frames only contain the simulation.
+        let readback = (self.capture.is_some() || self.clip.is_some())
+            .then(|| Readback::encode(&self.device, &mut encoder, &frame.texture));
+
+        let ui_buffers = self.render_ui(&mut encoder, &view, ui);
+
+        self.queue
+            .submit(ui_buffers.into_iter().chain(iter::once(encoder.finish())));
+
+        if let Some(image) = readback.and_then(|r| r.read(&self.device)) {
+            match (&mut self.capture, &mut self.clip) {
+                (Some(capture), Some(clip)) => {
+                    clip.push(image.clone());
+                    capture.submit(image);
+                }
+                (Some(capture), None) => capture.submit(image),
+                (None, Some(clip)) => clip.push(image),
+                (None, None) => {}
+            }
+        }
+
+        frame.present();
+
+        for id in &ui.textures_delta.free {
+            self.egui_renderer.free_texture(id);
+        }
+
+        Ok(())
+    }
+
+    /// Draws the scene, without the UI, into an offscreen texture and reads it back. Works
+    /// without presenting, so headless runs can save frames too.
+    pub fn render_to_image(&mut self) -> Option<RgbaImage> {
+        if !Readback::supports(self.config.format) {
+            log::error!("Format {:?} cannot be read back", self.config.format);
+            return None;
+        }
+
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+
+        self.draw_scene(&mut encoder, &view);
+
+        let readback = Readback::encode(&self.device, &mut encoder, &texture);
+
+        self.queue.submit(iter::once(encoder.finish()));
+        readback.read(&self.device)
+    }
+
+    /// Records the scene pass into `view`, resolving supersampling into it.
+    fn draw_scene(&self, encoder: &mut CommandEncoder, view: &TextureView) {
         {
+            let scene = self.supersample.view().unwrap_or(view);
+            let (target, resolve_target) = match &self.msaa_view {
+                Some(msaa_view) => (msaa_view, Some(scene)),
+                None => (scene, None),
+            };
+
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: target,
+                    resolve_target,
                     ops: Operations {
-                        load: LoadOp::Clear(Color {
-                            r: 0.02,
-                            g: 0.02,
-                            b: 0.03,
-                            a: 1.0,
-                        }),
+                        load: LoadOp::Clear(self.clear_color),
                         store: StoreOp::Store,
                     },
                     depth_slice: None,
@@ -266,17 +848,291 @@ impl Renderer {
                 timestamp_writes: None,
             });
 
-            pass.set_pipeline(&self.pipeline);
             pass.set_bind_group(0, &self.globals_bg, &[]);
+            self.heatmap.draw(&mut pass);
             pass.set_vertex_buffer(0, self.quad_vb.slice(..));
-            pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
             pass.set_index_buffer(self.quad_ib.slice(..), IndexFormat::Uint16);
-            pass.draw_indexed(0..6, 0, 0..(self.num_instances as u32));
+
+            let (below, above) = (self.lines_below as u32, self.num_lines as u32);
+
+            if below > 0 {
+                pass.set_pipeline(&self.line_pipeline);
+                pass.set_vertex_buffer(1, self.line_buffer.slice(..));
+                pass.draw_indexed(0..6, 0, 0..below);
+            }
+
+            pass.set_pipeline(&self.pipeline);
+
+            // Overlays are drawn over the particles of their own layer.
+            let mut layers: Vec<i32> = self
+                .instance_runs
+                .iter()
+                .chain(&self.overlay_runs)
+                .map(|run| run.layer)
+                .collect();
+
+            layers.sort_unstable();
+            layers.dedup();
+
+            for layer in layers {
+                pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+
+                for run in self.instance_runs.iter().filter(|run| run.layer == layer) {
+                    if let Some(viewport) = self.viewports.get(run.group) {
+                        let Some([x, y, w, h]) = self.scissor(viewport) else {
+                            continue;
+                        };
+
+                        pass.set_scissor_rect(x, y, w, h);
+                    }
+
+                    pass.draw_indexed(0..6, 0, instance_range(&run.range));
+                }
+
+                if !self.viewports.is_empty() {
+                    let [width, height] = self.target_size();
+                    pass.set_scissor_rect(0, 0, width, height);
+                }
+
+                pass.set_vertex_buffer(1, self.overlay_buffer.slice(..));
+
+                for run in self.overlay_runs.iter().filter(|run| run.layer == layer) {
+                    pass.draw_indexed(0..6, 0, instance_range(&run.range));
+                }
+            }
+
+            if above > below {
+                pass.set_pipeline(&self.line_pipeline);
+                pass.set_vertex_buffer(1, self.line_buffer.slice(..));
+                pass.draw_indexed(0..6, 0, below..above);
+            }
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        frame.present();
+        self.supersample.resolve(encoder, view);
+    }
 
-        Ok(())
+    fn render_ui(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        ui: &UiFrame,
+    ) -> Vec<CommandBuffer> {
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: ui.pixels_per_point,
+        };
+
+        for (id, delta) in &ui.textures_delta.set {
+            self.egui_renderer
+                .update_texture(&self.device, &self.queue, *id, delta);
+        }
+
+        let buffers = self.egui_renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            encoder,
+            &ui.primitives,
+            &screen,
+        );
+
+        let mut pass = encoder
+            .begin_render_pass(&RenderPassDescriptor {
+                label: Some("UI Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            })
+            .forget_lifetime();
+
+        self.egui_renderer
+            .render(&mut pass, &ui.primitives, &screen);
+
+        buffers
     }
 }
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Prefers a hardware adapter and falls back to a software one.
+async fn request_adapter(
+    instance: &Instance,
+    surface: Option<&Surface<'_>>,
+) -> Result<Adapter, Error> {
+    let adapter = match instance
+        .request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::HighPerformance,
+            compatible_surface: surface,
+            force_fallback_adapter: false,
+        })
+        .await
+    {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            // Headless machines often only have a software adapter (e.g. llvmpipe).
+            log::warn!("No hardware adapter ({e}), retrying with the fallback adapter");
+
+            instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: PowerPreference::LowPower,
+                    compatible_surface: surface,
+                    force_fallback_adapter: true,
+                })
+                .await?
+        }
+    };
+
+    log::info!("Using adapter {:?}", adapter.get_info().name);
+
+    Ok(adapter)
+}
+
+async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), Error> {
+    Ok(adapter
+        .request_device(&DeviceDescriptor {
+            label: Some("WGPU Device"),
+            required_features: Features::empty(),
+            required_limits: Limits {
+                max_texture_dimension_2d: 4096,
+                ..Limits::downlevel_defaults()
+            },
+            memory_hints: MemoryHints::default(),
+            trace: Trace::Off,
+            experimental_features: ExperimentalFeatures::disabled(),
+        })
+        .await?)
+}
+
+fn select_present_mode(
+    supported: &[wgpu::PresentMode],
+    wanted: wgpu::PresentMode,
+) -> wgpu::PresentMode {
+    if supported.contains(&wanted) {
+        return wanted;
+    }
+
+    let fallback = if supported.contains(&wgpu::PresentMode::Fifo) {
+        wgpu::PresentMode::Fifo
+    } else {
+        supported[0]
+    };
+
+    log::warn!("Present mode {wanted:?} is not supported, using {fallback:?}");
+
+    fallback
+}
+
+/// A shader module and the entry points a pipeline runs from it.
+pub(crate) struct Stages<'a> {
+    pub module: &'a ShaderModule,
+    pub vertex: &'a str,
+    pub fragment: &'a str,
+}
+
+impl<'a> Stages<'a> {
+    /// `vs_main` and `fs_main`.
+    pub fn main(module: &'a ShaderModule) -> Self {
+        Self {
+            module,
+            vertex: "vs_main",
+            fragment: "fs_main",
+        }
+    }
+}
+
+pub(crate) fn create_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &PipelineLayout,
+    stages: Stages,
+    buffers: &[VertexBufferLayout],
+    format: TextureFormat,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: stages.module,
+            entry_point: Some(stages.vertex),
+            buffers,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: stages.module,
+            entry_point: Some(stages.fragment),
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        multisample: MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn instance_range(range: &Range<usize>) -> Range<u32> {
+    range.start as u32..range.end as u32
+}
+
+fn create_instance_buffer(device: &Device, label: &str, capacity: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: (capacity * mem::size_of::<InstanceRaw>()) as u64,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_msaa_view(
+    device: &Device,
+    config: &SurfaceConfiguration,
+    scale: u32,
+    sample_count: u32,
+) -> Option<TextureView> {
+    if sample_count <= 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("MSAA Color"),
+        size: Extent3d {
+            width: config.width.max(1) * scale,
+            height: config.height.max(1) * scale,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format: config.format,
+        usage: TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    Some(texture.create_view(&TextureViewDescriptor::default()))
+}
@@ -0,0 +1,41 @@
+// Per-sample entry points for the particle shader, appended to shader.wgsl when the adapter
+// supports multisampled shading. Circles are cut out with `discard`, so MSAA only smooths their
+// edges when the cut is made at every sample rather than once at the pixel center.
+
+struct VSOutSample {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) v_color: vec3<f32>,
+	@location(1) v_center_ndc: vec2<f32>,
+	@location(2) v_radius_ndc: vec2<f32>,
+	@location(3) @interpolate(perspective, sample) v_ndc: vec2<f32>,
+	@location(4) v_outline: f32,
+	@location(5) v_alpha: f32,
+};
+
+@vertex
+fn vs_sample(in: VSIn) -> VSOutSample {
+	let v = vertex(in);
+
+	return VSOutSample(
+		v.clip_position,
+		v.v_color,
+		v.v_center_ndc,
+		v.v_radius_ndc,
+		v.v_ndc,
+		v.v_outline,
+		v.v_alpha
+	);
+}
+
+@fragment
+fn fs_sample(in: VSOutSample) -> @location(0) vec4<f32> {
+	return shade(VSOut(
+		in.clip_position,
+		in.v_color,
+		in.v_center_ndc,
+		in.v_radius_ndc,
+		in.v_ndc,
+		in.v_outline,
+		in.v_alpha
+	));
+}
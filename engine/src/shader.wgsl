@@ -1,6 +1,12 @@
 struct Globals {
 	screen_wh: vec2<f32>, 
-	_pad: vec2<f32>,
+	offset: vec2<f32>,
+	scale: f32,
+	// 1.0 when rendering to an sRGB surface, which expects linear colors.
+	srgb_target: f32,
+	// Minimum ring thickness for every particle, as a fraction of the radius.
+	outline_all: f32,
+	_pad1: f32,
 };
 @group(0) @binding(0) var<uniform> U: Globals;
 
@@ -9,46 +15,75 @@ struct VSOut {
 	@location(0) v_color: vec3<f32>,
 	@location(1) v_center_ndc: vec2<f32>,
 	@location(2) v_radius_ndc: vec2<f32>,
-	@location(3) v_ndc: vec2<f32>,        
+	@location(3) @interpolate(perspective, center) v_ndc: vec2<f32>,
+	@location(4) v_outline: f32,
+	@location(5) v_alpha: f32,
 };
 
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+	let lo = c / 12.92;
+	let hi = pow((c + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+
+	return select(hi, lo, c <= vec3<f32>(0.04045));
+}
+
 fn px_to_ndc(px: vec2<f32>) -> vec2<f32> {
 	let half = 0.5 * U.screen_wh;
+	let view = (px - U.offset) * U.scale;
 
-	return vec2<f32>(px.x / half.x, px.y / half.y);
+	return vec2<f32>(view.x / half.x, view.y / half.y);
 }
 
-@vertex
-fn vs_main(
+struct VSIn {
 	@location(0) quad_pos: vec2<f32>,
-	@location(1) i_pos_px: vec2<f32>,   
-	@location(2) i_radius_px: f32,     
+	@location(1) i_pos_px: vec2<f32>,
+	@location(2) i_radius_px: f32,
 	@location(3) i_color: vec3<f32>,
-) -> VSOut {
+	@location(4) i_outline: f32,
+	@location(5) i_alpha: f32,
+};
+
+fn vertex(in: VSIn) -> VSOut {
 	var out: VSOut;
 
-	let local_px = quad_pos * i_radius_px;  
-	let world_px = i_pos_px + local_px;
+	let local_px = in.quad_pos * in.i_radius_px;
+	let world_px = in.i_pos_px + local_px;
 
 	let ndc = px_to_ndc(world_px);
 	out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
 
-	out.v_center_ndc = px_to_ndc(i_pos_px);
-	out.v_radius_ndc = 2.0 * vec2<f32>(i_radius_px) / U.screen_wh; 
+	out.v_center_ndc = px_to_ndc(in.i_pos_px);
+	out.v_radius_ndc = 2.0 * vec2<f32>(in.i_radius_px * U.scale) / U.screen_wh;
 	out.v_ndc = ndc;
+	out.v_outline = max(in.i_outline, U.outline_all);
+	out.v_alpha = in.i_alpha;
 
-	out.v_color = i_color;
+	out.v_color = select(in.i_color, srgb_to_linear(in.i_color), U.srgb_target > 0.5);
 	return out;
 }
 
-@fragment
-fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+fn shade(in: VSOut) -> vec4<f32> {
 	let d = vec2<f32>(
 		(in.v_ndc.x - in.v_center_ndc.x) / in.v_radius_ndc.x,
 		(in.v_ndc.y - in.v_center_ndc.y) / in.v_radius_ndc.y
 	);
 
-	if (dot(d, d) > 1.0) { discard; }
+	let r2 = dot(d, d);
+	let inner = 1.0 - in.v_outline;
 
-	return vec4<f32>(in.v_color, 1.0);
+	if (r2 > 1.0) { discard; }
+	// Rings: drop the interior, keeping `v_outline` of the radius as the band.
+	if (in.v_outline > 0.0 && r2 < inner * inner) { discard; }
+
+	return vec4<f32>(in.v_color, in.v_alpha);
+}
+
+@vertex
+fn vs_main(in: VSIn) -> VSOut {
+	return vertex(in);
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+	return shade(in);
 }
@@ -0,0 +1,168 @@
+use glam::Vec2;
+
+use crate::{line::Line, particle::Particle};
+
+/// Something drawn besides the simulated particles, e.g. a wall or an obstacle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: [f32; 3],
+    },
+    /// A ring `width` wide centered on the circle of `radius`.
+    Ring {
+        center: Vec2,
+        radius: f32,
+        width: f32,
+        color: [f32; 3],
+    },
+    Segment(Line),
+}
+
+/// Anything that can be turned into shapes for [`crate::Simulation::scenery`].
+pub trait Renderable {
+    fn shapes(&self, out: &mut Vec<Shape>);
+}
+
+impl Renderable for Shape {
+    fn shapes(&self, out: &mut Vec<Shape>) {
+        out.push(*self);
+    }
+}
+
+impl Renderable for Line {
+    fn shapes(&self, out: &mut Vec<Shape>) {
+        out.push(Shape::Segment(*self));
+    }
+}
+
+impl Renderable for Particle {
+    fn shapes(&self, out: &mut Vec<Shape>) {
+        out.push(match self.outline > 0.0 {
+            true => {
+                let width = self.outline * self.radius;
+
+                Shape::Ring {
+                    center: self.position,
+                    radius: self.radius - 0.5 * width,
+                    width,
+                    color: self.color,
+                }
+            }
+            false => Shape::Circle {
+                center: self.position,
+                radius: self.radius,
+                color: self.color,
+            },
+        });
+    }
+}
+
+/// A closed polyline, e.g. a [`crate::boundary::BoundaryCondition::outline`].
+pub struct Outline<'a> {
+    pub points: &'a [Vec2],
+    pub color: [f32; 3],
+}
+
+impl Renderable for Outline<'_> {
+    fn shapes(&self, out: &mut Vec<Shape>) {
+        let n = self.points.len();
+
+        out.extend((0..n).map(|k| {
+            Shape::Segment(Line::new(
+                self.points[k],
+                self.points[(k + 1) % n],
+                self.color,
+            ))
+        }));
+    }
+}
+
+impl Shape {
+    pub fn translated(self, offset: Vec2) -> Self {
+        match self {
+            Shape::Circle {
+                center,
+                radius,
+                color,
+            } => Shape::Circle {
+                center: center + offset,
+                radius,
+                color,
+            },
+            Shape::Ring {
+                center,
+                radius,
+                width,
+                color,
+            } => Shape::Ring {
+                center: center + offset,
+                radius,
+                width,
+                color,
+            },
+            Shape::Segment(l) => Shape::Segment(Line {
+                start: l.start + offset,
+                end: l.end + offset,
+                ..l
+            }),
+        }
+    }
+}
+
+/// Splits shapes into the instances and lines the renderer draws them with.
+#[cfg(feature = "render")]
+pub(crate) fn partition(shapes: &[Shape]) -> (Vec<Particle>, Vec<Line>) {
+    let mut instances = Vec::new();
+    let mut lines = Vec::new();
+
+    for shape in shapes {
+        match *shape {
+            Shape::Circle {
+                center,
+                radius,
+                color,
+            } => instances.push(Particle::new(center, Vec2::ZERO, radius, 0.0, color)),
+            Shape::Ring {
+                center,
+                radius,
+                width,
+                color,
+            } => {
+                let outer = radius + 0.5 * width;
+
+                instances.push(Particle {
+                    outline: width / outer,
+                    ..Particle::new(center, Vec2::ZERO, outer, 0.0, color)
+                });
+            }
+            Shape::Segment(line) => lines.push(line),
+        }
+    }
+
+    (instances, lines)
+}
+
+#[cfg(all(test, feature = "render"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rings_round_trip_through_particles() {
+        let ring = Shape::Ring {
+            center: Vec2::new(1.0, 2.0),
+            radius: 10.0,
+            width: 2.0,
+            color: [1.0; 3],
+        };
+        let (instances, lines) = partition(&[ring]);
+        let mut shapes = Vec::new();
+
+        instances[0].shapes(&mut shapes);
+
+        assert!(lines.is_empty());
+        assert_eq!(instances[0].radius, 11.0);
+        assert_eq!(shapes, [ring]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+
+use glam::Vec2;
+
+#[cfg(feature = "render")]
+use crate::KeyCode;
+use crate::{
+    Bounds, Simulation, StepResult, context::Context, line::Line, particle::Particle, shape::Shape,
+};
+
+const DIVIDER_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+fn shift(d: Vec2) -> impl Fn(&Particle) -> Particle {
+    move |p| Particle {
+        position: p.position + d,
+        ..*p
+    }
+}
+
+/// Runs two simulations side by side, each in its own half of the window with half the width as
+/// its bounds. Both share the camera, pause state and time step, so they stay in lockstep.
+pub struct SplitScreen<L, R> {
+    left: L,
+    right: R,
+    bounds: Bounds,
+    particles: Vec<Particle>,
+}
+
+impl<L: Simulation, R: Simulation> SplitScreen<L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left,
+            right,
+            bounds: Bounds {
+                width: 0.0,
+                height: 0.0,
+            },
+            particles: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> (L, R) {
+        (self.left, self.right)
+    }
+
+    fn half(bounds: Bounds) -> Bounds {
+        Bounds {
+            width: bounds.width / 2.0,
+            height: bounds.height,
+        }
+    }
+
+    /// World offset of the right half's origin; the left half uses the negation.
+    fn offset(&self) -> Vec2 {
+        Vec2::new(self.bounds.width / 4.0, 0.0)
+    }
+
+    fn collect_particles(&mut self) {
+        let offset = self.offset();
+
+        self.particles.clear();
+        self.particles
+            .extend(self.left.particles().iter().map(shift(-offset)));
+        self.particles
+            .extend(self.right.particles().iter().map(shift(offset)));
+    }
+}
+
+impl<L: Simulation, R: Simulation> Simulation for SplitScreen<L, R> {
+    fn init(&mut self, ctx: &mut Context, bounds: Bounds) {
+        self.bounds = bounds;
+        self.left.init(ctx, Self::half(bounds));
+        self.right.init(ctx, Self::half(bounds));
+        self.collect_particles();
+    }
+
+    fn step(&mut self, ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult {
+        self.bounds = bounds;
+
+        let left = self.left.step(ctx, dt, Self::half(bounds));
+        let right = self.right.step(ctx, dt, Self::half(bounds));
+
+        self.collect_particles();
+        left.or(right)
+    }
+
+    #[cfg(feature = "render")]
+    fn init_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.left.init_gpu(device, queue);
+        self.right.init_gpu(device, queue);
+    }
+
+    #[cfg(feature = "render")]
+    fn step_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, dt: f32) {
+        self.left.step_gpu(device, queue, dt);
+        self.right.step_gpu(device, queue, dt);
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    fn poll(&mut self) -> bool {
+        let changed = self.left.poll() | self.right.poll();
+
+        if changed {
+            self.collect_particles();
+        }
+
+        changed
+    }
+
+    #[cfg(feature = "render")]
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Left", |ui| self.left.debug_ui(ui));
+        ui.collapsing("Right", |ui| self.right.debug_ui(ui));
+    }
+
+    #[cfg(feature = "render")]
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.left.ui(ctx);
+        self.right.ui(ctx);
+    }
+
+    fn hud(&self) -> Vec<String> {
+        let left = self.left.hud().into_iter().map(|l| format!("L {l}"));
+        let right = self.right.hud().into_iter().map(|l| format!("R {l}"));
+
+        left.chain(right).collect()
+    }
+
+    #[cfg(feature = "render")]
+    fn key_pressed(&mut self, key: KeyCode) {
+        self.left.key_pressed(key);
+        self.right.key_pressed(key);
+    }
+
+    fn clicked(&mut self, world: Vec2) {
+        let offset = self.offset();
+
+        if world.x < 0.0 {
+            self.left.clicked(world + offset);
+        } else {
+            self.right.clicked(world - offset);
+        }
+    }
+
+    fn selected_rect(&mut self, min: Vec2, max: Vec2) {
+        let offset = self.offset();
+
+        if (min.x + max.x) < 0.0 {
+            self.left.selected_rect(min + offset, max + offset);
+        } else {
+            self.right.selected_rect(min - offset, max - offset);
+        }
+    }
+
+    fn focus(&self) -> Option<Vec2> {
+        let offset = self.offset();
+
+        self.left
+            .focus()
+            .map(|f| f - offset)
+            .or_else(|| self.right.focus().map(|f| f + offset))
+    }
+
+    fn take_impacts(&mut self) -> Vec<Vec2> {
+        let offset = self.offset();
+        let left = self.left.take_impacts().into_iter().map(|p| p - offset);
+        let right = self.right.take_impacts().into_iter().map(|p| p + offset);
+
+        left.chain(right).collect()
+    }
+
+    fn quiescent(&self) -> bool {
+        self.left.quiescent() && self.right.quiescent()
+    }
+
+    fn on_exit(&mut self) {
+        self.left.on_exit();
+        self.right.on_exit();
+    }
+
+    fn overlay_instances(&self) -> Cow<'_, [Particle]> {
+        let offset = self.offset();
+        let left = self.left.overlay_instances();
+        let right = self.right.overlay_instances();
+
+        if left.is_empty() && right.is_empty() {
+            return Cow::Borrowed(&[]);
+        }
+
+        Cow::Owned(
+            left.iter()
+                .map(shift(-offset))
+                .chain(right.iter().map(shift(offset)))
+                .collect(),
+        )
+    }
+
+    fn scenery(&self) -> Vec<Shape> {
+        let offset = self.offset();
+        let left = self
+            .left
+            .scenery()
+            .into_iter()
+            .map(|s| s.translated(-offset));
+        let right = self
+            .right
+            .scenery()
+            .into_iter()
+            .map(|s| s.translated(offset));
+
+        left.chain(right).collect()
+    }
+
+    fn debug_lines(&self) -> Vec<Line> {
+        let offset = self.offset();
+        let shift = |d: Vec2| {
+            move |l: Line| Line {
+                start: l.start + d,
+                end: l.end + d,
+                ..l
+            }
+        };
+        let half_height = self.bounds.height / 2.0;
+
+        self.left
+            .debug_lines()
+            .into_iter()
+            .map(shift(-offset))
+            .chain(self.right.debug_lines().into_iter().map(shift(offset)))
+            .chain([Line::new(
+                Vec2::new(0.0, -half_height),
+                Vec2::new(0.0, half_height),
+                DIVIDER_COLOR,
+            )])
+            .collect()
+    }
+}
@@ -0,0 +1,197 @@
+use wgpu::*;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    scale: u32,
+    _pad: [u32; 3],
+}
+
+/// Offscreen target `scale` times the surface size that the scene is drawn into, box-filtered
+/// down onto the surface afterwards. A scale of one draws straight to the surface.
+pub struct Supersample {
+    requested: u32,
+    scale: u32,
+    pipeline: RenderPipeline,
+    bgl: BindGroupLayout,
+    params: Buffer,
+    target: Option<(TextureView, BindGroup)>,
+}
+
+impl Supersample {
+    pub fn new(device: &Device, format: TextureFormat, scale: u32) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("downsample.wgsl"));
+        let bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Downsample BGL"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Downsample Layout"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("downsample pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: MultisampleState::default(),
+            depth_stencil: None,
+            multiview: None,
+            cache: None,
+        });
+        let params = device.create_buffer(&BufferDescriptor {
+            label: Some("Downsample Params"),
+            size: size_of::<Params>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            requested: scale.max(1),
+            scale: 1,
+            pipeline,
+            bgl,
+            params,
+            target: None,
+        }
+    }
+
+    /// Current factor; lower than requested when the scaled target would exceed the device's
+    /// texture size limit.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The view to draw the scene into instead of the surface, if supersampling.
+    pub fn view(&self) -> Option<&TextureView> {
+        self.target.as_ref().map(|(view, _)| view)
+    }
+
+    /// Recreates the offscreen target for a surface of `config`'s size.
+    pub fn resize(&mut self, device: &Device, queue: &Queue, config: &SurfaceConfiguration) {
+        let max_dim = device.limits().max_texture_dimension_2d;
+        let largest = config.width.max(config.height).max(1);
+        let scale = self.requested.min(max_dim / largest).max(1);
+
+        if scale < self.requested && self.scale != scale {
+            log::warn!(
+                "{}x render scale exceeds the texture limit at {}x{}, using {scale}x",
+                self.requested,
+                config.width,
+                config.height
+            );
+        }
+
+        self.scale = scale;
+        self.target = None;
+
+        if scale == 1 {
+            return;
+        }
+
+        queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&Params {
+                scale,
+                _pad: [0; 3],
+            }),
+        );
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Supersample Color"),
+            size: Extent3d {
+                width: config.width.max(1) * scale,
+                height: config.height.max(1) * scale,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Downsample BG"),
+            layout: &self.bgl,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        });
+
+        self.target = Some((view, bind_group));
+    }
+
+    /// Filters the offscreen target down onto `surface`. Does nothing at scale one.
+    pub fn resolve(&self, encoder: &mut CommandEncoder, surface: &TextureView) {
+        let Some((_, bind_group)) = &self.target else {
+            return;
+        };
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Downsample Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,503 @@
+//! Steps a simulation on a worker thread, so a slow step does not hold up the event loop.
+//!
+//! The event loop keeps drawing the state of the last finished step while the next one runs.
+//! Steps requested while the worker is busy are queued and run back to back, so every `dt` is
+//! still simulated. Input (clicks, keys, selections) reaches the simulation right away when the
+//! worker is idle and just before its next step otherwise. Steps see a copy of the [`Context`]
+//! taken when the first of them is queued; changes they make to it are not sent back.
+
+use std::{
+    borrow::Cow,
+    mem,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        mpsc::{self, Receiver, Sender, TryRecvError},
+    },
+    thread::{self, JoinHandle},
+};
+
+use glam::Vec2;
+
+#[cfg(feature = "render")]
+use crate::KeyCode;
+use crate::{
+    Bounds, Simulation, StepResult, context::Context, grid::Viewport, line::Line,
+    particle::Particle, shape::Shape,
+};
+
+type Command<S> = Box<dyn FnOnce(&mut S) + Send>;
+
+/// Everything drawn from a simulation, copied out after it stepped. Two of these are swapped
+/// between the worker and the event loop.
+#[derive(Default)]
+struct Snapshot {
+    particles: Vec<Particle>,
+    overlay: Vec<Particle>,
+    scenery: Vec<Shape>,
+    debug_lines: Vec<Line>,
+    viewports: Vec<Viewport>,
+    labels: Vec<(Vec2, String)>,
+    hud: Vec<String>,
+    focus: Option<Vec2>,
+    quiescent: bool,
+    impacts: Vec<Vec2>,
+    result: StepResult,
+}
+
+impl Snapshot {
+    fn capture(&mut self, sim: &mut impl Simulation) {
+        self.particles.clear();
+        self.particles.extend_from_slice(sim.particles());
+        self.overlay.clear();
+        self.overlay.extend_from_slice(&sim.overlay_instances());
+        self.scenery = sim.scenery();
+        self.debug_lines = sim.debug_lines();
+        self.viewports = sim.viewports();
+        self.labels = sim.labels();
+        self.hud = sim.hud();
+        self.focus = sim.focus();
+        self.quiescent = sim.quiescent();
+        self.impacts.extend(sim.take_impacts());
+    }
+}
+
+struct Request<S> {
+    ctx: Context,
+    bounds: Bounds,
+    dts: Vec<f32>,
+    commands: Vec<Command<S>>,
+    #[cfg(feature = "render")]
+    gpu: Option<(wgpu::Device, wgpu::Queue)>,
+    /// Filled in by the worker and sent back.
+    snapshot: Snapshot,
+}
+
+pub struct Threaded<S> {
+    sim: Arc<Mutex<S>>,
+    requests: Option<Sender<Request<S>>>,
+    results: Receiver<Snapshot>,
+    worker: Option<JoinHandle<()>>,
+    front: Snapshot,
+    /// The snapshot the worker fills next, while it is not out with a request.
+    back: Snapshot,
+    busy: bool,
+    /// Steps and input waiting for the worker to finish the current request.
+    dts: Vec<f32>,
+    commands: Vec<Command<S>>,
+    ctx: Context,
+    bounds: Bounds,
+    #[cfg(feature = "render")]
+    gpu: Option<(wgpu::Device, wgpu::Queue)>,
+    impacts: Vec<Vec2>,
+    result: StepResult,
+}
+
+impl<S: Simulation + Send + 'static> Threaded<S> {
+    pub fn new(sim: S) -> Self {
+        let sim = Arc::new(Mutex::new(sim));
+        let (requests, inbox) = mpsc::channel();
+        let (outbox, results) = mpsc::channel();
+        let worker = {
+            let sim = sim.clone();
+
+            thread::Builder::new()
+                .name("simulation".to_string())
+                .spawn(move || work(&sim, inbox, outbox))
+                .expect("failed to spawn the simulation thread")
+        };
+
+        Self {
+            sim,
+            requests: Some(requests),
+            results,
+            worker: Some(worker),
+            front: Snapshot::default(),
+            back: Snapshot::default(),
+            busy: false,
+            dts: Vec::new(),
+            commands: Vec::new(),
+            ctx: Context::default(),
+            bounds: Bounds {
+                width: 0.0,
+                height: 0.0,
+            },
+            #[cfg(feature = "render")]
+            gpu: None,
+            impacts: Vec::new(),
+            result: StepResult::Continue,
+        }
+    }
+
+    /// Waits for the steps in flight and returns the simulation.
+    pub fn into_inner(mut self) -> S {
+        self.join();
+
+        let sim = self.sim.clone();
+
+        drop(self);
+
+        match Arc::try_unwrap(sim) {
+            Ok(sim) => sim.into_inner().unwrap_or_else(PoisonError::into_inner),
+            Err(_) => unreachable!("the worker has been joined"),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, S> {
+        self.sim.lock().expect("the simulation thread panicked")
+    }
+
+    /// Takes the worker's result if it is done and hands it the queued steps.
+    fn collect(&mut self) -> bool {
+        if !self.busy {
+            return false;
+        }
+
+        match self.results.try_recv() {
+            Ok(snapshot) => self.accept(snapshot),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => panic!("the simulation thread panicked"),
+        }
+
+        true
+    }
+
+    /// Takes in the worker's finished request and hands it the steps queued meanwhile.
+    fn accept(&mut self, mut snapshot: Snapshot) {
+        self.busy = false;
+        self.impacts.append(&mut snapshot.impacts);
+        self.result = self.result.or(mem::take(&mut snapshot.result));
+        self.back = mem::replace(&mut self.front, snapshot);
+
+        if !self.dts.is_empty() {
+            self.dispatch();
+        }
+    }
+
+    fn dispatch(&mut self) {
+        let request = Request {
+            ctx: self.ctx.clone(),
+            bounds: self.bounds,
+            dts: mem::take(&mut self.dts),
+            commands: mem::take(&mut self.commands),
+            #[cfg(feature = "render")]
+            gpu: self.gpu.clone(),
+            snapshot: mem::take(&mut self.back),
+        };
+
+        if let Some(requests) = &self.requests {
+            self.busy = requests.send(request).is_ok();
+        }
+    }
+
+    /// Runs `command` now if the worker is idle, otherwise right before its next step.
+    fn send(&mut self, command: impl FnOnce(&mut S) + Send + 'static) {
+        self.collect();
+
+        if self.busy {
+            self.commands.push(Box::new(command));
+            return;
+        }
+
+        let sim = self.sim.clone();
+        let mut sim = sim.lock().expect("the simulation thread panicked");
+
+        command(&mut sim);
+        self.front.capture(&mut *sim);
+        self.impacts.append(&mut self.front.impacts);
+    }
+
+    /// Waits for the worker to run every queued step, then stops it and applies the commands
+    /// still waiting for a step.
+    fn join(&mut self) {
+        while self.busy {
+            match self.results.recv() {
+                Ok(snapshot) => self.accept(snapshot),
+                Err(_) => break,
+            }
+        }
+
+        self.requests = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+
+        let mut sim = self.sim.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for command in self.commands.drain(..) {
+            command(&mut sim);
+        }
+    }
+}
+
+fn work<S: Simulation>(sim: &Mutex<S>, requests: Receiver<Request<S>>, results: Sender<Snapshot>) {
+    for mut request in requests {
+        let mut sim = sim.lock().expect("the simulation panicked");
+        let mut result = StepResult::Continue;
+
+        for command in request.commands {
+            command(&mut sim);
+        }
+
+        for &dt in &request.dts {
+            result = result.or(request.ctx.run_step(&mut *sim, dt, request.bounds));
+
+            if result == StepResult::Exit {
+                break;
+            }
+        }
+
+        #[cfg(feature = "render")]
+        if let Some((device, queue)) = &request.gpu {
+            sim.step_gpu(device, queue, request.dts.iter().sum());
+        }
+
+        request.snapshot.capture(&mut *sim);
+        request.snapshot.result = result;
+
+        if results.send(request.snapshot).is_err() {
+            break;
+        }
+    }
+}
+
+impl<S: Simulation + Send + 'static> Simulation for Threaded<S> {
+    fn init(&mut self, ctx: &mut Context, bounds: Bounds) {
+        self.bounds = bounds;
+
+        let sim = self.sim.clone();
+        let mut sim = sim.lock().expect("the simulation thread panicked");
+
+        sim.init(ctx, bounds);
+        self.front.capture(&mut *sim);
+    }
+
+    fn step(&mut self, ctx: &mut Context, dt: f32, bounds: Bounds) -> StepResult {
+        self.collect();
+
+        // The worker counts the queued steps itself, starting from the frame of the first one.
+        if self.dts.is_empty() {
+            self.ctx = ctx.clone();
+        }
+
+        self.bounds = bounds;
+        self.dts.push(dt);
+
+        if !self.busy {
+            self.dispatch();
+        }
+
+        mem::take(&mut self.result)
+    }
+
+    fn particles(&self) -> &[Particle] {
+        &self.front.particles
+    }
+
+    fn poll(&mut self) -> bool {
+        let changed = self.collect();
+
+        if !self.busy && !self.commands.is_empty() {
+            let commands = mem::take(&mut self.commands);
+
+            self.send(move |sim| commands.into_iter().for_each(|c| c(sim)));
+            return true;
+        }
+
+        changed
+    }
+
+    #[cfg(feature = "render")]
+    fn init_gpu(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.lock().init_gpu(device, queue);
+        self.gpu = Some((device.clone(), queue.clone()));
+    }
+
+    /// Done by the worker after each request's steps.
+    #[cfg(feature = "render")]
+    fn step_gpu(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue, _dt: f32) {}
+
+    /// Only shown while the worker is idle; the UI needs the simulation itself.
+    #[cfg(feature = "render")]
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        self.collect();
+
+        match self.busy {
+            true => {
+                ui.label("Stepping...");
+            }
+            false => {
+                let sim = self.sim.clone();
+                let mut sim = sim.lock().expect("the simulation thread panicked");
+
+                sim.debug_ui(ui);
+                self.front.capture(&mut *sim);
+                self.impacts.append(&mut self.front.impacts);
+            }
+        }
+    }
+
+    /// Only drawn while the worker is idle, like the debug UI.
+    #[cfg(feature = "render")]
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.collect();
+
+        if !self.busy {
+            let sim = self.sim.clone();
+            let mut sim = sim.lock().expect("the simulation thread panicked");
+
+            sim.ui(ctx);
+        }
+    }
+
+    fn hud(&self) -> Vec<String> {
+        self.front.hud.clone()
+    }
+
+    #[cfg(feature = "render")]
+    fn key_pressed(&mut self, key: KeyCode) {
+        self.send(move |sim| sim.key_pressed(key));
+    }
+
+    fn clicked(&mut self, world: Vec2) {
+        self.send(move |sim| sim.clicked(world));
+    }
+
+    fn selected_rect(&mut self, min: Vec2, max: Vec2) {
+        self.send(move |sim| sim.selected_rect(min, max));
+    }
+
+    fn focus(&self) -> Option<Vec2> {
+        self.front.focus
+    }
+
+    fn debug_lines(&self) -> Vec<Line> {
+        self.front.debug_lines.clone()
+    }
+
+    fn scenery(&self) -> Vec<Shape> {
+        self.front.scenery.clone()
+    }
+
+    fn overlay_instances(&self) -> Cow<'_, [Particle]> {
+        Cow::Borrowed(&self.front.overlay)
+    }
+
+    fn viewports(&self) -> Vec<Viewport> {
+        self.front.viewports.clone()
+    }
+
+    fn labels(&self) -> Vec<(Vec2, String)> {
+        self.front.labels.clone()
+    }
+
+    fn take_impacts(&mut self) -> Vec<Vec2> {
+        mem::take(&mut self.impacts)
+    }
+
+    fn quiescent(&self) -> bool {
+        !self.busy && self.dts.is_empty() && self.front.quiescent
+    }
+
+    fn on_exit(&mut self) {
+        self.join();
+        self.lock().on_exit();
+    }
+}
+
+impl<S> Drop for Threaded<S> {
+    fn drop(&mut self) {
+        self.requests = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Bounds = Bounds {
+        width: 100.0,
+        height: 100.0,
+    };
+
+    /// One particle moving right at 10 units/s, counting its steps.
+    struct Mover {
+        particles: Vec<Particle>,
+        steps: usize,
+    }
+
+    impl Simulation for Mover {
+        fn init(&mut self, _ctx: &mut Context, _bounds: Bounds) {}
+
+        fn step(&mut self, _ctx: &mut Context, dt: f32, _bounds: Bounds) -> StepResult {
+            self.steps += 1;
+            self.particles[0].position.x += 10.0 * dt;
+
+            match self.steps >= 3 {
+                true => StepResult::Exit,
+                false => StepResult::Continue,
+            }
+        }
+
+        fn particles(&self) -> &[Particle] {
+            &self.particles
+        }
+
+        fn clicked(&mut self, world: Vec2) {
+            self.particles[0].position = world;
+        }
+    }
+
+    fn mover() -> Threaded<Mover> {
+        let particle = Particle::new(Vec2::ZERO, Vec2::ZERO, 1.0, 1.0, [1.0; 3]);
+
+        Threaded::new(Mover {
+            particles: vec![particle],
+            steps: 0,
+        })
+    }
+
+    #[test]
+    fn runs_every_queued_step() {
+        let (mut sim, mut ctx) = (mover(), Context::default());
+
+        sim.init(&mut ctx, BOUNDS);
+
+        // Holding the simulation keeps the worker on the first step while the others queue up.
+        let held = sim.sim.clone();
+        let guard = held.lock().unwrap();
+
+        sim.step(&mut ctx, 0.1, BOUNDS);
+        sim.step(&mut ctx, 0.1, BOUNDS);
+        sim.clicked(Vec2::new(50.0, 0.0));
+        sim.step(&mut ctx, 0.1, BOUNDS);
+        assert!(sim.busy && sim.dts.len() == 2);
+
+        drop(guard);
+        drop(held);
+        sim.on_exit();
+
+        assert_eq!(sim.step(&mut ctx, 0.0, BOUNDS), StepResult::Exit);
+
+        let sim = sim.into_inner();
+
+        assert_eq!(sim.steps, 3);
+        assert!(sim.particles[0].position.x >= 50.0);
+    }
+
+    #[test]
+    fn draws_the_last_finished_step() {
+        let (mut sim, mut ctx) = (mover(), Context::default());
+
+        sim.init(&mut ctx, BOUNDS);
+        sim.step(&mut ctx, 1.0, BOUNDS);
+
+        while !sim.poll() {}
+
+        assert_eq!(sim.particles()[0].position.x, 10.0);
+    }
+}
@@ -0,0 +1,286 @@
+use std::borrow::Cow;
+
+use glam::Vec2;
+
+use crate::{Bounds, Simulation, StepResult, context::Context, particle::Particle};
+
+/// Steps further behind than this are dropped instead of being caught up on.
+const MAX_STEPS_PER_FRAME: usize = 8;
+
+/// Runs the simulation at a constant dt accumulated from wall time, so results do not depend on
+/// the render frame rate.
+pub struct FixedTimestep {
+    dt: f32,
+    accumulator: f32,
+    previous: Vec<Vec2>,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32) -> Self {
+        Self {
+            dt,
+            accumulator: 0.0,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Banks `elapsed` and spends it in whole steps. While paused, a single step always
+    /// advances exactly one dt. Stops early once a step asks to exit. While the steps run, the
+    /// frame and render times in `ctx` are split evenly across them, so per-step timings add up
+    /// to the frame; the whole times are back in place afterwards.
+    pub fn advance<S: Simulation>(
+        &mut self,
+        simulation: &mut S,
+        ctx: &mut Context,
+        elapsed: f32,
+        bounds: Bounds,
+    ) -> StepResult {
+        if ctx.is_paused() {
+            self.accumulator = 0.0;
+            return self.step(simulation, ctx, bounds);
+        }
+
+        self.accumulator += elapsed;
+
+        let due = ((self.accumulator / self.dt) as u32).clamp(1, MAX_STEPS_PER_FRAME as u32);
+        let (frame_time, render_time) = (ctx.frame_time(), ctx.render_time());
+
+        ctx.set_frame_time(frame_time / due);
+        ctx.set_render_time(render_time / due);
+
+        let result = self.spend(simulation, ctx, bounds);
+
+        ctx.set_frame_time(frame_time);
+        ctx.set_render_time(render_time);
+        result
+    }
+
+    /// Takes as many whole steps as the accumulator holds, up to the per-frame limit.
+    fn spend<S: Simulation>(
+        &mut self,
+        simulation: &mut S,
+        ctx: &mut Context,
+        bounds: Bounds,
+    ) -> StepResult {
+        for _ in 0..MAX_STEPS_PER_FRAME {
+            if self.accumulator < self.dt {
+                return StepResult::Continue;
+            }
+
+            self.accumulator -= self.dt;
+
+            if self.step(simulation, ctx, bounds) == StepResult::Exit {
+                return StepResult::Exit;
+            }
+        }
+
+        self.accumulator = self.accumulator.min(self.dt);
+        StepResult::Continue
+    }
+
+    fn step<S: Simulation>(
+        &mut self,
+        simulation: &mut S,
+        ctx: &mut Context,
+        bounds: Bounds,
+    ) -> StepResult {
+        self.previous.clear();
+        self.previous
+            .extend(simulation.particles().iter().map(|p| p.position));
+        ctx.run_step(simulation, self.dt, bounds)
+    }
+
+    /// Positions blended between the last two steps by how far wall time has run into the next.
+    /// While paused no time runs, so the latest step is shown as is.
+    pub fn interpolate<'a>(
+        &self,
+        ctx: &Context,
+        particles: &'a [Particle],
+        bounds: Bounds,
+    ) -> Cow<'a, [Particle]> {
+        if ctx.is_paused() || self.previous.len() != particles.len() {
+            return Cow::Borrowed(particles);
+        }
+
+        let alpha = (self.accumulator / self.dt).clamp(0.0, 1.0);
+        let max_jump = 0.25 * bounds.width.min(bounds.height);
+
+        particles
+            .iter()
+            .zip(&self.previous)
+            .map(|(p, &prev)| {
+                let jump = p.position - prev;
+
+                // Wrapped or respawned particles would otherwise streak across the screen.
+                if jump.length() > max_jump {
+                    return *p;
+                }
+
+                Particle {
+                    position: prev + jump * alpha,
+                    ..*p
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const BOUNDS: Bounds = Bounds {
+        width: 100.0,
+        height: 100.0,
+    };
+
+    /// One particle moving right at 10 units/s, counting its steps.
+    struct Mover {
+        particles: Vec<Particle>,
+        steps: usize,
+        /// Asks to exit once this many steps are done.
+        limit: usize,
+        frame_times: Vec<Duration>,
+    }
+
+    impl Mover {
+        fn new() -> Self {
+            Self {
+                particles: vec![Particle::new(
+                    Vec2::ZERO,
+                    Vec2::new(10.0, 0.0),
+                    1.0,
+                    1.0,
+                    [1.0; 3],
+                )],
+                steps: 0,
+                limit: usize::MAX,
+                frame_times: Vec::new(),
+            }
+        }
+    }
+
+    impl Simulation for Mover {
+        fn init(&mut self, _ctx: &mut Context, _bounds: Bounds) {}
+
+        fn step(&mut self, ctx: &mut Context, dt: f32, _bounds: Bounds) -> StepResult {
+            self.steps += 1;
+            self.frame_times.push(ctx.frame_time());
+
+            for p in &mut self.particles {
+                p.position += p.velocity * dt;
+            }
+
+            match self.steps >= self.limit {
+                true => StepResult::Exit,
+                false => StepResult::Continue,
+            }
+        }
+
+        fn particles(&self) -> &[Particle] {
+            &self.particles
+        }
+    }
+
+    #[test]
+    fn accumulates_partial_frames() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ts.advance(&mut sim, &mut ctx, 0.05, BOUNDS);
+        assert_eq!(sim.steps, 0);
+
+        ts.advance(&mut sim, &mut ctx, 0.17, BOUNDS);
+        assert_eq!(sim.steps, 2);
+    }
+
+    #[test]
+    fn counts_frames_in_the_context() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.25);
+
+        ts.advance(&mut sim, &mut ctx, 0.6, BOUNDS);
+
+        let frame = ctx.frame();
+
+        assert_eq!(frame.frame_index, 2);
+        assert_eq!(frame.elapsed.as_secs_f32(), 0.5);
+    }
+
+    #[test]
+    fn splits_the_frame_time_across_steps() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ctx.set_frame_time(Duration::from_millis(300));
+        ts.advance(&mut sim, &mut ctx, 0.3, BOUNDS);
+
+        assert_eq!(sim.frame_times, [Duration::from_millis(100); 3]);
+        assert_eq!(ctx.frame().frame_time, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn caps_catch_up_steps() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ts.advance(&mut sim, &mut ctx, 10.0, BOUNDS);
+        assert_eq!(sim.steps, MAX_STEPS_PER_FRAME);
+
+        ts.advance(&mut sim, &mut ctx, 0.0, BOUNDS);
+        assert_eq!(sim.steps, MAX_STEPS_PER_FRAME + 1);
+    }
+
+    #[test]
+    fn paused_advance_is_one_step() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ctx.pause();
+        ts.advance(&mut sim, &mut ctx, 5.0, BOUNDS);
+
+        assert_eq!(sim.steps, 1);
+    }
+
+    #[test]
+    fn stops_stepping_on_exit() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        sim.limit = 3;
+
+        assert_eq!(
+            ts.advance(&mut sim, &mut ctx, 0.55, BOUNDS),
+            StepResult::Exit
+        );
+        assert_eq!(sim.steps, 3);
+    }
+
+    #[test]
+    fn interpolates_between_steps() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ts.advance(&mut sim, &mut ctx, 0.15, BOUNDS);
+
+        let drawn = ts.interpolate(&ctx, sim.particles(), BOUNDS);
+
+        assert!((drawn[0].position.x - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn shows_the_latest_step_while_paused() {
+        let (mut sim, mut ctx) = (Mover::new(), Context::default());
+        let mut ts = FixedTimestep::new(0.1);
+
+        ts.advance(&mut sim, &mut ctx, 0.15, BOUNDS);
+        ctx.pause();
+        ts.advance(&mut sim, &mut ctx, 0.0, BOUNDS);
+
+        let drawn = ts.interpolate(&ctx, sim.particles(), BOUNDS);
+
+        assert_eq!(drawn[0].position, sim.particles()[0].position);
+    }
+}
@@ -0,0 +1,130 @@
+//! Motion under a constant acceleration such as gravity. Paths become parabolas, so contact
+//! times are roots of polynomials up to degree four instead of quadratics.
+
+use glam::Vec2;
+
+use crate::particle::Particle;
+
+/// Halvings of a bracket, enough to reach the precision of an `f64` time.
+const BISECTIONS: usize = 64;
+
+/// Acceleration `p` feels under `gravity`. Frozen particles have infinite mass and stay put.
+pub fn acceleration(p: &Particle, gravity: Vec2) -> Vec2 {
+    match p.mass.is_finite() {
+        true => gravity,
+        false => Vec2::ZERO,
+    }
+}
+
+/// Moves `p` along its parabola for `dt`.
+pub fn advance(p: &mut Particle, accel: Vec2, dt: f32) {
+    p.position += p.velocity * dt + 0.5 * accel * dt * dt;
+    p.velocity += accel * dt;
+}
+
+/// Earliest time in `[0, t_max]` at which the polynomial with ascending `coefficients` rises from
+/// negative to zero, e.g. a penetration depth reaching contact. A polynomial already at zero only
+/// counts at time zero if it is rising there, so a resolved contact is not found again.
+pub fn first_contact(coefficients: &[f64], t_max: f32) -> Option<f32> {
+    let t_max = t_max as f64;
+
+    if eval(coefficients, 0.0) == 0.0 && coefficients.get(1).is_some_and(|&slope| slope > 0.0) {
+        return Some(0.0);
+    }
+
+    let mut breaks = vec![0.0];
+
+    breaks.extend(roots(&derivative(coefficients), 0.0, t_max));
+    breaks.push(t_max);
+
+    breaks.windows(2).find_map(|w| {
+        let (a, b) = (w[0], w[1]);
+
+        (eval(coefficients, a) < 0.0 && eval(coefficients, b) >= 0.0)
+            .then(|| bisect(coefficients, a, b) as f32)
+    })
+}
+
+fn eval(coefficients: &[f64], t: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, &c| acc * t + c)
+}
+
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, &c)| k as f64 * c)
+        .collect()
+}
+
+/// Roots in `(lo, hi)` where the polynomial changes sign, ascending. The turning points split
+/// the range into monotonic pieces with at most one root each.
+fn roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coefficients.iter().rposition(|&c| c != 0.0).unwrap_or(0);
+
+    if degree == 0 {
+        return Vec::new();
+    }
+
+    let mut breaks = vec![lo];
+
+    breaks.extend(roots(&derivative(&coefficients[..=degree]), lo, hi));
+    breaks.push(hi);
+
+    breaks
+        .windows(2)
+        .filter(|w| eval(coefficients, w[0]) * eval(coefficients, w[1]) < 0.0)
+        .map(|w| bisect(coefficients, w[0], w[1]))
+        .collect()
+}
+
+/// Narrows a sign change in `[a, b]` down and returns its upper end, on the far side of the
+/// root.
+fn bisect(coefficients: &[f64], mut a: f64, mut b: f64) -> f64 {
+    let below = eval(coefficients, a) < 0.0;
+
+    for _ in 0..BISECTIONS {
+        let m = 0.5 * (a + b);
+
+        match (eval(coefficients, m) < 0.0) == below {
+            true => a = m,
+            false => b = m,
+        }
+    }
+
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_rising_root() {
+        // (t - 1)(t - 2)(t - 3)(t - 4), negative on (1, 2) and rising back to zero at 2.
+        let quartic = [24.0, -50.0, 35.0, -10.0, 1.0];
+        let t = first_contact(&quartic, 5.0).unwrap();
+
+        assert!((t - 2.0).abs() < 1e-5, "{t}");
+        assert_eq!(first_contact(&quartic, 1.5), None);
+    }
+
+    #[test]
+    fn resting_contacts_are_not_found_again() {
+        // At zero with no slope and rising: a particle resting on the floor under gravity.
+        assert_eq!(first_contact(&[0.0, 0.0, 1.0], 1.0), None);
+        assert_eq!(first_contact(&[0.0, 2.0, 1.0], 1.0), Some(0.0));
+    }
+
+    #[test]
+    fn falling_particles_follow_a_parabola() {
+        let mut p = Particle::new(Vec2::ZERO, Vec2::new(1.0, 0.0), 1.0, 1.0, [1.0; 3]);
+        let accel = acceleration(&p, Vec2::new(0.0, -10.0));
+
+        advance(&mut p, accel, 2.0);
+
+        assert_eq!(p.position, Vec2::new(2.0, -20.0));
+        assert_eq!(p.velocity, Vec2::new(1.0, -20.0));
+    }
+}
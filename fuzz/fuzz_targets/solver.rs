@@ -0,0 +1,120 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use engine::{
+    Bounds,
+    boundary::{BoundaryCondition, ReflectiveBox},
+    particle::Particle,
+};
+use glam::Vec2;
+use libfuzzer_sys::fuzz_target;
+use simulator::{
+    miscs::{BoundaryType, DetectionType},
+    solver::Solver,
+};
+
+const BOUNDS: Bounds = Bounds {
+    width: 800.0,
+    height: 600.0,
+};
+const SPEED: f32 = 500.0;
+const MAX_PARTICLES: usize = 64;
+const OVERLAP_TOL: f32 = 1e-2;
+const BOUNDS_TOL: f32 = 1e-3;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    detector: u8,
+    dt: u16,
+    frames: u8,
+    particles: Vec<[u16; 5]>,
+}
+
+fn unit(v: u16) -> f32 {
+    v as f32 / u16::MAX as f32
+}
+
+fn spawn(raw: &[[u16; 5]]) -> Vec<Particle> {
+    let (hw, hh) = BOUNDS.half_extents();
+
+    raw.iter()
+        .take(MAX_PARTICLES)
+        .map(|&[x, y, vx, vy, r]| {
+            let radius = 3.0 + 4.0 * unit(r);
+            let position = Vec2::new(
+                (2.0 * unit(x) - 1.0) * (hw - radius),
+                (2.0 * unit(y) - 1.0) * (hh - radius),
+            );
+            let velocity = Vec2::new(
+                (2.0 * unit(vx) - 1.0) * SPEED,
+                (2.0 * unit(vy) - 1.0) * SPEED,
+            );
+
+            Particle::new(
+                position,
+                velocity,
+                radius,
+                std::f32::consts::PI * radius * radius,
+                [1.0; 3],
+            )
+        })
+        .collect()
+}
+
+fn overlapping(particles: &[Particle], tol: f32) -> Option<(usize, usize)> {
+    for (i, a) in particles.iter().enumerate() {
+        for (j, b) in particles.iter().enumerate().skip(i + 1) {
+            if a.position.distance(b.position) < a.radius + b.radius - tol {
+                return Some((i, j));
+            }
+        }
+    }
+
+    None
+}
+
+fuzz_target!(|input: Input| {
+    let mut particles = spawn(&input.particles);
+
+    // Invariants only make sense for a valid starting state.
+    if overlapping(&particles, 0.0).is_some() {
+        return;
+    }
+
+    let detector = match input.detector % 3 {
+        0 => DetectionType::CellList,
+        1 => DetectionType::Tccd,
+        _ => DetectionType::SweptAabb,
+    };
+    let dt = 1e-4 + unit(input.dt) / 10.0;
+    let mut solver = Solver::new(
+        20.0,
+        None,
+        detector,
+        BoundaryType::Box,
+        0,
+        particles.len() as u64,
+    );
+
+    for _ in 0..=input.frames % 8 {
+        solver.solve(&mut particles, &BOUNDS, dt);
+
+        for (i, p) in particles.iter().enumerate() {
+            assert!(
+                p.position.is_finite() && p.velocity.is_finite(),
+                "particle {i} is not finite: {p:?}"
+            );
+
+            let mut inside = *p;
+            inside.radius -= BOUNDS_TOL;
+            assert!(
+                ReflectiveBox.contains(&inside, &BOUNDS),
+                "particle {i} escaped: {p:?}"
+            );
+        }
+
+        if let Some((i, j)) = overlapping(&particles, OVERLAP_TOL) {
+            panic!("particles {i} and {j} overlap after solve");
+        }
+    }
+});
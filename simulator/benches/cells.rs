@@ -0,0 +1,123 @@
+//! Compares the grid's open-addressing cell table with the `HashMap<IVec2, Vec<usize>>` it
+//! replaced: rebuilding every frame and the 3x3 cell queries of broad-phase detection, on a
+//! crowded box and on a sparse, spread-out domain.
+
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use engine::particle::Particle;
+use glam::{IVec2, Vec2};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use simulator::spatial::SpatialGrid;
+
+const CELL_SIZE: f32 = 20.0;
+
+/// The previous implementation, kept here as the baseline.
+struct HashGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl HashGrid {
+    fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn rebuild(&mut self, particles: &[Particle]) {
+        self.cells.clear();
+
+        for (i, p) in particles.iter().enumerate() {
+            self.cells
+                .entry(self.cell_coord(p.position))
+                .or_default()
+                .push(i);
+        }
+    }
+
+    fn cell_list<'a>(&'a self, p: &Particle) -> impl Iterator<Item = usize> + 'a {
+        let base = self.cell_coord(p.position);
+
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| IVec2::new(dx, dy)))
+            .flat_map(move |d| self.cells.get(&(base + d)).into_iter().flatten().copied())
+    }
+
+    fn cell_coord(&self, pos: Vec2) -> IVec2 {
+        (pos / self.cell_size).floor().as_ivec2()
+    }
+}
+
+/// `n` particles spread over a square `density` particles per cell would fill.
+fn particles(n: usize, density: f32) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(1);
+    let half = (n as f32 / density).sqrt() * CELL_SIZE / 2.0;
+
+    (0..n)
+        .map(|_| {
+            let position = Vec2::new(rng.random_range(-half..half), rng.random_range(-half..half));
+            Particle::new(position, Vec2::ZERO, 5.0, 1.0, [1.0; 3])
+        })
+        .collect()
+}
+
+const LAYOUTS: [(&str, f32); 2] = [("crowded", 2.0), ("sparse", 0.01)];
+
+fn rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cells_rebuild");
+
+    for (layout, density) in LAYOUTS {
+        for n in [1_000, 10_000, 100_000] {
+            let particles = particles(n, density);
+            let id = format!("{layout}/{n}");
+
+            group.bench_with_input(BenchmarkId::new("table", &id), &particles, |b, ps| {
+                let mut grid = SpatialGrid::new(CELL_SIZE);
+                b.iter(|| grid.rebuild(black_box(ps)));
+            });
+            group.bench_with_input(BenchmarkId::new("hashmap", &id), &particles, |b, ps| {
+                let mut grid = HashGrid::new(CELL_SIZE);
+                b.iter(|| grid.rebuild(black_box(ps)));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cells_query");
+
+    for (layout, density) in LAYOUTS {
+        let particles = particles(10_000, density);
+        let mut table = SpatialGrid::new(CELL_SIZE);
+        let mut hash = HashGrid::new(CELL_SIZE);
+
+        table.rebuild(&particles);
+        hash.rebuild(&particles);
+
+        group.bench_function(BenchmarkId::new("table", layout), |b| {
+            b.iter(|| {
+                particles
+                    .iter()
+                    .map(|p| table.cell_list(p).sum::<usize>())
+                    .sum::<usize>()
+            });
+        });
+        group.bench_function(BenchmarkId::new("hashmap", layout), |b| {
+            b.iter(|| {
+                particles
+                    .iter()
+                    .map(|p| hash.cell_list(p).sum::<usize>())
+                    .sum::<usize>()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, rebuild, query);
+criterion_main!(benches);
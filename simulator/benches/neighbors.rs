@@ -0,0 +1,126 @@
+//! Compares the k-d tree with the uniform grid on the query patterns of the analysis tools:
+//! building the structure, radius queries around every particle (pair correlation) and nearest
+//! neighbors (cluster detection).
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use engine::particle::Particle;
+use glam::Vec2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use simulator::{kdtree::KdTree, spatial::SpatialGrid};
+
+const CELL_SIZE: f32 = 20.0;
+const HALF_EXTENT: f32 = 1000.0;
+
+fn particles(n: usize) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(1);
+
+    (0..n)
+        .map(|_| {
+            let position = Vec2::new(
+                rng.random_range(-HALF_EXTENT..HALF_EXTENT),
+                rng.random_range(-HALF_EXTENT..HALF_EXTENT),
+            );
+            Particle::new(position, Vec2::ZERO, 5.0, 1.0, [1.0; 3])
+        })
+        .collect()
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+
+    for n in [1_000, 10_000, 100_000] {
+        let particles = particles(n);
+
+        group.bench_with_input(BenchmarkId::new("grid", n), &particles, |b, ps| {
+            let mut grid = SpatialGrid::new(CELL_SIZE);
+            b.iter(|| grid.rebuild(black_box(ps)));
+        });
+        group.bench_with_input(BenchmarkId::new("kdtree", n), &particles, |b, ps| {
+            b.iter(|| KdTree::new(black_box(ps)));
+        });
+    }
+
+    group.finish();
+}
+
+fn radius(c: &mut Criterion) {
+    let particles = particles(10_000);
+    let mut grid = SpatialGrid::new(CELL_SIZE);
+    let tree = KdTree::new(&particles);
+    let mut group = c.benchmark_group("radius");
+
+    grid.rebuild(&particles);
+
+    for r in [10.0, 50.0, 200.0] {
+        group.bench_with_input(BenchmarkId::new("grid", r), &r, |b, &r| {
+            b.iter(|| {
+                particles
+                    .iter()
+                    .map(|p| grid.neighbors_within(&particles, p.position, r).count())
+                    .sum::<usize>()
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("kdtree", r), &r, |b, &r| {
+            b.iter(|| {
+                particles
+                    .iter()
+                    .map(|p| tree.within(p.position, r).len())
+                    .sum::<usize>()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn nearest(c: &mut Criterion) {
+    let particles = particles(10_000);
+    let mut grid = SpatialGrid::new(CELL_SIZE);
+    let tree = KdTree::new(&particles);
+    let mut group = c.benchmark_group("nearest");
+
+    grid.rebuild(&particles);
+
+    // The grid has no nearest query, so grow the search radius until something other than the
+    // particle itself turns up.
+    group.bench_function("grid", |b| {
+        b.iter(|| {
+            particles
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let mut r = CELL_SIZE;
+
+                    loop {
+                        let best = grid
+                            .neighbors_within(&particles, p.position, r)
+                            .filter(|&j| j != i)
+                            .map(|j| particles[j].position.distance(p.position))
+                            .min_by(f32::total_cmp);
+
+                        if let Some(d) = best {
+                            break d;
+                        }
+
+                        r *= 2.0;
+                    }
+                })
+                .sum::<f32>()
+        });
+    });
+    group.bench_function("kdtree", |b| {
+        b.iter(|| {
+            particles
+                .iter()
+                .map(|p| tree.k_nearest(p.position, 2)[1].1)
+                .sum::<f32>()
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, build, radius, nearest);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+//! Allocation counting for the hot paths. With the `alloc-audit` feature the simulator installs
+//! [`CountingAllocator`], which attributes every allocation to the [`Section`] marked with
+//! [`scope`] on the allocating thread. Rayon workers start outside any section, so parallel
+//! closures re-enter the caller's [`current`] section themselves. Without the feature, scopes do
+//! nothing and every count stays zero.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const ENABLED: bool = cfg!(feature = "alloc-audit");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// Anything outside the other sections, i.e. the engine's rendering, UI and event loop.
+    Render,
+    /// The step itself, outside the grid, detector and recorder.
+    Solver,
+    Grid,
+    Detector,
+    Recorder,
+}
+
+impl Section {
+    pub const ALL: [Section; 5] = [
+        Section::Render,
+        Section::Solver,
+        Section::Grid,
+        Section::Detector,
+        Section::Recorder,
+    ];
+}
+
+thread_local! {
+    static CURRENT: Cell<usize> = const { Cell::new(Section::Render as usize) };
+}
+static COUNTS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static BYTES: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+
+/// Allocations and allocated bytes per section, indexed by `Section as usize`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocCounts {
+    pub counts: [u64; 5],
+    pub bytes: [u64; 5],
+}
+
+impl AllocCounts {
+    pub fn count(&self, section: Section) -> u64 {
+        self.counts[section as usize]
+    }
+
+    pub fn bytes(&self, section: Section) -> u64 {
+        self.bytes[section as usize]
+    }
+}
+
+/// Restores the previous section when dropped.
+#[must_use]
+pub struct Scope(usize);
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if ENABLED {
+            CURRENT.set(self.0);
+        }
+    }
+}
+
+/// Attributes allocations to `section` until the returned guard is dropped.
+pub fn scope(section: Section) -> Scope {
+    match ENABLED {
+        true => Scope(CURRENT.replace(section as usize)),
+        false => Scope(0),
+    }
+}
+
+/// The section this thread is in, to hand to [`scope`] on the workers of a parallel section.
+pub fn current() -> Section {
+    Section::ALL[CURRENT.get()]
+}
+
+/// Counts since the last call.
+pub fn take() -> AllocCounts {
+    let mut counts = AllocCounts::default();
+
+    for k in 0..Section::ALL.len() {
+        counts.counts[k] = COUNTS[k].swap(0, Ordering::Relaxed);
+        counts.bytes[k] = BYTES[k].swap(0, Ordering::Relaxed);
+    }
+
+    counts
+}
+
+fn record(size: usize) {
+    // The slot is gone while the thread is being torn down; count that as rendering.
+    let k = CURRENT
+        .try_with(Cell::get)
+        .unwrap_or(Section::Render as usize);
+
+    COUNTS[k].fetch_add(1, Ordering::Relaxed);
+    BYTES[k].fetch_add(size as u64, Ordering::Relaxed);
+}
+
+/// The system allocator, counting allocations and reallocations per section.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_stay_on_their_thread() {
+        let _alloc = scope(Section::Detector);
+        let expected = if ENABLED {
+            Section::Detector
+        } else {
+            Section::Render
+        };
+
+        assert_eq!(current(), expected);
+        assert_eq!(std::thread::spawn(current).join().unwrap(), Section::Render);
+    }
+}
@@ -0,0 +1,206 @@
+//! Combines the CSV outputs of several runs of the same configuration (typically different
+//! seeds) into mean ± std per detector and particle count.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Directories holding the `frames_*` and `events_*` CSVs of one run each
+    #[arg(required = true)]
+    runs: Vec<PathBuf>,
+
+    /// Where to write the aggregated table
+    #[arg(short, long, default_value = "aggregate.csv")]
+    out: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct FrameRow {
+    time_s: f32,
+    kinetic_energy: f64,
+    solve_ms: f32,
+    /// Missing from recordings made before it was added.
+    #[serde(default)]
+    wall_ms: f32,
+}
+
+#[derive(Serialize)]
+struct AggregateRow<'a> {
+    detector: &'a str,
+    particle_count: u64,
+    metric: &'a str,
+    runs: usize,
+    mean: f64,
+    std: f64,
+}
+
+/// Metric name to one sample per run.
+type Samples = BTreeMap<&'static str, Vec<f64>>;
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let mut configs = BTreeMap::<(String, u64), Samples>::new();
+
+    for dir in &args.runs {
+        for (detector, count, frames) in outputs(dir, "frames")? {
+            let samples = configs.entry((detector.clone(), count)).or_default();
+            let events = dir.join(format!("events_{detector}_{count}.csv"));
+
+            run_metrics(&frames, &events, samples)
+                .with_context(|| format!("reading run in {}", dir.display()))?;
+        }
+    }
+
+    let mut writer = csv::Writer::from_path(&args.out)?;
+
+    println!(
+        "{:<12} {:>8} {:<24} {:>5} {:>14} {:>14}",
+        "detector", "count", "metric", "runs", "mean", "std"
+    );
+
+    for ((detector, particle_count), samples) in &configs {
+        for (metric, values) in samples {
+            let (mean, std) = mean_std(values);
+            let row = AggregateRow {
+                detector,
+                particle_count: *particle_count,
+                metric,
+                runs: values.len(),
+                mean,
+                std,
+            };
+
+            println!(
+                "{:<12} {:>8} {:<24} {:>5} {:>14.6e} {:>14.6e}",
+                row.detector, row.particle_count, row.metric, row.runs, row.mean, row.std
+            );
+            writer.serialize(row)?;
+        }
+    }
+
+    writer.flush()?;
+    println!("Wrote {}", args.out.display());
+
+    Ok(())
+}
+
+/// Finds `{prefix}_{detector}_{count}.csv` files in `dir`.
+fn outputs(dir: &Path, prefix: &str) -> anyhow::Result<Vec<(String, u64, PathBuf)>> {
+    let mut found = Vec::new();
+
+    for entry in fs::read_dir(dir).with_context(|| format!("listing {}", dir.display()))? {
+        let path = entry?.path();
+        let Some(stem) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.strip_prefix(prefix))
+            .and_then(|s| s.strip_prefix('_'))
+        else {
+            continue;
+        };
+
+        if let Some((detector, count)) = stem.rsplit_once('_')
+            && let Ok(count) = count.parse()
+        {
+            found.push((detector.to_string(), count, path));
+        }
+    }
+
+    Ok(found)
+}
+
+fn run_metrics(frames: &Path, events: &Path, samples: &mut Samples) -> anyhow::Result<()> {
+    let rows = csv::Reader::from_path(frames)?
+        .deserialize::<FrameRow>()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+        return Ok(());
+    };
+
+    let frame_count = rows.len() as f64;
+    let solve_ms = rows.iter().skip(1).map(|r| r.solve_ms as f64);
+
+    let mut push = |metric, value| samples.entry(metric).or_default().push(value);
+
+    push(
+        "mean_solve_ms",
+        solve_ms.clone().sum::<f64>() / (frame_count - 1.0).max(1.0),
+    );
+    push("max_solve_ms", solve_ms.fold(0.0, f64::max));
+
+    // Only over steps with a known wall time, which excludes the first of a soak test.
+    let (sim_s, wall_s) =
+        rows.windows(2)
+            .filter(|w| w[1].wall_ms > 0.0)
+            .fold((0.0, 0.0), |(sim, wall), w| {
+                (
+                    sim + (w[1].time_s - w[0].time_s) as f64,
+                    wall + w[1].wall_ms as f64 / 1000.0,
+                )
+            });
+
+    if wall_s > 0.0 {
+        push("real_time_factor", sim_s / wall_s);
+    }
+
+    if first.kinetic_energy > 0.0 {
+        push(
+            "energy_drift",
+            (last.kinetic_energy - first.kinetic_energy) / first.kinetic_energy,
+        );
+    }
+
+    if !events.exists() {
+        return Ok(());
+    }
+
+    let mut reader = csv::Reader::from_path(events)?;
+    let kind = reader
+        .headers()?
+        .iter()
+        .position(|h| h == "type")
+        .context("events CSV has no type column")?;
+    let mut counts = BTreeMap::<String, f64>::new();
+
+    for record in reader.records() {
+        *counts.entry(record?[kind].to_string()).or_default() += 1.0;
+    }
+
+    for (kind, metric) in [
+        ("Pair", "pair_per_frame"),
+        ("Wall", "wall_per_frame"),
+        ("RestingPair", "resting_pair_per_frame"),
+        ("RestingWall", "resting_wall_per_frame"),
+    ] {
+        push(
+            metric,
+            counts.get(kind).copied().unwrap_or(0.0) / frame_count,
+        );
+    }
+
+    Ok(())
+}
+
+/// Mean and sample standard deviation.
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+
+    (mean, var.sqrt())
+}
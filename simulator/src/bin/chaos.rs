@@ -0,0 +1,158 @@
+//! Sensitivity test: runs the same seeded system twice per detection method, jittering every
+//! particle of one copy by a tiny distance every few frames, and writes how far the two copies
+//! drift apart over time.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use engine::particle::Particle;
+use glam::Vec2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Serialize;
+use simulator::{
+    miscs::DetectionType,
+    workload::{self, BOUNDS},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Detection methods to test; all of them when omitted
+    #[arg(value_enum)]
+    methods: Vec<DetectionType>,
+
+    /// Number of particles to simulate
+    #[arg(short, long, default_value_t = 500)]
+    particle_count: usize,
+
+    /// Number of frames to simulate
+    #[arg(short, long, default_value_t = 1200)]
+    frames: u64,
+
+    /// Frame rate for the simulation
+    #[arg(long, default_value_t = 60)]
+    fps: u64,
+
+    /// Initial speed range of the particles
+    #[arg(long, default_value_t = 100.0)]
+    speed: f32,
+
+    /// Distance every particle of the perturbed copy is moved by, in a random direction
+    #[arg(short, long, default_value_t = 1e-4)]
+    epsilon: f32,
+
+    /// Perturb every this many frames (0 = only before the first frame)
+    #[arg(short = 'n', long, default_value_t = 60)]
+    every: u64,
+
+    /// Separation, in world units, from which a particle counts as diverged
+    #[arg(long, default_value_t = 1.0)]
+    threshold: f32,
+
+    /// Random seed for the initial state and the perturbations
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+
+    /// Where to write the divergence-vs-time CSV
+    #[arg(short, long, default_value = "chaos.csv")]
+    output: PathBuf,
+}
+
+#[derive(Serialize)]
+struct DivergenceRow {
+    method: &'static str,
+    frame: u64,
+    time_s: f32,
+    mean_distance: f32,
+    max_distance: f32,
+    diverged_fraction: f32,
+    contacts_reference: usize,
+    contacts_perturbed: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+    let methods = match args.methods.is_empty() {
+        true => DetectionType::value_variants().to_vec(),
+        false => args.methods.clone(),
+    };
+    let mut writer = csv::Writer::from_path(&args.output)?;
+
+    for method in methods {
+        let name = method.tag();
+
+        match run(&args, method, &mut writer)? {
+            Some(frame) => println!("{name:<12} diverged at frame {frame}"),
+            None => println!("{name:<12} did not diverge in {} frames", args.frames),
+        }
+    }
+
+    writer.flush()?;
+    println!("written to {}", args.output.display());
+
+    Ok(())
+}
+
+/// Simulates both copies with `method`, appending a row per frame. Returns the first frame in
+/// which any particle has drifted past the threshold.
+fn run(
+    args: &Args,
+    method: DetectionType,
+    writer: &mut csv::Writer<std::fs::File>,
+) -> anyhow::Result<Option<u64>> {
+    let dt = 1.0 / args.fps as f32;
+    let mut reference = workload::spawn(args.particle_count, args.speed, args.seed);
+    let mut perturbed = reference.clone();
+    let mut reference_solver = workload::new_solver(method, args.particle_count);
+    let mut perturbed_solver = workload::new_solver(method, args.particle_count);
+    let mut jitter_rng = StdRng::seed_from_u64(args.seed.wrapping_add(1));
+    let mut first_diverged = None;
+
+    for frame in 0..args.frames {
+        let due = match args.every {
+            0 => frame == 0,
+            n => frame.is_multiple_of(n),
+        };
+
+        if due {
+            jitter(&mut perturbed, args.epsilon, &mut jitter_rng);
+        }
+
+        reference_solver.solve(&mut reference, &BOUNDS, dt);
+        perturbed_solver.solve(&mut perturbed, &BOUNDS, dt);
+
+        let distances = reference
+            .iter()
+            .zip(&perturbed)
+            .map(|(a, b)| a.position.distance(b.position))
+            .collect::<Vec<_>>();
+        let diverged = distances.iter().filter(|&&d| d > args.threshold).count();
+
+        if diverged > 0 {
+            first_diverged.get_or_insert(frame + 1);
+        }
+
+        writer.serialize(DivergenceRow {
+            method: method.tag(),
+            frame: frame + 1,
+            time_s: (frame + 1) as f32 * dt,
+            mean_distance: distances.iter().sum::<f32>() / distances.len().max(1) as f32,
+            max_distance: distances.iter().copied().fold(0.0, f32::max),
+            diverged_fraction: diverged as f32 / distances.len().max(1) as f32,
+            contacts_reference: reference_solver.contacts.len(),
+            contacts_perturbed: perturbed_solver.contacts.len(),
+        })?;
+    }
+
+    Ok(first_diverged)
+}
+
+fn jitter(particles: &mut [Particle], epsilon: f32, rng: &mut StdRng) {
+    for p in particles {
+        let angle = rng.random_range(0.0..std::f32::consts::TAU);
+
+        p.position += Vec2::from_angle(angle) * epsilon;
+    }
+}
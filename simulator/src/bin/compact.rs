@@ -0,0 +1,45 @@
+//! Converts a recorded `events_*` CSV into a compact index sorted by frame and by particle, so
+//! later lookups (e.g. `repro --events`) do not have to scan the whole CSV again.
+
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use simulator::event_index::EventIndex;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Recorded `events_*` CSV
+    events: PathBuf,
+
+    /// Where to write the index; defaults to the CSV path with an `.idx` extension
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let out = args
+        .out
+        .unwrap_or_else(|| args.events.with_extension("idx"));
+
+    let index = EventIndex::from_csv(&args.events)?;
+    index.save(&out)?;
+
+    let size = |path: &PathBuf| fs::metadata(path).map_or(0, |m| m.len());
+    let frames = index.frame_range().map_or(String::new(), |r| {
+        format!(", frames {}-{}", r.start(), r.end())
+    });
+
+    println!(
+        "Indexed {} events of {} particles{} into {} ({} -> {} bytes)",
+        index.len(),
+        index.particle_count(),
+        frames,
+        out.display(),
+        size(&args.events),
+        size(&out),
+    );
+
+    Ok(())
+}
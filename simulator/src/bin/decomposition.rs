@@ -0,0 +1,141 @@
+//! Runs the experimental stripe-decomposed solver next to the plain one on the same seeded
+//! system, for several stripe counts, and writes per-frame halo sizes, ghost disagreement,
+//! drift from the plain solver and timings.
+
+use std::{path::PathBuf, time::Instant};
+
+use clap::Parser;
+use serde::Serialize;
+use simulator::{
+    decomposed::DecomposedSolver,
+    miscs::{BoundaryType, DetectionType},
+    workload::{self, BOUNDS, CELL_SIZE, POLYGON_SIDES},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Detection method used inside every stripe and by the reference
+    #[arg(value_enum, default_value_t = DetectionType::Tccd)]
+    method: DetectionType,
+
+    /// Stripe counts to test, comma-separated
+    #[arg(long, value_delimiter = ',', default_values_t = [2, 4, 8])]
+    stripes: Vec<usize>,
+
+    /// Number of particles to simulate
+    #[arg(short, long, default_value_t = 500)]
+    particle_count: usize,
+
+    /// Number of frames to simulate
+    #[arg(short, long, default_value_t = 300)]
+    frames: u64,
+
+    /// Frame rate for the simulation
+    #[arg(long, default_value_t = 60)]
+    fps: u64,
+
+    /// Initial speed range of the particles
+    #[arg(long, default_value_t = 100.0)]
+    speed: f32,
+
+    /// Random seed for the initial state
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+
+    /// Where to write the per-frame CSV
+    #[arg(short, long, default_value = "decomposition.csv")]
+    output: PathBuf,
+}
+
+#[derive(Serialize)]
+struct DecompositionRow {
+    stripes: usize,
+    frame: u64,
+    time_s: f32,
+    halo: usize,
+    ghost_error: f32,
+    mean_drift: f32,
+    max_drift: f32,
+    contacts_reference: usize,
+    contacts_decomposed: usize,
+    solve_ms_reference: f32,
+    solve_ms_decomposed: f32,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+    let mut writer = csv::Writer::from_path(&args.output)?;
+
+    for &stripes in &args.stripes {
+        let rows = run(&args, stripes);
+        let n = rows.len().max(1) as f32;
+
+        println!(
+            "{stripes:>3} stripes: mean halo {:.1}, worst ghost error {:.3e}, final max drift {:.3e}, {:.2} vs {:.2} ms",
+            rows.iter().map(|r| r.halo as f32).sum::<f32>() / n,
+            rows.iter().map(|r| r.ghost_error).fold(0.0, f32::max),
+            rows.last().map_or(0.0, |r| r.max_drift),
+            rows.iter().map(|r| r.solve_ms_decomposed).sum::<f32>() / n,
+            rows.iter().map(|r| r.solve_ms_reference).sum::<f32>() / n,
+        );
+
+        for row in rows {
+            writer.serialize(row)?;
+        }
+    }
+
+    writer.flush()?;
+    println!("written to {}", args.output.display());
+
+    Ok(())
+}
+
+fn run(args: &Args, stripes: usize) -> Vec<DecompositionRow> {
+    let dt = 1.0 / args.fps as f32;
+    let mut reference = workload::spawn(args.particle_count, args.speed, args.seed);
+    let mut decomposed = reference.clone();
+    let mut reference_solver = workload::new_solver(args.method, args.particle_count);
+    let mut decomposed_solver = DecomposedSolver::new(
+        stripes,
+        CELL_SIZE,
+        args.method,
+        BoundaryType::Box,
+        POLYGON_SIDES,
+    );
+
+    (0..args.frames)
+        .map(|frame| {
+            let start = Instant::now();
+            reference_solver.solve(&mut reference, &BOUNDS, dt);
+            let solve_ms_reference = start.elapsed().as_secs_f32() * 1000.0;
+
+            let start = Instant::now();
+            decomposed_solver.solve(&mut decomposed, &BOUNDS, dt);
+            let solve_ms_decomposed = start.elapsed().as_secs_f32() * 1000.0;
+
+            let drift = reference
+                .iter()
+                .zip(&decomposed)
+                .map(|(a, b)| a.position.distance(b.position))
+                .collect::<Vec<_>>();
+            let stats = decomposed_solver.stats;
+
+            DecompositionRow {
+                stripes,
+                frame: frame + 1,
+                time_s: (frame + 1) as f32 * dt,
+                halo: stats.halo,
+                ghost_error: stats.ghost_error,
+                mean_drift: drift.iter().sum::<f32>() / drift.len().max(1) as f32,
+                max_drift: drift.iter().copied().fold(0.0, f32::max),
+                contacts_reference: reference_solver.contacts.len(),
+                contacts_decomposed: stats.contacts,
+                solve_ms_reference,
+                solve_ms_decomposed,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+//! Experimental: measures keeping particle state on the GPU. Ballistic motion is integrated by a
+//! compute shader and the state is only read back for frames in which the CPU solver has a
+//! collision to resolve. The same run is repeated fully on the CPU for comparison.
+
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use engine::compute::GpuParticles;
+use simulator::{
+    miscs::DetectionType,
+    workload::{self, BOUNDS},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Number of particles to simulate
+    #[arg(short, long, default_value_t = 500)]
+    particle_count: usize,
+
+    /// Number of frames to simulate
+    #[arg(short, long, default_value_t = 600)]
+    frames: u64,
+
+    /// Frame rate for the simulation
+    #[arg(long, default_value_t = 30)]
+    fps: u64,
+
+    /// Initial speed range of the particles
+    #[arg(long, default_value_t = 100.0)]
+    speed: f32,
+
+    /// How many frames ahead to look for the next collision after a readback
+    #[arg(long, default_value_t = 30)]
+    lookahead: u64,
+
+    /// Random seed for reproducibility
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(Default)]
+struct Timings {
+    total: Duration,
+    readback: Duration,
+    readbacks: u64,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args = Args::parse();
+    let dt = 1.0 / args.fps as f32;
+    let initial = workload::spawn(args.particle_count, args.speed, args.seed);
+
+    let mut cpu = initial.clone();
+    let mut solver = workload::new_solver(DetectionType::Tccd, args.particle_count);
+    let start = Instant::now();
+
+    for _ in 0..args.frames {
+        solver.solve(&mut cpu, &BOUNDS, dt);
+    }
+
+    let cpu_time = start.elapsed();
+
+    let mut hybrid = initial;
+    let mut solver = workload::new_solver(DetectionType::Tccd, args.particle_count);
+    let gpu = pollster::block_on(GpuParticles::new(&hybrid))?;
+    let horizon = args.lookahead as f32 * dt;
+    let mut free_time = 0.0;
+    let mut timings = Timings::default();
+    let start = Instant::now();
+
+    for _ in 0..args.frames {
+        if free_time > dt {
+            gpu.integrate(dt);
+            free_time -= dt;
+            continue;
+        }
+
+        let readback = Instant::now();
+        gpu.read_back(&mut hybrid)?;
+        timings.readback += readback.elapsed();
+        timings.readbacks += 1;
+
+        solver.solve(&mut hybrid, &BOUNDS, dt);
+        gpu.upload(&hybrid);
+        free_time = solver
+            .next_event(&hybrid, &BOUNDS, horizon)
+            .unwrap_or(horizon);
+    }
+
+    gpu.read_back(&mut hybrid)?;
+    timings.total = start.elapsed();
+
+    let deviation = cpu
+        .iter()
+        .zip(&hybrid)
+        .map(|(a, b)| a.position.distance(b.position))
+        .fold(0.0, f32::max);
+    let per_frame = |d: Duration| d.as_secs_f64() * 1000.0 / args.frames as f64;
+
+    println!("frames:              {}", args.frames);
+    println!("cpu ms/frame:        {:.4}", per_frame(cpu_time));
+    println!("hybrid ms/frame:     {:.4}", per_frame(timings.total));
+    println!(
+        "readbacks:           {} ({:.1}% of frames)",
+        timings.readbacks,
+        100.0 * timings.readbacks as f64 / args.frames as f64
+    );
+    println!(
+        "readback ms/call:    {:.4}",
+        timings.readback.as_secs_f64() * 1000.0 / timings.readbacks.max(1) as f64
+    );
+    println!("max position delta:  {deviation:.3e}");
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Cuts a minimal reproduction out of a recorded run: the particles involved in a violation plus
+//! their neighbors, as they were on the frame before it. Run the result with
+//! `simulator --scenario <out>` and step through it with `.`. Given an index built by `compact`,
+//! it also lists the involved particles' collisions leading up to the violation.
+
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::Parser;
+use simulator::{event_index::EventIndex, scenario};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Recorded `particles_*` snapshot CSV
+    particles: PathBuf,
+
+    /// Frame the violation was reported on
+    #[arg(short, long)]
+    frame: u64,
+
+    /// Ids of the particles involved in the violation (e.g. 12,40)
+    #[arg(short, long, required = true, value_delimiter = ',')]
+    ids: Vec<usize>,
+
+    /// Also keep particles whose center is within this distance of an involved one
+    #[arg(short, long, default_value_t = 50.0)]
+    radius: f32,
+
+    /// Event index of the same run, written by `compact`
+    #[arg(short, long)]
+    events: Option<PathBuf>,
+
+    /// How many frames before the violation to list events for
+    #[arg(long, default_value_t = 5, requires = "events")]
+    history: u64,
+
+    /// Where to write the scenario
+    #[arg(short, long, default_value = "repro.csv")]
+    out: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let Some(frame) = args.frame.checked_sub(1) else {
+        bail!("the violation frame must be after the first recorded frame");
+    };
+
+    let rows = scenario::read_rows(&args.particles)?;
+    let picked = scenario::neighborhood(&rows, frame, &args.ids, args.radius)?;
+
+    scenario::save(&args.out, &picked)?;
+
+    println!(
+        "Wrote {} particles from frame {frame} to {}",
+        picked.len(),
+        args.out.display()
+    );
+
+    for (index, row) in picked.iter().enumerate().take(args.ids.len()) {
+        println!("  particle {} -> index {index}", row.particle_id);
+    }
+
+    if let Some(path) = &args.events {
+        let index = EventIndex::load(path)?;
+        let frames = args.frame.saturating_sub(args.history)..=args.frame;
+
+        for &id in &args.ids {
+            println!(
+                "Events of particle {id} in frames {}-{}:",
+                frames.start(),
+                frames.end()
+            );
+
+            for e in index.particle(id, frames.clone()) {
+                let other = e.j.filter(|&j| j != id).or((e.i != id).then_some(e.i));
+
+                println!(
+                    "  frame {} toi {:.6} {}{} v_n {:.3} -> {:.3}",
+                    e.frame,
+                    e.toi,
+                    e.kind.name(),
+                    other.map_or(String::new(), |o| format!(" with {o}")),
+                    e.before,
+                    e.after,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+//! Time-reversal test: runs a seeded system forward N frames per detection method, negates every
+//! velocity, runs it N frames back and measures how far each particle ends up from where it
+//! started. Elastic hard spheres retrace their paths, so the deviation is the error the detector
+//! and the float arithmetic added along the way.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use engine::particle::Particle;
+use serde::Serialize;
+use simulator::{
+    miscs::DetectionType,
+    workload::{self, BOUNDS},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Detection methods to test; all of them when omitted
+    #[arg(value_enum)]
+    methods: Vec<DetectionType>,
+
+    /// Number of particles to simulate
+    #[arg(short, long, default_value_t = 500)]
+    particle_count: usize,
+
+    /// Frame counts to run forward and back, comma-separated
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_values_t = [10, 30, 100, 300, 1000]
+    )]
+    frames: Vec<u64>,
+
+    /// Frame rate for the simulation
+    #[arg(long, default_value_t = 60)]
+    fps: u64,
+
+    /// Initial speed range of the particles
+    #[arg(long, default_value_t = 100.0)]
+    speed: f32,
+
+    /// Random seed for the initial state
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+
+    /// Where to write the deviation-vs-frames CSV
+    #[arg(short, long, default_value = "reversal.csv")]
+    output: PathBuf,
+
+    /// Also write every particle's deviation to this CSV
+    #[arg(long)]
+    particles: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct ReversalRow {
+    method: &'static str,
+    frames: u64,
+    time_s: f32,
+    collisions: usize,
+    mean_position_error: f32,
+    rms_position_error: f32,
+    max_position_error: f32,
+    mean_velocity_error: f32,
+    max_velocity_error: f32,
+}
+
+#[derive(Serialize)]
+struct ParticleDeviationRow {
+    method: &'static str,
+    frames: u64,
+    particle: usize,
+    position_error: f32,
+    velocity_error: f32,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let mut args = Args::parse();
+    let methods = match args.methods.is_empty() {
+        true => DetectionType::value_variants().to_vec(),
+        false => args.methods.clone(),
+    };
+
+    args.frames.sort_unstable();
+    args.frames.dedup();
+
+    let mut writer = csv::Writer::from_path(&args.output)?;
+    let mut particle_writer = args
+        .particles
+        .as_ref()
+        .map(csv::Writer::from_path)
+        .transpose()?;
+
+    for method in methods {
+        for row in run(&args, method, particle_writer.as_mut())? {
+            println!(
+                "{:<12} {:>6} frames: max {:.3e}, rms {:.3e}",
+                row.method, row.frames, row.max_position_error, row.rms_position_error
+            );
+            writer.serialize(row)?;
+        }
+    }
+
+    writer.flush()?;
+
+    if let Some(writer) = &mut particle_writer {
+        writer.flush()?;
+    }
+
+    println!("written to {}", args.output.display());
+
+    Ok(())
+}
+
+/// Runs forward once up to the largest frame count, reversing a copy of the state at each
+/// requested count.
+fn run(
+    args: &Args,
+    method: DetectionType,
+    mut particle_writer: Option<&mut csv::Writer<std::fs::File>>,
+) -> anyhow::Result<Vec<ReversalRow>> {
+    let dt = 1.0 / args.fps as f32;
+    // Overlapping particles never collide, which would break reversibility from the first frame.
+    let initial = workload::spawn_apart(args.particle_count, args.speed, args.seed);
+    let mut particles = initial.clone();
+    let mut solver = workload::new_solver(method, args.particle_count);
+    let mut collisions = 0;
+    let mut frame = 0;
+    let mut rows = Vec::new();
+
+    for &frames in &args.frames {
+        while frame < frames {
+            solver.solve(&mut particles, &BOUNDS, dt);
+            collisions += solver.contacts.len();
+            frame += 1;
+        }
+
+        let mut reversed = particles.clone();
+        let mut backward = workload::new_solver(method, args.particle_count);
+
+        negate_velocities(&mut reversed);
+
+        for _ in 0..frames {
+            backward.solve(&mut reversed, &BOUNDS, dt);
+        }
+
+        negate_velocities(&mut reversed);
+
+        let errors = initial
+            .iter()
+            .zip(&reversed)
+            .map(|(a, b)| {
+                (
+                    a.position.distance(b.position),
+                    a.velocity.distance(b.velocity),
+                )
+            })
+            .collect::<Vec<_>>();
+        let n = errors.len().max(1) as f32;
+
+        if let Some(writer) = &mut particle_writer {
+            for (particle, &(position_error, velocity_error)) in errors.iter().enumerate() {
+                writer.serialize(ParticleDeviationRow {
+                    method: method.tag(),
+                    frames,
+                    particle,
+                    position_error,
+                    velocity_error,
+                })?;
+            }
+        }
+
+        rows.push(ReversalRow {
+            method: method.tag(),
+            frames,
+            time_s: frames as f32 * dt,
+            collisions,
+            mean_position_error: errors.iter().map(|e| e.0).sum::<f32>() / n,
+            rms_position_error: (errors.iter().map(|e| e.0 * e.0).sum::<f32>() / n).sqrt(),
+            max_position_error: errors.iter().map(|e| e.0).fold(0.0, f32::max),
+            mean_velocity_error: errors.iter().map(|e| e.1).sum::<f32>() / n,
+            max_velocity_error: errors.iter().map(|e| e.1).fold(0.0, f32::max),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn negate_velocities(particles: &mut [Particle]) {
+    for p in particles {
+        p.velocity = -p.velocity;
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::event::Event;
+
+/// Groups the collisions resolved within a frame into chains of dependent impacts. Two
+/// collisions belong to the same chain whenever they share a particle, which is how a shock
+/// propagates through e.g. a Newton's cradle.
+#[derive(Default)]
+pub struct ChainTracker {
+    chain_of: HashMap<usize, usize>,
+    next_chain: usize,
+    order: usize,
+    elapsed: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainStep {
+    pub chain: usize,
+    pub order: usize,
+    pub elapsed: f32,
+}
+
+impl ChainTracker {
+    pub fn begin_frame(&mut self) {
+        self.chain_of.clear();
+        self.next_chain = 0;
+        self.order = 0;
+        self.elapsed = 0.0;
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    pub fn link(&mut self, event: &Event) -> ChainStep {
+        let (i, j) = event.particles();
+        let ci = self.chain_of.get(&i).copied();
+        let cj = j.and_then(|j| self.chain_of.get(&j).copied());
+
+        let chain = match (ci, cj) {
+            (Some(a), Some(b)) if a != b => {
+                let (keep, merged) = (a.min(b), a.max(b));
+
+                self.chain_of
+                    .values_mut()
+                    .filter(|c| **c == merged)
+                    .for_each(|c| *c = keep);
+
+                keep
+            }
+            (Some(c), _) | (None, Some(c)) => c,
+            (None, None) => {
+                self.next_chain += 1;
+                self.next_chain - 1
+            }
+        };
+
+        self.chain_of.insert(i, chain);
+
+        if let Some(j) = j {
+            self.chain_of.insert(j, chain);
+        }
+
+        self.order += 1;
+
+        ChainStep {
+            chain,
+            order: self.order - 1,
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PairEvent, WallEvent};
+    use engine::boundary::Wall;
+
+    #[test]
+    fn shared_particles_join_the_same_chain() {
+        let mut tracker = ChainTracker::default();
+
+        tracker.begin_frame();
+
+        let a = tracker.link(&PairEvent::new(0.0, 0, 1).into());
+        let b = tracker.link(&PairEvent::new(0.0, 5, 6).into());
+        let c = tracker.link(&PairEvent::new(0.0, 1, 2).into());
+        let d = tracker.link(&WallEvent::new(0.0, 2, Wall::Left).into());
+        let e = tracker.link(&PairEvent::new(0.0, 2, 6).into());
+
+        assert_eq!(
+            (a.chain, b.chain, c.chain, d.chain, e.chain),
+            (0, 1, 0, 0, 0)
+        );
+        assert_eq!(e.order, 4);
+    }
+}
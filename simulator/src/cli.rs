@@ -1,6 +1,14 @@
+use std::{path::PathBuf, time::Duration};
+
 use clap::Parser;
 
-use crate::miscs::{DetectionType, RecorderType};
+use simulator::{
+    format::FloatFormat,
+    miscs::{
+        BackendType, BoundaryType, DetectionType, PresentModeType, RecorderType, ThemeType,
+        ThermostatType,
+    },
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about)]
@@ -9,10 +17,35 @@ pub struct Cli {
     #[arg(default_value_t = DetectionType::Tccd,  value_enum)]
     pub method: DetectionType,
 
+    /// Run a second detection method side by side on the same seed for comparison
+    #[arg(long, value_enum, conflicts_with = "soak")]
+    pub compare: Option<DetectionType>,
+
+    /// Instead of side by side, run the --compare detector on a copy of the state every frame
+    /// and record both, so their outputs are aligned frame by frame
+    #[arg(long, requires = "compare")]
+    pub lockstep: bool,
+
+    /// Run this many simulations in a grid, seeded consecutively from the seed
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..), conflicts_with_all = ["soak", "compare"])]
+    pub grid: Option<u64>,
+
+    /// Shape and behavior of the container walls
+    #[arg(short, long, default_value_t = BoundaryType::Box, value_enum)]
+    pub boundary: BoundaryType,
+
+    /// Number of sides for the polygon boundary
+    #[arg(long, default_value_t = 6)]
+    pub polygon_sides: usize,
+
     /// Number of particles to simulate
     #[arg(short, long, default_value_t = 500)]
     pub particle_count: u64,
 
+    /// Start from the particles in this CSV (e.g. one written by `repro`) instead of spawning
+    #[arg(long)]
+    pub scenario: Option<PathBuf>,
+
     /// Random seed for reproducibility
     #[arg(short, long)]
     pub seed: Option<u64>,
@@ -21,15 +54,246 @@ pub struct Cli {
     #[arg(short, long, value_enum)]
     pub record: Option<RecorderType>,
 
+    /// Directory recordings, state dumps and soak reports are written into
+    #[arg(long, default_value = ".")]
+    pub output_dir: PathBuf,
+
+    /// How recorded floats are written: printf-style "%.9g", "%.6e" or "%.4f" to trade
+    /// precision for file size, or "shortest" round-trip digits
+    #[arg(long, default_value_t = FloatFormat::default())]
+    pub float_format: FloatFormat,
+
+    /// Record the resolution order and velocities of dependent collision chains to CSV
+    #[arg(long, default_value_t = false)]
+    pub trace_chains: bool,
+
+    /// Reuse each particle's broad-phase candidates while its grid cells keep their occupants
+    #[arg(long, default_value_t = false)]
+    pub pair_cache: bool,
+
     /// Cell size for spatial partitioning
     #[arg(short, long, default_value_t = 20.0)]
     pub cell_size: f32,
 
+    /// Normal speed below which contacts are resolved as resting instead of bouncing
+    #[arg(long, default_value_t = 0.0)]
+    pub resting_threshold: f32,
+
+    /// Coefficient of restitution between particles, 1 for elastic collisions
+    #[arg(long, default_value_t = 1.0, value_parser = parse_restitution)]
+    pub restitution: f32,
+
+    /// Coefficient of friction for particle and wall contacts, 0 for frictionless ones
+    #[arg(long, default_value_t = 0.0, value_parser = parse_friction)]
+    pub friction: f32,
+
+    /// Downward acceleration of every particle, in world units per second squared
+    #[arg(long, default_value_t = 0.0)]
+    pub gravity: f32,
+
     /// Frame rate for the simulation
     #[arg(short, long, default_value_t = 30)]
     pub fps: u64,
 
+    /// Quit after simulating this many frames, not counting warm-up
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub frames: Option<u64>,
+
+    /// Step physics at this constant rate instead of the render frame time
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub physics_hz: Option<u32>,
+
+    /// Longest time in seconds one frame may advance the simulation; the rest of a stall is dropped
+    #[arg(long, default_value_t = 0.1)]
+    pub max_dt: f32,
+
+    /// Replay the interventions from an inputs CSV of an earlier recording; use the same seed
+    /// and --physics-hz for an exact replay
+    #[arg(long)]
+    pub replay_inputs: Option<PathBuf>,
+
+    /// Number of frames to simulate before recording begins
+    #[arg(long, default_value_t = 0)]
+    pub warmup_frames: u64,
+
+    /// Target temperature to rescale velocities to after each warm-up frame
+    #[arg(long, requires = "warmup_frames", value_parser = parse_temperature)]
+    pub warmup_temperature: Option<f32>,
+
+    /// Thermostat applied after every frame to counteract energy drift
+    #[arg(long, value_enum)]
+    pub thermostat: Option<ThermostatType>,
+
+    /// Thermostat target temperature (defaults to the temperature after the first frame)
+    #[arg(long, requires = "thermostat")]
+    pub thermostat_temperature: Option<f32>,
+
+    /// Berendsen coupling time constant in seconds
+    #[arg(long, default_value_t = 1.0, value_parser = parse_tau)]
+    pub thermostat_tau: f32,
+
+    /// MSAA sample count used to smooth circle edges
+    #[arg(long, default_value_t = 1)]
+    pub msaa: u32,
+
+    /// Render at this multiple of the window resolution and downsample (supersampling)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=4))]
+    pub render_scale: u32,
+
+    /// Graphics backend used for rendering
+    #[arg(long, default_value_t = BackendType::Auto, value_enum)]
+    pub backend: BackendType,
+
+    /// Frame presentation mode; fifo is vsynced, immediate is uncapped by the display
+    #[arg(long, default_value_t = PresentModeType::Fifo, value_enum)]
+    pub present_mode: PresentModeType,
+
+    /// Color theme for the background and debug UI
+    #[arg(long, default_value_t = ThemeType::Dark, value_enum)]
+    pub theme: ThemeType,
+
+    /// Background color as a hex triplet (e.g. ffffff), overriding the theme
+    #[arg(long, value_parser = parse_hex_color)]
+    pub background: Option<[f32; 3]>,
+
+    /// Render to an sRGB surface with gamma-correct color conversion
+    #[arg(long, default_value_t = false)]
+    pub srgb: bool,
+
+    /// Start with the collision heatmap overlay shown (toggle with M)
+    #[arg(long, default_value_t = false)]
+    pub heatmap: bool,
+
+    /// Keep the walls where they start when the window is resized; the view is scaled to fit
+    #[arg(long, default_value_t = false)]
+    pub lock_bounds: bool,
+
+    /// Step on a worker thread so the window stays responsive during slow frames
+    #[arg(long, default_value_t = false)]
+    pub threaded: bool,
+
+    /// Write every rendered frame as a numbered PNG into this directory
+    #[arg(long)]
+    pub capture_dir: Option<PathBuf>,
+
+    /// Keep the last this many rendered frames and save them as an animated GIF on X
+    #[arg(long, default_value_t = 0)]
+    pub clip_frames: usize,
+
+    /// CSV of time_s,offset_x,offset_y,scale camera keyframes to play during the run
+    #[arg(long)]
+    pub camera_path: Option<PathBuf>,
+
+    /// Run headlessly for a wall-clock duration (e.g. 8h, 1h30m) and write a health report
+    #[arg(long, value_parser = crate::soak::parse_duration)]
+    pub soak: Option<Duration>,
+
+    /// Seconds between invariant checks during a soak test
+    #[arg(long, default_value_t = 60, requires = "soak")]
+    pub soak_check_interval: u64,
+
+    /// Start a new set of recording files every this many frames during a soak test (0 = never)
+    #[arg(long, default_value_t = 1_000_000, requires = "soak")]
+    pub soak_shard_frames: u64,
+
+    /// Save every this many frames of a soak test as a PNG into the capture directory
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..), requires_all = ["soak", "capture_dir"])]
+    pub soak_capture_every: Option<u64>,
+
+    /// Window size in logical pixels as WIDTHxHEIGHT; also the bounds of a soak test
+    #[arg(long, default_value = "800x600", value_parser = parse_window_size)]
+    pub window_size: (u32, u32),
+
+    /// Measure the world in logical pixels so particles look the same size on HiDPI monitors
+    #[arg(long, default_value_t = false)]
+    pub dpi_scaling: bool,
+
+    /// Keep simulating and recording while the window is unfocused
+    #[arg(long, default_value_t = false)]
+    pub run_unfocused: bool,
+
     /// Open in fullscreen mode
     #[arg(long, default_value_t = false)]
     pub fullscreen: bool,
+
+    /// Write logs to this file instead of stderr, rotating it once it reaches --log-max-size
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Size in MiB at which the log file is rotated
+    #[arg(long, default_value_t = 10, requires = "log_file")]
+    pub log_max_size: u64,
+
+    /// Number of rotated log files to keep next to the current one
+    #[arg(long, default_value_t = 5, requires = "log_file")]
+    pub log_keep: usize,
+
+    /// Log levels in RUST_LOG syntax, overriding it, e.g. "info,engine=warn,simulator::solver=debug"
+    #[arg(long)]
+    pub log_filter: Option<String>,
+
+    /// Monitor to go fullscreen on, by index (0 = first)
+    #[arg(long, default_value_t = 0, requires = "fullscreen")]
+    pub monitor: usize,
+}
+
+fn parse_hex_color(s: &str) -> Result<[f32; 3], String> {
+    let hex = s.trim_start_matches('#');
+
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("expected six hex digits, got '{s}'"));
+    }
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|v| v as f32 / 255.0)
+            .map_err(|e| format!("invalid color '{s}': {e}"))
+    };
+
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn parse_window_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{s}'"))?;
+    let dim = |v: &str| match v.trim().parse::<u32>() {
+        Ok(0) => Err(format!("window size must be non-zero, got '{s}'")),
+        Ok(v) => Ok(v),
+        Err(e) => Err(format!("invalid window size '{s}': {e}")),
+    };
+
+    Ok((dim(w)?, dim(h)?))
+}
+
+fn parse_restitution(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(e) if (0.0..=1.0).contains(&e) => Ok(e),
+        Ok(_) => Err(format!("restitution must be in 0..=1, got '{s}'")),
+        Err(e) => Err(format!("invalid restitution '{s}': {e}")),
+    }
+}
+
+fn parse_friction(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(mu) if mu >= 0.0 && mu.is_finite() => Ok(mu),
+        Ok(_) => Err(format!("friction must be non-negative, got '{s}'")),
+        Err(e) => Err(format!("invalid friction '{s}': {e}")),
+    }
+}
+
+fn parse_temperature(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(t) if t >= 0.0 && t.is_finite() => Ok(t),
+        Ok(_) => Err(format!("temperature must be non-negative, got '{s}'")),
+        Err(e) => Err(format!("invalid temperature '{s}': {e}")),
+    }
+}
+
+fn parse_tau(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(tau) if tau > 0.0 && tau.is_finite() => Ok(tau),
+        Ok(_) => Err(format!("thermostat tau must be positive, got '{s}'")),
+        Err(e) => Err(format!("invalid thermostat tau '{s}': {e}")),
+    }
 }
@@ -0,0 +1,244 @@
+//! Experimental domain decomposition: the bounds are cut into vertical stripes, and every stripe
+//! is solved on its own with ghost copies of the particles from other stripes that could reach
+//! it within the step. Nothing is exchanged while a step runs, as if each stripe lived on a
+//! different machine.
+//!
+//! The halo (every ghost, i.e. what a distributed run would send per step) grows with speed and
+//! the number of stripe edges. Accuracy suffers where impacts chain across an edge: a ghost
+//! moves ballistically in its copy except for the impacts that copy sees, so a stripe can miss a
+//! deflection that happened in the neighbor, or act on one the owner resolved differently.
+//! [`DecompositionStats::ghost_error`] measures how far those copies end up from the owner's
+//! result.
+
+use engine::{Bounds, particle::Particle};
+use rayon::prelude::*;
+
+use crate::{
+    alloc,
+    miscs::{BoundaryType, DetectionType},
+    solver::Solver,
+};
+
+/// What one step cost in communication and how consistent the stripes stayed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecompositionStats {
+    /// Ghost copies made, summed over stripes.
+    pub halo: usize,
+    /// Largest distance between a ghost's final position and its owner's.
+    pub ghost_error: f32,
+    /// Contacts resolved, summed over stripes; one across an edge counts once per stripe.
+    pub contacts: usize,
+}
+
+pub struct DecomposedSolver {
+    tiles: Vec<Solver>,
+    /// Particle indices per stripe, owned ones first.
+    members: Vec<Vec<usize>>,
+    owned: Vec<usize>,
+    pub stats: DecompositionStats,
+}
+
+impl DecomposedSolver {
+    pub fn new(
+        stripes: usize,
+        cell_size: f32,
+        d_type: DetectionType,
+        b_type: BoundaryType,
+        polygon_sides: usize,
+    ) -> Self {
+        let stripes = stripes.max(1);
+
+        if matches!(b_type, BoundaryType::Periodic) {
+            log::warn!("Ghosts are not wrapped across periodic edges; expect missed impacts");
+        }
+
+        Self {
+            tiles: (0..stripes)
+                .map(|_| Solver::new(cell_size, None, d_type, b_type, polygon_sides, 0))
+                .collect(),
+            members: vec![Vec::new(); stripes],
+            owned: vec![0; stripes],
+            stats: DecompositionStats::default(),
+        }
+    }
+
+    pub fn stripes(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn solve(&mut self, particles: &mut [Particle], bounds: &Bounds, dt: f32) {
+        self.partition(particles, bounds, dt);
+
+        let section = alloc::current();
+        let results = self
+            .tiles
+            .par_iter_mut()
+            .zip(&self.members)
+            .map(|(solver, members)| {
+                let _alloc = alloc::scope(section);
+                let mut local = members.iter().map(|&i| particles[i]).collect::<Vec<_>>();
+
+                solver.solve(&mut local, bounds, dt);
+                local
+            })
+            .collect::<Vec<_>>();
+
+        for (k, local) in results.iter().enumerate() {
+            for (slot, &i) in self.members[k].iter().enumerate().take(self.owned[k]) {
+                particles[i] = local[slot];
+            }
+        }
+
+        self.stats = DecompositionStats {
+            halo: self
+                .members
+                .iter()
+                .zip(&self.owned)
+                .map(|(m, &o)| m.len() - o)
+                .sum(),
+            ghost_error: results
+                .iter()
+                .enumerate()
+                .flat_map(|(k, local)| {
+                    self.members[k]
+                        .iter()
+                        .zip(local)
+                        .skip(self.owned[k])
+                        .map(|(&i, ghost)| ghost.position.distance(particles[i].position))
+                })
+                .fold(0.0, f32::max),
+            contacts: self.tiles.iter().map(|t| t.contacts.len()).sum(),
+        };
+    }
+
+    /// Assigns every particle to the stripe holding its center, then adds as ghosts the
+    /// particles whose swept x-extent overlaps that of a stripe's own particles.
+    fn partition(&mut self, particles: &[Particle], bounds: &Bounds, dt: f32) {
+        let stripes = self.tiles.len();
+        let (hw, _) = bounds.half_extents();
+        let stripe_of = |p: &Particle| {
+            let t = (p.position.x + hw) / bounds.width * stripes as f32;
+
+            (t.max(0.0) as usize).min(stripes - 1)
+        };
+        let swept = |p: &Particle| {
+            let end = p.position.x + p.velocity.x * dt;
+
+            (
+                p.position.x.min(end) - p.radius,
+                p.position.x.max(end) + p.radius,
+            )
+        };
+        let mut extents = vec![(f32::INFINITY, f32::NEG_INFINITY); stripes];
+
+        for members in &mut self.members {
+            members.clear();
+        }
+
+        for (i, p) in particles.iter().enumerate() {
+            let k = stripe_of(p);
+            let (lo, hi) = swept(p);
+
+            self.members[k].push(i);
+            extents[k] = (extents[k].0.min(lo), extents[k].1.max(hi));
+        }
+
+        for (k, members) in self.members.iter().enumerate() {
+            self.owned[k] = members.len();
+        }
+
+        for (i, p) in particles.iter().enumerate() {
+            let owner = stripe_of(p);
+            let (lo, hi) = swept(p);
+
+            for (k, &(min, max)) in extents.iter().enumerate() {
+                if k != owner && lo <= max && hi >= min {
+                    self.members[k].push(i);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+
+    const BOUNDS: Bounds = Bounds {
+        width: 400.0,
+        height: 200.0,
+    };
+
+    fn particle(x: f32, y: f32, vx: f32, vy: f32) -> Particle {
+        Particle::new(Vec2::new(x, y), Vec2::new(vx, vy), 5.0, 1.0, [1.0; 3])
+    }
+
+    fn reference(particles: &mut [Particle], steps: usize, dt: f32) {
+        let mut solver = Solver::new(
+            20.0,
+            None,
+            DetectionType::Tccd,
+            BoundaryType::Box,
+            6,
+            particles.len() as u64,
+        );
+
+        for _ in 0..steps {
+            solver.solve(particles, &BOUNDS, dt);
+        }
+    }
+
+    #[test]
+    fn one_stripe_matches_the_plain_solver() {
+        let initial = (0..40)
+            .map(|i| {
+                let f = i as f32;
+                particle(
+                    -180.0 + 9.0 * f,
+                    60.0 * (f * 0.7).sin(),
+                    80.0 * f.cos(),
+                    50.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut expected = initial.clone();
+        let mut actual = initial;
+        let mut solver = DecomposedSolver::new(1, 20.0, DetectionType::Tccd, BoundaryType::Box, 6);
+
+        reference(&mut expected, 30, 1.0 / 60.0);
+
+        for _ in 0..30 {
+            solver.solve(&mut actual, &BOUNDS, 1.0 / 60.0);
+        }
+
+        for (a, b) in actual.iter().zip(&expected) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.velocity, b.velocity);
+        }
+
+        assert_eq!(solver.stats.halo, 0);
+    }
+
+    #[test]
+    fn impacts_across_a_stripe_edge_agree() {
+        // Head-on across x = 0, the edge between two stripes.
+        let mut particles = [
+            particle(-12.0, 0.0, 60.0, 0.0),
+            particle(12.0, 0.0, -60.0, 0.0),
+        ];
+        let mut expected = particles;
+        let mut solver = DecomposedSolver::new(2, 20.0, DetectionType::Tccd, BoundaryType::Box, 6);
+
+        reference(&mut expected, 1, 0.2);
+        solver.solve(&mut particles, &BOUNDS, 0.2);
+
+        assert_eq!(solver.stats.halo, 2);
+        assert!(solver.stats.ghost_error < 1e-4);
+
+        for (a, b) in particles.iter().zip(&expected) {
+            assert!(a.position.distance(b.position) < 1e-4);
+            assert!(a.velocity.distance(b.velocity) < 1e-4);
+        }
+    }
+}
@@ -1,131 +1,393 @@
-use engine::{Bounds, particle::Particle};
+use std::{
+    cmp::Ordering,
+    time::{Duration, Instant},
+};
+
+use engine::{
+    Bounds,
+    boundary::{self, BoundaryCondition},
+    particle::Particle,
+    trajectory,
+};
+use glam::Vec2;
+use rayon::prelude::*;
 
 use crate::{
+    alloc::{self, Section},
+    kdtree::KdTree,
     solver::{Collision, Toi},
     spatial::SpatialGrid,
 };
 
-pub trait Detector {
-    fn find_min_toi(
+/// Pairs per chunk handed to one narrow-phase task.
+const CHUNK_SIZE: usize = 1024;
+/// Fewest particles per task when testing them against the boundary.
+const MIN_WALL_TASK: usize = 1024;
+
+/// Candidate pairs `(i, j)` with `i < j` from the broad phase, grouped by `i` in ascending
+/// order and split into fixed-size chunks for the narrow phase.
+pub struct CandidatePairs {
+    pairs: Vec<(usize, usize)>,
+    chunk_size: usize,
+}
+
+impl Default for CandidatePairs {
+    fn default() -> Self {
+        Self::new(CHUNK_SIZE)
+    }
+}
+
+impl CandidatePairs {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            pairs: Vec::new(),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pairs.clear();
+    }
+
+    pub fn push(&mut self, i: usize, j: usize) {
+        self.pairs.push((i, j));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn pairs(&self) -> &[(usize, usize)] {
+        &self.pairs
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn par_chunks(&self) -> rayon::slice::Chunks<'_, (usize, usize)> {
+        self.pairs.par_chunks(self.chunk_size)
+    }
+}
+
+/// Finds the particle pairs that might collide within `dt`.
+pub trait BroadPhase: Send {
+    fn candidates(
         &mut self,
-        grid: &mut SpatialGrid,
+        grid: &SpatialGrid,
         particles: &[Particle],
+        dt: f32,
+        out: &mut CandidatePairs,
+    );
+
+    /// Share of per-particle queries answered from a cache, for broad phases that keep one.
+    fn cache_hit_rate(&self) -> Option<f32> {
+        None
+    }
+}
+
+/// Finds the earliest impact among the candidate pairs and between any particle and the
+/// boundary, with every movable particle accelerating at `gravity`. Ties go to what a sequential
+/// scan over the particles would have found first. In a periodic domain, pairs meet at their
+/// nearest images.
+pub trait NarrowPhase: Send {
+    fn min_toi(
+        &mut self,
+        pairs: &CandidatePairs,
+        particles: &[Particle],
+        boundary: &dyn BoundaryCondition,
         bounds: &Bounds,
         dt: f32,
+        gravity: Vec2,
     ) -> Option<Toi>;
 }
 
-pub struct CellListDetector;
-pub struct TccdDetector;
-pub struct SweptAabbDetector;
+pub struct CellListBroadPhase;
+pub struct TccdBroadPhase;
+pub struct SweptAabbBroadPhase;
+/// Bounds every sweep by a circle around its midpoint and pairs up the circles that overlap,
+/// found with a [`KdTree`] built anew on every call instead of the grid's cells.
+pub struct KdTreeBroadPhase;
+
+/// Tests chunks of pairs on the rayon pool.
+pub struct ParallelNarrowPhase;
+
+/// Time spent in each phase, summed over every [`Detector::find_min_toi`] call since the last
+/// reset. [`crate::solver::Solver::solve`] resets it at the start of each frame, so it covers all
+/// of that frame's iterations.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimes {
+    pub broad: Duration,
+    pub narrow: Duration,
+}
+
+/// A broad phase feeding a narrow phase.
+pub struct Detector {
+    broad: Box<dyn BroadPhase>,
+    narrow: Box<dyn NarrowPhase>,
+    pairs: CandidatePairs,
+    pub times: PhaseTimes,
+}
 
-impl Detector for CellListDetector {
-    fn find_min_toi(
+impl Detector {
+    pub fn new(broad: Box<dyn BroadPhase>, narrow: Box<dyn NarrowPhase>) -> Self {
+        Self {
+            broad,
+            narrow,
+            pairs: CandidatePairs::default(),
+            times: PhaseTimes::default(),
+        }
+    }
+
+    pub fn cache_hit_rate(&self) -> Option<f32> {
+        self.broad.cache_hit_rate()
+    }
+
+    pub fn find_min_toi(
         &mut self,
-        grid: &mut SpatialGrid,
+        grid: &SpatialGrid,
         particles: &[Particle],
+        boundary: &dyn BoundaryCondition,
         bounds: &Bounds,
         dt: f32,
+        gravity: Vec2,
     ) -> Option<Toi> {
-        let mut min_toi = None;
+        let _alloc = alloc::scope(Section::Detector);
+        let start = Instant::now();
+
+        self.pairs.clear();
+        self.broad.candidates(grid, particles, dt, &mut self.pairs);
+
+        let broad_done = Instant::now();
+        let toi = self
+            .narrow
+            .min_toi(&self.pairs, particles, boundary, bounds, dt, gravity);
 
+        self.times.broad += broad_done - start;
+        self.times.narrow += broad_done.elapsed();
+
+        toi
+    }
+}
+
+impl BroadPhase for CellListBroadPhase {
+    fn candidates(
+        &mut self,
+        grid: &SpatialGrid,
+        particles: &[Particle],
+        _dt: f32,
+        out: &mut CandidatePairs,
+    ) {
         for (i, p) in particles.iter().enumerate() {
-            for j in grid.cell_list(p) {
-                if j <= i {
-                    continue;
-                }
-
-                if let Some(t) = p2p_toi(p, &particles[j], dt)
-                    && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
-                {
-                    min_toi = Some(Toi::from((t, Collision::Pair(i, j))));
-                }
+            for j in grid.cell_list(p).filter(|&j| j > i) {
+                out.push(i, j);
             }
+        }
+    }
+}
 
-            if let Some(t) = boundary_toi(p, bounds, dt)
-                && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
+impl BroadPhase for TccdBroadPhase {
+    fn candidates(
+        &mut self,
+        grid: &SpatialGrid,
+        particles: &[Particle],
+        dt: f32,
+        out: &mut CandidatePairs,
+    ) {
+        for i in 0..particles.len() {
+            for j in grid
+                .candidates_along_sweep_with_radius(particles, i, dt)
+                .filter(|&j| j > i)
             {
-                min_toi = Some(Toi::from((t, Collision::Wall(i))));
+                out.push(i, j);
             }
         }
-
-        min_toi
     }
 }
 
-impl Detector for TccdDetector {
-    fn find_min_toi(
+impl BroadPhase for SweptAabbBroadPhase {
+    fn candidates(
         &mut self,
-        grid: &mut SpatialGrid,
+        grid: &SpatialGrid,
         particles: &[Particle],
-        bounds: &Bounds,
         dt: f32,
-    ) -> Option<Toi> {
-        let mut min_toi = None;
-
-        for (i, p1) in particles.iter().enumerate() {
-            for j in grid.candidates_along_sweep_with_radius(particles, i, dt) {
-                if j <= i {
-                    continue;
-                }
-
-                if let Some(t) = p2p_toi(p1, &particles[j], dt)
-                    && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
-                {
-                    min_toi = Some(Toi::from((t, Collision::Pair(i, j))));
-                }
-            }
-
-            if let Some(t) = boundary_toi(p1, bounds, dt)
-                && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
+        out: &mut CandidatePairs,
+    ) {
+        for i in 0..particles.len() {
+            for j in grid
+                .candidates_swept_aabb(particles, i, dt)
+                .filter(|&j| j > i)
             {
-                min_toi = Some(Toi::from((t, Collision::Wall(i))));
+                out.push(i, j);
             }
         }
-
-        min_toi
     }
 }
 
-impl Detector for SweptAabbDetector {
-    fn find_min_toi(
+impl BroadPhase for KdTreeBroadPhase {
+    fn candidates(
         &mut self,
-        grid: &mut SpatialGrid,
+        grid: &SpatialGrid,
         particles: &[Particle],
-        bounds: &Bounds,
         dt: f32,
-    ) -> Option<Toi> {
-        let mut min_toi = None;
-
-        for (i, p1) in particles.iter().enumerate() {
-            for j in grid.candidates_swept_aabb(particles, i, dt) {
-                if j <= i {
-                    continue;
-                }
-
-                if let Some(t) = p2p_toi(p1, &particles[j], dt)
-                    && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
-                {
-                    min_toi = Some(Toi::from((t, Collision::Pair(i, j))));
-                }
+        out: &mut CandidatePairs,
+    ) {
+        let period = grid.period();
+        let (centers, reaches): (Vec<Vec2>, Vec<f32>) = particles
+            .iter()
+            .map(|p| {
+                let half_travel = 0.5 * dt * p.velocity;
+                let center = p.position + half_travel;
+                let center = match period {
+                    Some(period) => (center + 0.5 * period).rem_euclid(period) - 0.5 * period,
+                    None => center,
+                };
+
+                (
+                    center,
+                    p.radius + half_travel.length() + grid.sweep_padding(),
+                )
+            })
+            .unzip();
+        let tree = KdTree::from_points(centers.iter().copied());
+        let max_reach = reaches.iter().copied().fold(0.0, f32::max);
+        // A periodic domain is searched through its neighbouring images too.
+        let images: Vec<Vec2> = match period {
+            Some(period) => (-1..=1)
+                .flat_map(|y| (-1..=1).map(move |x| Vec2::new(x as f32, y as f32) * period))
+                .collect(),
+            None => vec![Vec2::ZERO],
+        };
+        let mut found = Vec::new();
+
+        for i in 0..particles.len() {
+            found.clear();
+
+            for image in &images {
+                found.extend(tree.within(centers[i] + *image, reaches[i] + max_reach));
             }
 
-            if let Some(t) = boundary_toi(p1, bounds, dt)
-                && !min_toi.is_some_and(|toi: Toi| t >= toi.time)
-            {
-                min_toi = Some(Toi::from((t, Collision::Wall(i))));
+            found.retain(|&j| {
+                let d = match period {
+                    Some(period) => boundary::minimum_image(centers[j] - centers[i], period),
+                    None => centers[j] - centers[i],
+                };
+
+                j > i && d.length() <= reaches[i] + reaches[j]
+            });
+            found.sort_unstable();
+            found.dedup();
+
+            for &j in &found {
+                out.push(i, j);
             }
         }
+    }
+}
 
-        min_toi
+/// An impact with its place in the sequential scan order: particle `i`'s pairs in candidate
+/// order, then its wall.
+#[derive(Clone, Copy)]
+struct Ranked {
+    toi: Toi,
+    rank: (usize, bool, usize),
+}
+
+impl Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.toi
+            .time
+            .total_cmp(&other.toi.time)
+            .then(self.rank.cmp(&other.rank))
+    }
+}
+
+impl NarrowPhase for ParallelNarrowPhase {
+    fn min_toi(
+        &mut self,
+        pairs: &CandidatePairs,
+        particles: &[Particle],
+        boundary: &dyn BoundaryCondition,
+        bounds: &Bounds,
+        dt: f32,
+        gravity: Vec2,
+    ) -> Option<Toi> {
+        let chunk_size = pairs.chunk_size();
+        let period = boundary.period(bounds);
+        let section = alloc::current();
+        let pair_hits = pairs.par_chunks().enumerate().filter_map(|(c, chunk)| {
+            let _alloc = alloc::scope(section);
+
+            chunk
+                .iter()
+                .enumerate()
+                .filter_map(|(k, &(i, j))| {
+                    p2p_toi(&particles[i], &particles[j], dt, gravity, period).map(|t| Ranked {
+                        toi: Toi::from((t, Collision::Pair(i, j))),
+                        rank: (i, false, c * chunk_size + k),
+                    })
+                })
+                .min_by(Ranked::cmp)
+        });
+        let wall_hits = particles
+            .par_iter()
+            .with_min_len(MIN_WALL_TASK)
+            .enumerate()
+            .filter_map(|(i, p)| {
+                let _alloc = alloc::scope(section);
+                let accel = trajectory::acceleration(p, gravity);
+
+                boundary.toi(p, accel, bounds, dt).map(|t| Ranked {
+                    toi: Toi::from((t, Collision::Wall(i))),
+                    rank: (i, true, 0),
+                })
+            });
+
+        pair_hits
+            .chain(wall_hits)
+            .min_by(Ranked::cmp)
+            .map(|hit| hit.toi)
     }
 }
 
-fn p2p_toi(p1: &Particle, p2: &Particle, dt: f32) -> Option<f32> {
-    let dp = p2.position - p1.position;
+/// Time of impact between `p1` and `p2`, or between their images nearest at the start of the
+/// step when the domain repeats every `period`.
+fn p2p_toi(
+    p1: &Particle,
+    p2: &Particle,
+    dt: f32,
+    gravity: Vec2,
+    period: Option<Vec2>,
+) -> Option<f32> {
+    let dp = match period {
+        Some(period) => boundary::minimum_image(p2.position - p1.position, period),
+        None => p2.position - p1.position,
+    };
     let dv = p2.velocity - p1.velocity;
+    let da = trajectory::acceleration(p2, gravity) - trajectory::acceleration(p1, gravity);
     let r = p1.radius + p2.radius;
 
+    // Falling together keeps their relative path straight; against a frozen particle it curves.
+    if da != Vec2::ZERO {
+        let (dp, dv, h, r) = (dp.as_dvec2(), dv.as_dvec2(), 0.5 * da.as_dvec2(), r as f64);
+
+        // r² - |dp + dv t + h t²|², the overlap of the two discs.
+        return trajectory::first_contact(
+            &[
+                r * r - dp.dot(dp),
+                -2.0 * dp.dot(dv),
+                -(dv.dot(dv) + 2.0 * dp.dot(h)),
+                -2.0 * dv.dot(h),
+                -h.dot(h),
+            ],
+            dt,
+        );
+    }
+
     let a = dv.dot(dv);
     let b = 2.0 * dp.dot(dv);
     let c = dp.dot(dp) - r * r;
@@ -157,47 +419,157 @@ fn p2p_toi(p1: &Particle, p2: &Particle, dt: f32) -> Option<f32> {
     }
 }
 
-fn boundary_toi(p: &Particle, bounds: &Bounds, dt: f32) -> Option<f32> {
-    let (hw, hh) = bounds.half_extents();
-    let pos = p.position;
-    let vel = p.velocity;
-    let r = p.radius;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::boundary::{Periodic, ReflectiveBox};
+
+    const TOL: f32 = 1e-4;
+
+    fn particle(position: (f32, f32), velocity: (f32, f32), radius: f32) -> Particle {
+        Particle::new(
+            Vec2::from(position),
+            Vec2::from(velocity),
+            radius,
+            1.0,
+            [1.0; 3],
+        )
+    }
 
-    let (x_min, x_max) = (-hw + r, hw - r);
-    let (y_min, y_max) = (-hh + r, hh - r);
+    /// Runs every broad phase on the same scene and returns their answers.
+    fn detect(particles: &[Particle], bounds: Bounds, dt: f32) -> Vec<Option<Toi>> {
+        detect_in(particles, &ReflectiveBox, bounds, dt)
+    }
 
-    let mut t_min = f32::INFINITY;
+    fn detect_in(
+        particles: &[Particle],
+        boundary: &dyn BoundaryCondition,
+        bounds: Bounds,
+        dt: f32,
+    ) -> Vec<Option<Toi>> {
+        let broad_phases: [Box<dyn BroadPhase>; 4] = [
+            Box::new(CellListBroadPhase),
+            Box::new(TccdBroadPhase),
+            Box::new(SweptAabbBroadPhase),
+            Box::new(KdTreeBroadPhase),
+        ];
+
+        broad_phases
+            .into_iter()
+            .map(|broad| {
+                let mut detector = Detector::new(broad, Box::new(ParallelNarrowPhase));
+                let mut grid = SpatialGrid::new(100.0);
+
+                grid.set_period(boundary.period(&bounds));
+                grid.rebuild(particles);
+                detector.find_min_toi(&grid, particles, boundary, &bounds, dt, Vec2::ZERO)
+            })
+            .collect()
+    }
 
-    if vel.x > 0.0 {
-        let t = (x_max - pos.x) / vel.x;
+    fn assert_pair(results: &[Option<Toi>], (i, j): (usize, usize), time: f32) {
+        for toi in results {
+            let toi = toi.expect("detector missed the collision");
 
-        if t >= 0.0 && t <= dt {
-            t_min = t_min.min(t);
+            assert!(matches!(toi.collision, Collision::Pair(a, b) if (a, b) == (i, j)));
+            assert!((toi.time - time).abs() < TOL, "{} != {time}", toi.time);
         }
-    } else if vel.x < 0.0 {
-        let t = (x_min - pos.x) / vel.x;
+    }
 
-        if t >= 0.0 && t <= dt {
-            t_min = t_min.min(t);
-        }
+    const WIDE: Bounds = Bounds {
+        width: 1000.0,
+        height: 1000.0,
+    };
+
+    #[test]
+    fn head_on() {
+        let particles = [
+            particle((-20.0, 0.0), (10.0, 0.0), 5.0),
+            particle((20.0, 0.0), (-10.0, 0.0), 5.0),
+        ];
+
+        // Gap of 30 closed at 20 per second.
+        assert_pair(&detect(&particles, WIDE, 2.0), (0, 1), 1.5);
+    }
+
+    #[test]
+    fn head_on_across_the_seam() {
+        let bounds = Bounds {
+            width: 300.0,
+            height: 300.0,
+        };
+        let particles = [
+            particle((-140.0, 0.0), (-10.0, 0.0), 5.0),
+            particle((140.0, 0.0), (10.0, 0.0), 5.0),
+        ];
+
+        // 20 apart through the seam, so a gap of 10 closed at 20 per second.
+        assert_pair(&detect_in(&particles, &Periodic, bounds, 2.0), (0, 1), 0.5);
+        assert!(detect(&particles, bounds, 0.4).iter().all(Option::is_none));
     }
 
-    if vel.y > 0.0 {
-        let t = (y_max - pos.y) / vel.y;
+    #[test]
+    fn grazing_pass() {
+        let hit = [
+            particle((0.0, 0.0), (0.0, 0.0), 5.0),
+            particle((-40.0, 6.0), (20.0, 0.0), 5.0),
+        ];
+        let miss = [
+            particle((0.0, 0.0), (0.0, 0.0), 5.0),
+            particle((-40.0, 10.5), (20.0, 0.0), 5.0),
+        ];
+
+        // Contact once dx² + 6² = 10², i.e. after travelling 40 - 8.
+        assert_pair(&detect(&hit, WIDE, 2.0), (0, 1), 1.6);
+        assert!(detect(&miss, WIDE, 2.0).iter().all(Option::is_none));
+    }
 
-        if t >= 0.0 && t <= dt {
-            t_min = t_min.min(t);
-        }
-    } else if vel.y < 0.0 {
-        let t = (y_min - pos.y) / vel.y;
+    #[test]
+    fn fast_small_against_slow_large() {
+        let particles = [
+            particle((-60.0, 0.0), (200.0, 0.0), 1.0),
+            particle((20.0, 0.0), (-5.0, 0.0), 20.0),
+        ];
 
-        if t >= 0.0 && t <= dt {
-            t_min = t_min.min(t);
+        assert_pair(&detect(&particles, WIDE, 0.5), (0, 1), 59.0 / 205.0);
+    }
+
+    #[test]
+    fn corner_approach() {
+        let bounds = Bounds {
+            width: 100.0,
+            height: 100.0,
+        };
+        let particles = [particle((30.0, 30.0), (20.0, 20.0), 5.0)];
+
+        // Both walls are 15 away along their axis, so the corner is reached at once.
+        for toi in detect(&particles, bounds, 1.0) {
+            let toi = toi.expect("detector missed the corner");
+
+            assert!(matches!(toi.collision, Collision::Wall(0)));
+            assert!((toi.time - 0.75).abs() < TOL);
         }
     }
 
-    match t_min.is_finite() {
-        true => Some(t_min),
-        false => None,
+    #[test]
+    fn simultaneous_impacts_resolve_in_scan_order() {
+        // Two identical head-on pairs, far apart, listed out of order in chunks of one.
+        let particles = [
+            particle((-20.0, 0.0), (10.0, 0.0), 5.0),
+            particle((20.0, 0.0), (-10.0, 0.0), 5.0),
+            particle((-20.0, 300.0), (10.0, 0.0), 5.0),
+            particle((20.0, 300.0), (-10.0, 0.0), 5.0),
+        ];
+        let mut pairs = CandidatePairs::new(1);
+
+        pairs.push(2, 3);
+        pairs.push(0, 1);
+
+        let toi = ParallelNarrowPhase
+            .min_toi(&pairs, &particles, &ReflectiveBox, &WIDE, 2.0, Vec2::ZERO)
+            .unwrap();
+
+        assert!(matches!(toi.collision, Collision::Pair(0, 1)));
+        assert!((toi.time - 1.5).abs() < TOL);
     }
 }
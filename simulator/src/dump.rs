@@ -0,0 +1,116 @@
+use std::{
+    fs, io,
+    path::Path,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use engine::particle::Particle;
+use serde::Serialize;
+
+use crate::{
+    miscs::{self, ParticleRow},
+    spatial::SpatialGrid,
+    thermo,
+};
+
+/// Everything needed to inspect the simulation offline, captured at a single frame.
+pub struct StateDump {
+    summary: SummaryRow,
+    particles: Vec<ParticleRow>,
+    cells: Vec<CellRow>,
+}
+
+#[derive(Serialize)]
+struct SummaryRow {
+    frame: u64,
+    time_s: f32,
+    particle_count: usize,
+    kinetic_energy: f64,
+    solver_iterations: usize,
+    cell_size: f32,
+    occupied_cells: usize,
+    max_occupancy: usize,
+    mean_occupancy: f32,
+}
+
+#[derive(Serialize)]
+struct CellRow {
+    cx: i32,
+    cy: i32,
+    count: usize,
+}
+
+impl StateDump {
+    pub fn capture(
+        frame: u64,
+        time_s: f32,
+        particles: &[Particle],
+        grid: &SpatialGrid,
+        solver_iterations: usize,
+    ) -> Self {
+        let cells = grid
+            .occupancy()
+            .map(|(c, count)| CellRow {
+                cx: c.x,
+                cy: c.y,
+                count,
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            summary: SummaryRow {
+                frame,
+                time_s,
+                particle_count: particles.len(),
+                kinetic_energy: thermo::kinetic_energy(particles),
+                solver_iterations,
+                cell_size: grid.cell_size(),
+                occupied_cells: cells.len(),
+                max_occupancy: cells.iter().map(|c| c.count).max().unwrap_or(0),
+                mean_occupancy: match cells.len() {
+                    0 => 0.0,
+                    n => particles.len() as f32 / n as f32,
+                },
+            },
+            particles: particles
+                .iter()
+                .enumerate()
+                .map(|(i, p)| ParticleRow::new(frame, time_s, i, p))
+                .collect(),
+            cells,
+        }
+    }
+
+    /// Writes the bundle into a fresh timestamped directory on a background thread, so the
+    /// simulation does not stall while the files are written.
+    pub fn save_in_background(self) {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let dir = miscs::output_path(format!("dump_{millis}"));
+
+        thread::spawn(move || match self.save(&dir) {
+            Ok(()) => log::info!("Dumped simulation state to {}", dir.display()),
+            Err(e) => log::error!("Failed to dump state to {}: {e}", dir.display()),
+        });
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        write_csv(&dir.join("summary.csv"), [&self.summary])?;
+        write_csv(&dir.join("particles.csv"), &self.particles)?;
+        write_csv(&dir.join("cells.csv"), &self.cells)
+    }
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: impl IntoIterator<Item = T>) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,225 @@
+use engine::boundary::Wall;
+use glam::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Pair(PairEvent),
+    Wall(WallEvent),
+    RestingPair(PairEvent),
+    RestingWall(WallEvent),
+    Intervention(InterventionEvent),
+}
+
+impl Event {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::Pair(_) => "pair",
+            Event::Wall(_) => "wall",
+            Event::RestingPair(_) => "resting_pair",
+            Event::RestingWall(_) => "resting_wall",
+            Event::Intervention(_) => "intervention",
+        }
+    }
+
+    /// Whether the contact was slow enough to be held rather than bounced.
+    pub fn is_resting(&self) -> bool {
+        matches!(self, Event::RestingPair(_) | Event::RestingWall(_))
+    }
+
+    pub fn normal(&self) -> Vec2 {
+        match self {
+            Event::Pair(e) | Event::RestingPair(e) => e.normal,
+            Event::Wall(e) | Event::RestingWall(e) => e.normal,
+            Event::Intervention(_) => Vec2::ZERO,
+        }
+    }
+
+    /// Indices of the particles involved; wall events only have one.
+    pub fn particles(&self) -> (usize, Option<usize>) {
+        match self {
+            Event::Pair(e) | Event::RestingPair(e) => (e.i, Some(e.j)),
+            Event::Wall(e) | Event::RestingWall(e) => (e.i, None),
+            Event::Intervention(e) => (e.i, None),
+        }
+    }
+}
+
+/// A change made to a particle from outside the solver, e.g. through the viewer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterventionEvent {
+    pub i: usize,
+    pub action: Intervention,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intervention {
+    Velocity(Vec2),
+    Color([f32; 3]),
+    /// Gives the particle infinite mass and stops it.
+    Freeze,
+    /// Solver settings from the controls panel. They apply to every particle, so they are
+    /// logged without ids.
+    Restitution(f32),
+    Friction(f32),
+    /// Downward, in world units per second squared.
+    Gravity(f32),
+    CellSize(f32),
+}
+
+impl Intervention {
+    /// The new value of a solver setting.
+    pub fn setting(&self) -> Option<f32> {
+        match *self {
+            Intervention::Restitution(v)
+            | Intervention::Friction(v)
+            | Intervention::Gravity(v)
+            | Intervention::CellSize(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Intervention::Velocity(_) => "velocity",
+            Intervention::Color(_) => "color",
+            Intervention::Freeze => "freeze",
+            Intervention::Restitution(_) => "restitution",
+            Intervention::Friction(_) => "friction",
+            Intervention::Gravity(_) => "gravity",
+            Intervention::CellSize(_) => "cell_size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairEvent {
+    pub toi: f32,
+    pub i: usize,
+    pub j: usize,
+    pub normal: Vec2,
+    pub vrel_n_before: f32,
+    pub vrel_n_after: f32,
+}
+
+impl PairEvent {
+    pub fn new(toi: f32, i: usize, j: usize) -> Self {
+        Self {
+            toi,
+            i,
+            j,
+            normal: Vec2::ZERO,
+            vrel_n_before: 0.0,
+            vrel_n_after: 0.0,
+        }
+    }
+
+    pub fn normal(mut self, normal: Vec2) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn vrel_n(mut self, before: f32, after: f32) -> Self {
+        self.vrel_n_before = before;
+        self.vrel_n_after = after;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallEvent {
+    pub toi: f32,
+    pub i: usize,
+    pub wall: Wall,
+    pub normal: Vec2,
+    pub vn_before: f32,
+    pub vn_after: f32,
+}
+
+impl WallEvent {
+    pub fn new(toi: f32, i: usize, wall: Wall) -> Self {
+        Self {
+            toi,
+            i,
+            wall,
+            normal: Vec2::ZERO,
+            vn_before: 0.0,
+            vn_after: 0.0,
+        }
+    }
+
+    pub fn normal(mut self, normal: Vec2) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn vn(mut self, before: f32, after: f32) -> Self {
+        self.vn_before = before;
+        self.vn_after = after;
+        self
+    }
+}
+
+impl From<PairEvent> for Event {
+    fn from(value: PairEvent) -> Self {
+        Event::Pair(value)
+    }
+}
+
+impl From<InterventionEvent> for Event {
+    fn from(value: InterventionEvent) -> Self {
+        Event::Intervention(value)
+    }
+}
+
+impl From<WallEvent> for Event {
+    fn from(value: WallEvent) -> Self {
+        Event::Wall(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_builder_sets_fields() {
+        let e = PairEvent::new(0.25, 3, 7)
+            .normal(Vec2::new(0.0, 1.0))
+            .vrel_n(-2.0, 2.0);
+
+        assert_eq!(e.toi, 0.25);
+        assert_eq!((e.i, e.j), (3, 7));
+        assert_eq!(e.normal, Vec2::new(0.0, 1.0));
+        assert_eq!((e.vrel_n_before, e.vrel_n_after), (-2.0, 2.0));
+    }
+
+    #[test]
+    fn wall_builder_sets_fields() {
+        let e = WallEvent::new(0.5, 4, Wall::Left)
+            .normal(Vec2::new(-1.0, 0.0))
+            .vn(-3.0, 3.0);
+
+        assert_eq!(e.toi, 0.5);
+        assert_eq!(e.i, 4);
+        assert_eq!(e.wall, Wall::Left);
+        assert_eq!(e.normal, Vec2::new(-1.0, 0.0));
+        assert_eq!((e.vn_before, e.vn_after), (-3.0, 3.0));
+    }
+
+    #[test]
+    fn builder_defaults_are_zeroed() {
+        let e = PairEvent::new(0.0, 0, 1);
+
+        assert_eq!(e.normal, Vec2::ZERO);
+        assert_eq!((e.vrel_n_before, e.vrel_n_after), (0.0, 0.0));
+    }
+
+    #[test]
+    fn converts_into_event() {
+        let pair = PairEvent::new(0.1, 0, 1);
+        let wall = WallEvent::new(0.2, 2, Wall::Top);
+
+        assert_eq!(Event::from(pair), Event::Pair(pair));
+        assert_eq!(Event::from(wall), Event::Wall(wall));
+    }
+}
@@ -0,0 +1,406 @@
+//! Compact binary index of a recorded events CSV. Events are stored column by column in frame
+//! order, next to a second view sorted by particle, so the events of a particle over a range of
+//! frames are found with a binary search instead of a scan of the whole CSV. Pair events appear in
+//! the particle view under both of their particles.
+
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+use anyhow::{Context, bail, ensure};
+
+const MAGIC: &[u8; 4] = b"TCEI";
+const VERSION: u32 = 1;
+/// Stored in the `j` column of events that involve a single particle.
+const NONE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventKind {
+    Pair,
+    Wall,
+    RestingPair,
+    RestingWall,
+    Intervention,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 5] = [
+        EventKind::Pair,
+        EventKind::Wall,
+        EventKind::RestingPair,
+        EventKind::RestingWall,
+        EventKind::Intervention,
+    ];
+
+    /// The value of the `type` column.
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Pair => "Pair",
+            EventKind::Wall => "Wall",
+            EventKind::RestingPair => "RestingPair",
+            EventKind::RestingWall => "RestingWall",
+            EventKind::Intervention => "Intervention",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    fn is_pair(self) -> bool {
+        matches!(self, EventKind::Pair | EventKind::RestingPair)
+    }
+}
+
+/// One row of the index. Interventions have no normal or velocities, so those are NaN.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexedEvent {
+    pub frame: u64,
+    pub time_s: f32,
+    pub toi: f32,
+    pub kind: EventKind,
+    pub i: usize,
+    pub j: Option<usize>,
+    pub nx: f32,
+    pub ny: f32,
+    /// Normal velocity before the response: relative for pairs, the particle's own for walls.
+    pub before: f32,
+    pub after: f32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct EventIndex {
+    frame: Vec<u64>,
+    time_s: Vec<f32>,
+    toi: Vec<f32>,
+    kind: Vec<u8>,
+    i: Vec<u32>,
+    j: Vec<u32>,
+    nx: Vec<f32>,
+    ny: Vec<f32>,
+    before: Vec<f32>,
+    after: Vec<f32>,
+    /// `(particle, row)` for every particle of every event, sorted.
+    by_particle: Vec<(u32, u32)>,
+}
+
+impl EventIndex {
+    /// Reads an events CSV as written by the recorder.
+    pub fn from_csv(path: &Path) -> anyhow::Result<Self> {
+        let mut reader =
+            csv::Reader::from_path(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut events = Vec::new();
+
+        for (line, record) in reader.records().enumerate() {
+            let record = record.with_context(|| format!("parsing {}", path.display()))?;
+
+            events.push(
+                parse_record(&record)
+                    .with_context(|| format!("{} row {}", path.display(), line + 1))?,
+            );
+        }
+
+        Ok(Self::from_events(events))
+    }
+
+    pub fn from_events(mut events: Vec<IndexedEvent>) -> Self {
+        events.sort_by_key(|e| e.frame);
+
+        let mut index = Self::default();
+
+        for (row, e) in events.iter().enumerate() {
+            index.frame.push(e.frame);
+            index.time_s.push(e.time_s);
+            index.toi.push(e.toi);
+            index.kind.push(e.kind as u8);
+            index.i.push(e.i as u32);
+            index.j.push(e.j.map_or(NONE, |j| j as u32));
+            index.nx.push(e.nx);
+            index.ny.push(e.ny);
+            index.before.push(e.before);
+            index.after.push(e.after);
+
+            index.by_particle.push((e.i as u32, row as u32));
+            index
+                .by_particle
+                .extend(e.j.map(|j| (j as u32, row as u32)));
+        }
+
+        index.by_particle.sort_unstable();
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.frame.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frame.is_empty()
+    }
+
+    /// First and last frame with an event.
+    pub fn frame_range(&self) -> Option<RangeInclusive<u64>> {
+        Some(*self.frame.first()?..=*self.frame.last()?)
+    }
+
+    /// Number of distinct particles that took part in an event.
+    pub fn particle_count(&self) -> usize {
+        self.by_particle.chunk_by(|a, b| a.0 == b.0).count()
+    }
+
+    pub fn get(&self, row: usize) -> IndexedEvent {
+        IndexedEvent {
+            frame: self.frame[row],
+            time_s: self.time_s[row],
+            toi: self.toi[row],
+            kind: EventKind::ALL[self.kind[row] as usize],
+            i: self.i[row] as usize,
+            j: (self.j[row] != NONE).then_some(self.j[row] as usize),
+            nx: self.nx[row],
+            ny: self.ny[row],
+            before: self.before[row],
+            after: self.after[row],
+        }
+    }
+
+    /// Events involving particle `id` during `frames`, in recorded order.
+    pub fn particle(
+        &self,
+        id: usize,
+        frames: RangeInclusive<u64>,
+    ) -> impl Iterator<Item = IndexedEvent> {
+        let rows = self.particle_rows(id as u32);
+        // Rows are in frame order, so so are a particle's rows.
+        let start = rows.partition_point(|&(_, row)| self.frame[row as usize] < *frames.start());
+        let end = rows.partition_point(|&(_, row)| self.frame[row as usize] <= *frames.end());
+
+        rows[start..end.max(start)]
+            .iter()
+            .map(|&(_, row)| self.get(row as usize))
+    }
+
+    fn particle_rows(&self, id: u32) -> &[(u32, u32)] {
+        let start = self.by_particle.partition_point(|&(p, _)| p < id);
+        let end = self.by_particle.partition_point(|&(p, _)| p <= id);
+
+        &self.by_particle[start..end]
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file =
+            fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.len() as u64).to_le_bytes())?;
+        out.write_all(&(self.by_particle.len() as u64).to_le_bytes())?;
+
+        write_column(&mut out, &self.frame)?;
+        for column in [&self.time_s, &self.toi] {
+            write_column(&mut out, column)?;
+        }
+        write_column(&mut out, &self.kind)?;
+        for column in [&self.i, &self.j] {
+            write_column(&mut out, column)?;
+        }
+        for column in [&self.nx, &self.ny, &self.before, &self.after] {
+            write_column(&mut out, column)?;
+        }
+        for (p, row) in &self.by_particle {
+            out.write_all(&p.to_le_bytes())?;
+            out.write_all(&row.to_le_bytes())?;
+        }
+
+        out.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let mut bytes = Bytes(&data);
+
+        if bytes.take(4)? != MAGIC {
+            bail!("{} is not an event index", path.display());
+        }
+
+        let version = u32::read(&mut bytes)?;
+        ensure!(
+            version == VERSION,
+            "{} has index version {version}, expected {VERSION}",
+            path.display()
+        );
+
+        let rows = u64::read(&mut bytes)? as usize;
+        let links = u64::read(&mut bytes)? as usize;
+        let mut index = Self {
+            frame: read_column(&mut bytes, rows)?,
+            time_s: read_column(&mut bytes, rows)?,
+            toi: read_column(&mut bytes, rows)?,
+            kind: read_column(&mut bytes, rows)?,
+            i: read_column(&mut bytes, rows)?,
+            j: read_column(&mut bytes, rows)?,
+            nx: read_column(&mut bytes, rows)?,
+            ny: read_column(&mut bytes, rows)?,
+            before: read_column(&mut bytes, rows)?,
+            after: read_column(&mut bytes, rows)?,
+            by_particle: Vec::with_capacity(links),
+        };
+
+        for _ in 0..links {
+            index
+                .by_particle
+                .push((u32::read(&mut bytes)?, u32::read(&mut bytes)?));
+        }
+
+        ensure!(bytes.0.is_empty(), "{} has trailing data", path.display());
+        ensure!(
+            index
+                .kind
+                .iter()
+                .all(|&k| (k as usize) < EventKind::ALL.len())
+                && index
+                    .by_particle
+                    .iter()
+                    .all(|&(_, row)| (row as usize) < rows),
+            "{} is corrupt",
+            path.display()
+        );
+
+        Ok(index)
+    }
+}
+
+/// Columns by position, since the header only names the columns of the first row's type.
+fn parse_record(record: &csv::StringRecord) -> anyhow::Result<IndexedEvent> {
+    let field = |k: usize| record.get(k).context("missing column");
+    let float = |k: usize| -> anyhow::Result<f32> { Ok(field(k)?.parse()?) };
+
+    let kind = EventKind::from_name(field(0)?)
+        .with_context(|| format!("unknown event type {:?}", field(0).unwrap_or_default()))?;
+    let intervention = kind == EventKind::Intervention;
+
+    Ok(IndexedEvent {
+        frame: field(1)?.parse()?,
+        time_s: float(2)?,
+        toi: float(3)?,
+        kind,
+        i: field(4)?.parse()?,
+        j: match kind.is_pair() {
+            true => Some(field(5)?.parse()?),
+            false => None,
+        },
+        nx: if intervention { f32::NAN } else { float(6)? },
+        ny: if intervention { f32::NAN } else { float(7)? },
+        before: if intervention { f32::NAN } else { float(8)? },
+        after: if intervention { f32::NAN } else { float(9)? },
+    })
+}
+
+trait Le: Sized {
+    const SIZE: usize;
+
+    fn write(&self, out: &mut impl Write) -> std::io::Result<()>;
+
+    fn read(bytes: &mut Bytes) -> anyhow::Result<Self>;
+}
+
+macro_rules! impl_le {
+    ($($t:ty),*) => {$(
+        impl Le for $t {
+            const SIZE: usize = size_of::<$t>();
+
+            fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+                out.write_all(&self.to_le_bytes())
+            }
+
+            fn read(bytes: &mut Bytes) -> anyhow::Result<Self> {
+                Ok(<$t>::from_le_bytes(bytes.take(Self::SIZE)?.try_into()?))
+            }
+        }
+    )*};
+}
+
+impl_le!(u8, u32, u64, f32);
+
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.0.len() >= n, "event index is truncated");
+
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+
+        Ok(head)
+    }
+}
+
+fn write_column<T: Le>(out: &mut impl Write, column: &[T]) -> std::io::Result<()> {
+    column.iter().try_for_each(|v| v.write(out))
+}
+
+fn read_column<T: Le>(bytes: &mut Bytes, len: usize) -> anyhow::Result<Vec<T>> {
+    ensure!(
+        len.checked_mul(T::SIZE).is_some_and(|n| n <= bytes.0.len()),
+        "event index is truncated"
+    );
+
+    (0..len).map(|_| T::read(bytes)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(frame: u64, kind: EventKind, i: usize, j: Option<usize>) -> IndexedEvent {
+        IndexedEvent {
+            frame,
+            time_s: frame as f32 * 0.1,
+            toi: 0.05,
+            kind,
+            i,
+            j,
+            nx: 1.0,
+            ny: 0.0,
+            before: -2.0,
+            after: 2.0,
+        }
+    }
+
+    #[test]
+    fn looks_up_by_particle_and_round_trips() {
+        let index = EventIndex::from_events(vec![
+            event(1, EventKind::Pair, 0, Some(3)),
+            event(2, EventKind::Wall, 3, None),
+            event(2, EventKind::Pair, 1, Some(2)),
+            event(5, EventKind::RestingPair, 2, Some(3)),
+        ]);
+
+        assert_eq!(index.frame_range(), Some(1..=5));
+        assert_eq!(index.particle_count(), 4);
+        assert_eq!(
+            index
+                .particle(3, 0..=u64::MAX)
+                .map(|e| e.frame)
+                .collect::<Vec<_>>(),
+            [1, 2, 5]
+        );
+        assert_eq!(
+            index.particle(3, 2..=4).map(|e| e.kind).collect::<Vec<_>>(),
+            [EventKind::Wall]
+        );
+
+        let path = std::env::temp_dir().join(format!("tccd-index-{}.idx", std::process::id()));
+        index.save(&path).unwrap();
+        let loaded = EventIndex::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, index);
+    }
+}
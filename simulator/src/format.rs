@@ -0,0 +1,190 @@
+//! Number formatting for the recorder's CSV output.
+
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use anyhow::bail;
+use serde::Serializer;
+
+static FLOAT_FORMAT: OnceLock<FloatFormat> = OnceLock::new();
+
+/// How recorded floats are written. Every variant parses back with the standard float parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// Fewest digits that read back to the same value.
+    Shortest,
+    /// `%.Ng`: `N` significant digits, scientific only for very large or small magnitudes.
+    General(usize),
+    /// `%.Ne`: scientific with `N` digits after the point.
+    Scientific(usize),
+    /// `%.Nf`: `N` digits after the point.
+    Fixed(usize),
+}
+
+/// `%.9g`, enough to read every `f32` back exactly at a fixed width.
+impl Default for FloatFormat {
+    fn default() -> Self {
+        FloatFormat::General(9)
+    }
+}
+
+impl FloatFormat {
+    pub fn format(self, v: f64) -> String {
+        if !v.is_finite() {
+            return v.to_string();
+        }
+
+        match self {
+            // Debug switches to an exponent for extreme magnitudes, like the CSV writer did.
+            FloatFormat::Shortest => format!("{v:?}"),
+            FloatFormat::General(digits) => general(v, digits.max(1)),
+            FloatFormat::Scientific(digits) => format!("{v:.digits$e}"),
+            FloatFormat::Fixed(digits) => format!("{v:.digits$}"),
+        }
+    }
+
+    fn format_f32(self, v: f32) -> String {
+        match self {
+            // Shortest for the f32 itself, not for its f64 widening.
+            FloatFormat::Shortest if v.is_finite() => format!("{v:?}"),
+            _ => self.format(v as f64),
+        }
+    }
+}
+
+/// `%g`: scientific when the exponent is below -4 or not below `digits`, fixed otherwise, with
+/// trailing zeros removed.
+fn general(v: f64, digits: usize) -> String {
+    let sci = format!("{v:.*e}", digits - 1);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp = exp.parse::<i32>().unwrap();
+
+    if exp < -4 || exp >= digits as i32 {
+        return format!("{}e{exp}", trim_zeros(mantissa));
+    }
+
+    let decimals = (digits as i32 - 1 - exp) as usize;
+
+    trim_zeros(&format!("{v:.decimals$}")).to_string()
+}
+
+fn trim_zeros(s: &str) -> &str {
+    match s.contains('.') {
+        true => s.trim_end_matches('0').trim_end_matches('.'),
+        false => s,
+    }
+}
+
+impl FromStr for FloatFormat {
+    type Err = anyhow::Error;
+
+    /// `shortest`, or a printf-style `%.Ng`, `%.Ne` or `%.Nf`.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s == "shortest" {
+            return Ok(FloatFormat::Shortest);
+        }
+
+        let Some(spec) = s.strip_prefix("%.") else {
+            bail!("expected 'shortest' or %.N followed by g, e or f, got '{s}'");
+        };
+        let (digits, kind) = spec.split_at(spec.len().saturating_sub(1));
+        let digits = digits.parse::<usize>()?;
+
+        Ok(match kind {
+            "g" => FloatFormat::General(digits),
+            "e" => FloatFormat::Scientific(digits),
+            "f" => FloatFormat::Fixed(digits),
+            _ => bail!("unknown float format '{kind}' in '{s}', expected g, e or f"),
+        })
+    }
+}
+
+impl fmt::Display for FloatFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FloatFormat::Shortest => write!(f, "shortest"),
+            FloatFormat::General(n) => write!(f, "%.{n}g"),
+            FloatFormat::Scientific(n) => write!(f, "%.{n}e"),
+            FloatFormat::Fixed(n) => write!(f, "%.{n}f"),
+        }
+    }
+}
+
+/// Sets the format for all recorder output. Only the first call has an effect, so call it
+/// before recording starts.
+pub fn set_float_format(format: FloatFormat) {
+    if FLOAT_FORMAT.set(format).is_err() {
+        log::warn!("Float format already set, ignoring {format}");
+    }
+}
+
+pub fn float_format() -> FloatFormat {
+    FLOAT_FORMAT.get().copied().unwrap_or_default()
+}
+
+/// `serialize_with` helpers for row fields.
+pub fn f32<S: Serializer>(v: &f32, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&float_format().format_f32(*v))
+}
+
+pub fn f64<S: Serializer>(v: &f64, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&float_format().format(*v))
+}
+
+pub fn opt_f32<S: Serializer>(v: &Option<f32>, s: S) -> Result<S::Ok, S::Error> {
+    match v {
+        Some(v) => f32(v, s),
+        None => s.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_like_printf() {
+        let g9 = FloatFormat::General(9);
+
+        assert_eq!(g9.format(0.1), "0.1");
+        assert_eq!(g9.format(1.0 / 3.0), "0.333333333");
+        assert_eq!(g9.format(123456789012.0), "1.23456789e11");
+        assert_eq!(g9.format(-2.5e-7), "-2.5e-7");
+        assert_eq!(g9.format(100.0), "100");
+        assert_eq!(FloatFormat::General(3).format(999.6), "1e3");
+        assert_eq!(FloatFormat::Scientific(2).format(1234.5), "1.23e3");
+        assert_eq!(FloatFormat::Fixed(3).format(-0.00049), "-0.000");
+        assert_eq!(g9.format(f64::NAN), "NaN");
+        assert_eq!(FloatFormat::Shortest.format_f32(0.1), "0.1");
+        assert_eq!(FloatFormat::Shortest.format(1e-300), "1e-300");
+    }
+
+    #[test]
+    fn every_format_reads_back() {
+        for format in [
+            FloatFormat::Shortest,
+            FloatFormat::General(9),
+            FloatFormat::Scientific(8),
+            FloatFormat::Fixed(4),
+        ] {
+            for v in [0.0f32, -1.5, 3.0e-6, 7.25e9, f32::INFINITY] {
+                let read = format.format_f32(v).parse::<f32>().unwrap();
+
+                match format {
+                    FloatFormat::Fixed(_) => assert!((read - v).abs() <= 1e-4 || read == v),
+                    _ => assert_eq!(read, v, "{format} {v}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_printf_specs() {
+        for s in ["shortest", "%.9g", "%.6e", "%.4f"] {
+            assert_eq!(s.parse::<FloatFormat>().unwrap().to_string(), s);
+        }
+
+        for s in ["%9g", "%.g", "%.9x", "9"] {
+            assert!(s.parse::<FloatFormat>().is_err(), "{s}");
+        }
+    }
+}
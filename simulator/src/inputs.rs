@@ -0,0 +1,223 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::event::Intervention;
+
+/// One viewer intervention as written to the inputs CSV. The action is applied to every particle
+/// in `ids` (space separated) before the step that follows `frame`. Solver settings have no ids
+/// and keep their new value in `value`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRow {
+    pub frame: u64,
+    pub time_s: f32,
+    pub action: String,
+    pub ids: String,
+    pub vx: f32,
+    pub vy: f32,
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    /// Missing from files recorded before settings were logged.
+    #[serde(default)]
+    pub value: f32,
+}
+
+impl InputRow {
+    pub fn new(frame: u64, time_s: f32, ids: &[usize], action: Intervention) -> Self {
+        let (vx, vy) = match action {
+            Intervention::Velocity(v) => (v.x, v.y),
+            _ => (0.0, 0.0),
+        };
+        let [r, g, b] = match action {
+            Intervention::Color(c) => c,
+            _ => [0.0; 3],
+        };
+        let value = action.setting().unwrap_or(0.0);
+
+        Self {
+            frame,
+            time_s,
+            action: action.name().to_string(),
+            ids: ids
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(" "),
+            vx,
+            vy,
+            r,
+            g,
+            b,
+            value,
+        }
+    }
+
+    pub fn intervention(&self) -> anyhow::Result<Intervention> {
+        Ok(match self.action.as_str() {
+            "velocity" => Intervention::Velocity(Vec2::new(self.vx, self.vy)),
+            "color" => Intervention::Color([self.r, self.g, self.b]),
+            "freeze" => Intervention::Freeze,
+            "restitution" => Intervention::Restitution(self.value),
+            "friction" => Intervention::Friction(self.value),
+            "gravity" => Intervention::Gravity(self.value),
+            "cell_size" => Intervention::CellSize(self.value),
+            other => bail!("unknown action '{other}'"),
+        })
+    }
+
+    pub fn ids(&self) -> anyhow::Result<Vec<usize>> {
+        self.ids
+            .split_whitespace()
+            .map(|id| {
+                id.parse()
+                    .with_context(|| format!("bad particle id '{id}'"))
+            })
+            .collect()
+    }
+}
+
+/// Inputs recorded in an earlier session, handed back frame by frame so they land on the same
+/// steps again. Replays are only exact when the original run used a fixed physics rate.
+pub struct InputReplay {
+    inputs: Vec<(u64, Vec<usize>, Intervention)>,
+    next: usize,
+}
+
+impl InputReplay {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let rows: Vec<InputRow> = csv::Reader::from_path(path)
+            .with_context(|| format!("reading {}", path.display()))?
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("parsing {}", path.display()))?;
+
+        Self::from_rows(&rows).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    pub fn from_rows(rows: &[InputRow]) -> anyhow::Result<Self> {
+        let mut inputs = rows
+            .iter()
+            .enumerate()
+            .map(|(n, row)| {
+                row.ids()
+                    .and_then(|ids| Ok((row.frame, ids, row.intervention()?)))
+                    .with_context(|| format!("row {}", n + 1))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        inputs.sort_by_key(|&(frame, _, _)| frame);
+
+        Ok(Self { inputs, next: 0 })
+    }
+
+    /// Inputs recorded at `frame`, in file order. Inputs for earlier frames that were never
+    /// taken are skipped.
+    pub fn take(&mut self, frame: u64) -> Vec<(Vec<usize>, Intervention)> {
+        let mut due = Vec::new();
+
+        while let Some((f, ids, action)) = self.inputs.get(self.next) {
+            if *f > frame {
+                break;
+            }
+
+            if *f == frame {
+                due.push((ids.clone(), *action));
+            }
+
+            self.next += 1;
+        }
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_round_trip() {
+        for action in [
+            Intervention::Velocity(Vec2::new(3.0, -4.0)),
+            Intervention::Color([0.25, 0.5, 1.0]),
+            Intervention::Freeze,
+            Intervention::Restitution(0.75),
+            Intervention::Friction(0.1),
+            Intervention::Gravity(300.0),
+            Intervention::CellSize(24.0),
+        ] {
+            let row = InputRow::new(7, 0.5, &[4, 1, 9], action);
+
+            assert_eq!(row.ids().unwrap(), vec![4, 1, 9]);
+            assert_eq!(row.intervention().unwrap(), action);
+        }
+    }
+
+    #[test]
+    fn replay_hands_out_inputs_by_frame() {
+        let rows = [
+            InputRow::new(5, 0.0, &[2], Intervention::Freeze),
+            InputRow::new(3, 0.0, &[0, 1], Intervention::Freeze),
+            InputRow::new(5, 0.0, &[3], Intervention::Velocity(Vec2::X)),
+        ];
+        let mut replay = InputReplay::from_rows(&rows).unwrap();
+
+        assert_eq!(replay.take(3), vec![(vec![0, 1], Intervention::Freeze)]);
+        assert!(replay.take(4).is_empty());
+        assert_eq!(
+            replay.take(5),
+            vec![
+                (vec![2], Intervention::Freeze),
+                (vec![3], Intervention::Velocity(Vec2::X))
+            ]
+        );
+        assert!(replay.take(6).is_empty());
+    }
+
+    #[test]
+    fn malformed_rows_are_rejected() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+
+        const ACTIONS: [&str; 6] = ["velocity", "color", "freeze", "", "Freeze", "teleport"];
+        const IDS: [&str; 7] = ["", "3", "0 1 2", "  7  ", "-1", "1.5", "4 x"];
+
+        let mut rng = StdRng::seed_from_u64(9);
+
+        for _ in 0..500 {
+            let action = ACTIONS[rng.random_range(0..ACTIONS.len())];
+            let ids = IDS[rng.random_range(0..IDS.len())];
+            let mut row = InputRow::new(rng.random_range(0..10), 0.0, &[], Intervention::Freeze);
+
+            row.action = action.to_string();
+            row.ids = ids.to_string();
+
+            let valid = ACTIONS[..3].contains(&action) && !["-1", "1.5", "4 x"].contains(&ids);
+
+            assert_eq!(
+                InputReplay::from_rows(&[row]).is_ok(),
+                valid,
+                "{action:?} {ids:?}"
+            );
+        }
+
+        // Garbage files are errors, never panics, whether they fail in the CSV reader or later.
+        const ALPHABET: &[u8] = b"0123456789.,- \nabcvxyz\"";
+
+        for _ in 0..200 {
+            let bytes = (0..rng.random_range(0..64))
+                .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())])
+                .collect::<Vec<_>>();
+            let data = [b"frame,time_s,action,ids,vx,vy,r,g,b\n".as_slice(), &bytes].concat();
+
+            if let Ok(rows) = csv::Reader::from_reader(&data[..])
+                .deserialize::<InputRow>()
+                .collect::<Result<Vec<_>, _>>()
+            {
+                let _ = InputReplay::from_rows(&rows);
+            }
+        }
+    }
+}
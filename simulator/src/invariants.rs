@@ -0,0 +1,98 @@
+use engine::{
+    Bounds,
+    boundary::{self, BoundaryCondition},
+    particle::Particle,
+};
+
+use crate::thermo;
+
+/// Slack allowed before a particle counts as overlapping or outside the container.
+pub const TOLERANCE: f32 = 1e-2;
+
+/// Counts of particles (or pairs) breaking the solver's invariants at one instant.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Violations {
+    pub overlaps: usize,
+    pub out_of_bounds: usize,
+    pub non_finite: usize,
+}
+
+impl Violations {
+    pub fn check(
+        particles: &[Particle],
+        boundary: &dyn BoundaryCondition,
+        bounds: &Bounds,
+    ) -> Self {
+        let mut violations = Self::default();
+        let period = boundary.period(bounds);
+
+        for (i, p) in particles.iter().enumerate() {
+            if !(p.position.is_finite() && p.velocity.is_finite()) {
+                violations.non_finite += 1;
+                continue;
+            }
+
+            let shrunk = Particle {
+                radius: p.radius - TOLERANCE,
+                ..*p
+            };
+
+            if !boundary.contains(&shrunk, bounds) {
+                violations.out_of_bounds += 1;
+            }
+
+            violations.overlaps += particles[i + 1..]
+                .iter()
+                .filter(|q| {
+                    let d = match period {
+                        Some(period) => boundary::minimum_image(q.position - p.position, period),
+                        None => q.position - p.position,
+                    };
+
+                    d.length() < p.radius + q.radius - TOLERANCE
+                })
+                .count();
+        }
+
+        if !thermo::kinetic_energy(particles).is_finite() {
+            violations.non_finite = violations.non_finite.max(1);
+        }
+
+        violations
+    }
+
+    pub fn total(&self) -> usize {
+        self.overlaps + self.out_of_bounds + self.non_finite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::boundary::{Periodic, ReflectiveBox};
+    use glam::Vec2;
+
+    use super::*;
+
+    const BOUNDS: Bounds = Bounds {
+        width: 200.0,
+        height: 100.0,
+    };
+
+    fn at(x: f32) -> Particle {
+        Particle::new(Vec2::new(x, 0.0), Vec2::ZERO, 5.0, 1.0, [1.0; 3])
+    }
+
+    #[test]
+    fn periodic_overlaps_are_found_across_the_seam() {
+        let particles = [at(-97.0), at(97.0)];
+
+        assert_eq!(
+            Violations::check(&particles, &Periodic, &BOUNDS).overlaps,
+            1
+        );
+        assert_eq!(
+            Violations::check(&particles, &ReflectiveBox, &BOUNDS).overlaps,
+            0
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use engine::particle::Particle;
+use glam::Vec2;
+
+/// Subtrees smaller than this are built on the current thread.
+const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Static 2D k-d tree over particle positions for offline analysis (pair correlation, cluster
+/// detection, density estimates), where queries span very different radii and the fixed cell size
+/// of `SpatialGrid` is a poor fit. It also backs [`crate::detector::KdTreeBroadPhase`].
+///
+/// The tree is implicit: every slice stores its median at the middle, split on x at even depths
+/// and on y at odd ones.
+pub struct KdTree {
+    points: Vec<(Vec2, usize)>,
+}
+
+impl KdTree {
+    pub fn new(particles: &[Particle]) -> Self {
+        Self::from_points(particles.iter().map(|p| p.position))
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec2>) -> Self {
+        let mut points = points
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (p, i))
+            .collect::<Vec<_>>();
+
+        build(&mut points, 0);
+
+        Self { points }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Index of and distance to the point closest to `pos`.
+    pub fn nearest(&self, pos: Vec2) -> Option<(usize, f32)> {
+        self.k_nearest(pos, 1).pop()
+    }
+
+    /// The `k` points closest to `pos` as `(index, distance)`, nearest first.
+    pub fn k_nearest(&self, pos: Vec2, k: usize) -> Vec<(usize, f32)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+
+        if k > 0 {
+            k_nearest(&self.points, 0, pos, k, &mut heap);
+        }
+
+        let mut out = heap
+            .into_iter()
+            .map(|c| (c.index, c.dist_sq.sqrt()))
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| a.1.total_cmp(&b.1));
+        out
+    }
+
+    /// Indices of every point within `r` of `pos`, in no particular order.
+    pub fn within(&self, pos: Vec2, r: f32) -> Vec<usize> {
+        let mut out = Vec::new();
+        within(&self.points, 0, pos, r, &mut out);
+        out
+    }
+}
+
+fn build(points: &mut [(Vec2, usize)], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+
+    let axis = depth % 2;
+    let mid = points.len() / 2;
+    let parallel = points.len() >= PARALLEL_THRESHOLD;
+
+    points.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+
+    let (left, right) = points.split_at_mut(mid);
+    let right = &mut right[1..];
+
+    if parallel {
+        rayon::join(|| build(left, depth + 1), || build(right, depth + 1));
+    } else {
+        build(left, depth + 1);
+        build(right, depth + 1);
+    }
+}
+
+/// Heap entry ordered by distance, so the heap's top is the worst of the current candidates.
+struct Candidate {
+    dist_sq: f32,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.dist_sq.total_cmp(&other.dist_sq)
+    }
+}
+
+fn k_nearest(
+    points: &[(Vec2, usize)],
+    depth: usize,
+    pos: Vec2,
+    k: usize,
+    heap: &mut BinaryHeap<Candidate>,
+) {
+    if points.is_empty() {
+        return;
+    }
+
+    let mid = points.len() / 2;
+    let (p, index) = points[mid];
+    let dist_sq = p.distance_squared(pos);
+
+    if heap.len() < k {
+        heap.push(Candidate { dist_sq, index });
+    } else if heap.peek().is_some_and(|worst| dist_sq < worst.dist_sq) {
+        heap.pop();
+        heap.push(Candidate { dist_sq, index });
+    }
+
+    let axis = depth % 2;
+    let delta = pos[axis] - p[axis];
+    let (near, far) = if delta < 0.0 {
+        (&points[..mid], &points[mid + 1..])
+    } else {
+        (&points[mid + 1..], &points[..mid])
+    };
+
+    k_nearest(near, depth + 1, pos, k, heap);
+
+    if heap.len() < k
+        || heap
+            .peek()
+            .is_some_and(|worst| delta * delta < worst.dist_sq)
+    {
+        k_nearest(far, depth + 1, pos, k, heap);
+    }
+}
+
+fn within(points: &[(Vec2, usize)], depth: usize, pos: Vec2, r: f32, out: &mut Vec<usize>) {
+    if points.is_empty() {
+        return;
+    }
+
+    let mid = points.len() / 2;
+    let (p, index) = points[mid];
+
+    if p.distance_squared(pos) <= r * r {
+        out.push(index);
+    }
+
+    let axis = depth % 2;
+    let delta = pos[axis] - p[axis];
+
+    if delta - r <= 0.0 {
+        within(&points[..mid], depth + 1, pos, r, out);
+    }
+
+    if delta + r >= 0.0 {
+        within(&points[mid + 1..], depth + 1, pos, r, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    fn random_points(n: usize, seed: u64) -> Vec<Vec2> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        (0..n)
+            .map(|_| {
+                Vec2::new(
+                    rng.random_range(-100.0..100.0),
+                    rng.random_range(-100.0..100.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let points = random_points(PARALLEL_THRESHOLD * 2 + 3, 7);
+        let tree = KdTree::from_points(points.iter().copied());
+
+        for &pos in &random_points(20, 8) {
+            let mut expected = (0..points.len())
+                .filter(|&i| points[i].distance(pos) <= 12.0)
+                .collect::<Vec<_>>();
+            let mut found = tree.within(pos, 12.0);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+
+            let mut by_distance = (0..points.len()).collect::<Vec<_>>();
+            by_distance
+                .sort_by(|&a, &b| points[a].distance(pos).total_cmp(&points[b].distance(pos)));
+            let nearest = tree.k_nearest(pos, 5);
+            assert_eq!(
+                nearest.iter().map(|n| n.0).collect::<Vec<_>>(),
+                by_distance[..5]
+            );
+        }
+    }
+
+    #[test]
+    fn empty_tree_has_no_neighbors() {
+        let tree = KdTree::from_points([]);
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.nearest(Vec2::ZERO), None);
+        assert!(tree.within(Vec2::ZERO, 10.0).is_empty());
+    }
+}
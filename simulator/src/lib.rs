@@ -0,0 +1,19 @@
+pub mod alloc;
+pub mod chain;
+pub mod decomposed;
+pub mod detector;
+pub mod dump;
+pub mod event;
+pub mod event_index;
+pub mod format;
+pub mod inputs;
+pub mod invariants;
+pub mod kdtree;
+pub mod miscs;
+pub mod observer;
+pub mod pair_cache;
+pub mod scenario;
+pub mod solver;
+pub mod spatial;
+pub mod thermo;
+pub mod workload;
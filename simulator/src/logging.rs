@@ -0,0 +1,130 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::cli::Cli;
+
+/// Sets up `env_logger` from the command line: `--log-filter` (or `RUST_LOG`) picks per-module
+/// levels, and `--log-file` sends everything to rotating files instead of stderr.
+pub fn init(cli: &Cli) -> anyhow::Result<()> {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    if let Some(filter) = &cli.log_filter {
+        builder.parse_filters(filter);
+    }
+
+    if let Some(path) = &cli.log_file {
+        let file = RotatingFile::open(path, cli.log_max_size * 1024 * 1024, cli.log_keep)?;
+
+        builder
+            .target(env_logger::Target::Pipe(Box::new(file)))
+            .write_style(env_logger::WriteStyle::Never);
+    }
+
+    builder.init();
+
+    Ok(())
+}
+
+/// Appends to `path` until it grows past `max_bytes`, then shifts it to `path.1` (and older
+/// files to `path.2` ...) keeping at most `keep` old files.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.numbered(self.keep));
+
+            for n in (1..self.keep).rev() {
+                let from = self.numbered(n);
+
+                if from.exists() {
+                    fs::rename(from, self.numbered(n + 1))?;
+                }
+            }
+
+            fs::rename(&self.path, self.numbered(1))?;
+            self.file = File::create(&self.path)?;
+        }
+
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_drops_oldest() {
+        let dir = std::env::temp_dir().join(format!("tccd-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("run.log");
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+
+        for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        file.flush().unwrap();
+
+        let read = |p: PathBuf| fs::read_to_string(p).unwrap();
+
+        assert_eq!(read(path.clone()), "dddddddd\n");
+        assert_eq!(read(file.numbered(1)), "cccccccc\n");
+        assert_eq!(read(file.numbered(2)), "bbbbbbbb\n");
+        assert!(!file.numbered(3).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,87 +1,1012 @@
 mod cli;
-mod detector;
-mod miscs;
-mod solver;
-mod spatial;
+mod logging;
+mod soak;
+
+use std::{
+    borrow::Cow,
+    iter,
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use engine::{Bounds, Simulation, SimulationConfig, particle::Particle};
+use engine::{
+    Bounds, KeyCode, Simulation, SimulationConfig, StepResult,
+    camera::CameraPath,
+    context::Context,
+    egui,
+    grid::Grid,
+    line::Line,
+    particle::Particle,
+    shape::{Outline, Renderable, Shape},
+    split::SplitScreen,
+    threaded::Threaded,
+};
 use glam::Vec2;
 use rand::{Rng, SeedableRng, rngs::StdRng};
 
-use crate::{cli::Cli, solver::Solver};
+use simulator::{
+    alloc::{self, Section},
+    dump::StateDump,
+    event::{Event, Intervention, InterventionEvent},
+    inputs::InputReplay,
+    invariants::Violations,
+    miscs::{DetectionType, FrameTimes, Recorder},
+    scenario,
+    solver::Solver,
+    thermo::{self, Thermostat},
+};
+
+use crate::cli::Cli;
+
+#[cfg(feature = "alloc-audit")]
+#[global_allocator]
+static ALLOCATOR: alloc::CountingAllocator = alloc::CountingAllocator;
 
 const SPEED: f32 = 500.0;
+const MAX_SPAWN_ATTEMPTS: usize = 100;
+const CONTACT_NORMAL_LENGTH: f32 = 15.0;
+const GRID_COLOR: [f32; 3] = [0.15, 0.15, 0.2];
+const SWEEP_CELL_COLOR: [f32; 3] = [0.2, 0.8, 0.4];
+const VELOCITY_ARROW_SCALE: f32 = 0.05;
+const ARROW_HEAD_LENGTH: f32 = 6.0;
+const ARROW_HEAD_ANGLE: f32 = 0.45;
+const ARROW_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
+const WALL_COLOR: [f32; 3] = [0.45, 0.45, 0.5];
+const HIGHLIGHT_OUTLINE: f32 = 0.35;
+const HIGHLIGHT_LAYER: i32 = 1;
+const GHOST_COLOR: [f32; 3] = [1.0, 0.35, 0.2];
+const GHOST_OUTLINE: f32 = 0.2;
+/// How far the lockstep detector must put a particle from the primary's result to show a ghost.
+const GHOST_DISTANCE: f32 = 1e-3;
+/// Grid and sweep cells sit under the particles.
+const GRID_LAYER: i32 = -1;
+/// How far outside a particle's rim a click still picks it, in world units.
+const PICK_DISTANCE: f32 = 15.0;
+const MAX_GRAVITY: f32 = 2000.0;
+const MAX_FRICTION: f32 = 2.0;
+const CELL_SIZE_RANGE: std::ops::RangeInclusive<f32> = 2.0..=500.0;
+
+/// Solver parameters edited in the control panel, handed to the solvers at the start of the next
+/// step.
+#[derive(Clone, Copy)]
+struct Controls {
+    restitution: f32,
+    friction: f32,
+    /// Downward, in world units per second squared.
+    gravity: f32,
+    cell_size: f32,
+}
 
 struct TCcdSim {
     particles: Vec<Particle>,
     solver: Solver,
+    last_dt: f32,
+    show_debug_lines: bool,
+    show_grid: bool,
+    show_velocities: bool,
+    selected: Option<usize>,
+    group: Vec<usize>,
+    group_velocity: Vec2,
+    group_color: [f32; 3],
+    half_extents: Vec2,
+    from_scenario: bool,
 
-    _seed: Option<u64>,
+    fps: u64,
+    /// Frames after which the run ends (`--frames`).
+    max_frames: Option<u64>,
+    warmup_frames: u64,
+    warmup_temperature: Option<f32>,
+    thermostat: Option<Thermostat>,
+    replay: Option<InputReplay>,
+    /// Second detector run on a copy of the pre-step state every frame (`--lockstep`).
+    shadow: Option<Solver>,
+    /// Where the shadow detector put the particles it disagreed on last frame.
+    ghosts: Vec<Particle>,
+    /// Contact points since the engine last took them for the heatmap.
+    impacts: Vec<Vec2>,
+    controls: Controls,
+    /// Tells the control panels of a grid's cells apart.
+    seed: u64,
 }
 
 impl Simulation for TCcdSim {
-    fn init(&mut self, bounds: Bounds) {
+    fn init(&mut self, ctx: &mut Context, bounds: Bounds) {
         let (hw, hh) = bounds.half_extents();
-        let mut rng = if let Some(seed) = self._seed {
-            StdRng::seed_from_u64(seed)
+        self.half_extents = Vec2::new(hw, hh);
+
+        let mut rng = StdRng::seed_from_u64(ctx.seed());
+
+        self.seed = ctx.seed();
+        self.apply_controls();
+
+        if self.from_scenario {
+            for p in &mut self.particles {
+                p.color = [rng.random(), rng.random(), rng.random()];
+            }
         } else {
-            StdRng::from_os_rng()
-        };
+            self.spawn(&mut rng, &bounds);
+        }
 
-        self.particles.iter_mut().for_each(|p| {
-            p.position = Vec2::new(
-                rng.random_range(-0.9 * hw..0.9 * hw),
-                rng.random_range(-0.9 * hh..0.9 * hh),
-            );
-            p.velocity = Vec2::new(
-                rng.random_range(-SPEED..SPEED),
-                rng.random_range(-SPEED..SPEED),
-            );
-            p.radius = rng.random_range(3.0..7.0);
-            p.mass = std::f32::consts::PI * p.radius * p.radius;
-            p.color = [rng.random(), rng.random(), rng.random()];
-        });
+        self.warmup(&bounds);
 
         self.solver.recorder.frame += 1;
+        self.solver.recorder.enabled = ctx.is_recording();
         self.solver
             .recorder
             .write_particles_snapshot(&self.particles);
+        self.solver.recorder.write_frame(
+            thermo::kinetic_energy(&self.particles),
+            thermo::momentum(&self.particles),
+            1.0,
+            FrameTimes::default(),
+        );
+
+        if let Some(shadow) = &mut self.shadow {
+            shadow.recorder.frame = self.solver.recorder.frame;
+            shadow.recorder.enabled = ctx.is_recording();
+            shadow.recorder.write_particles_snapshot(&self.particles);
+            shadow.recorder.write_frame(
+                thermo::kinetic_energy(&self.particles),
+                thermo::momentum(&self.particles),
+                1.0,
+                FrameTimes::default(),
+            );
+        }
     }
 
-    fn step(&mut self, dt: f32, bounds: engine::Bounds) {
+    fn step(&mut self, ctx: &mut Context, dt: f32, bounds: engine::Bounds) -> StepResult {
+        let _alloc = alloc::scope(Section::Solver);
+
+        self.half_extents = bounds.half_extents().into();
+        self.solver.recorder.enabled = ctx.is_recording();
+
+        // Recorded frames count from 1 at the initial state, so the last one written is the
+        // engine's frame index plus one.
+        let frame = ctx.frame();
+        self.solver.recorder.frame = frame.frame_index + 1;
+        self.solver.recorder.time_s = frame.elapsed.as_secs_f32();
+
+        self.replay_inputs();
+        self.apply_controls();
+        let shadow_state = self.step_shadow(ctx, dt, &bounds);
+
+        let start = Instant::now();
         self.solver.solve(&mut self.particles, &bounds, dt);
+        let solve_ms = start.elapsed().as_secs_f32() * 1000.0;
+
+        if let Some(state) = shadow_state {
+            self.ghosts = ghosts(&state, &self.particles);
+        }
+
+        self.last_dt = dt;
+        self.impacts
+            .extend(self.solver.contacts.iter().map(|&(point, _)| point));
+
+        let scale = self
+            .thermostat
+            .as_mut()
+            .map_or(1.0, |t| t.apply(&mut self.particles, dt));
 
         self.solver.recorder.frame += 1;
         self.solver.recorder.time_s += dt;
+
+        let record_start = Instant::now();
         self.solver
             .recorder
             .write_particles_snapshot(&self.particles);
+        let record_ms = record_start.elapsed().as_secs_f32() * 1000.0;
+
+        self.solver.recorder.write_frame(
+            thermo::kinetic_energy(&self.particles),
+            thermo::momentum(&self.particles),
+            scale,
+            FrameTimes {
+                dt,
+                wall_ms: ctx.frame_time().as_secs_f32() * 1000.0,
+                solve_ms,
+                record_ms,
+                render_ms: ctx.render_time().as_secs_f32() * 1000.0,
+                allocs: alloc::take(),
+            },
+        );
         self.solver.recorder.flush();
+
+        match self.max_frames {
+            Some(frames) if self.solver.recorder.frame >= frames => {
+                log::info!("Simulated {frames} frames, exiting");
+                StepResult::Exit
+            }
+            _ => StepResult::Continue,
+        }
     }
 
     fn particles(&self) -> &[Particle] {
         &self.particles
     }
+
+    fn overlay_instances(&self) -> Cow<'_, [Particle]> {
+        Cow::Borrowed(&self.ghosts)
+    }
+
+    fn ui(&mut self, ctx: &egui::Context) {
+        let tag = self.solver.detection_type().tag();
+
+        egui::Window::new(format!("Controls ({tag}, seed {})", self.seed))
+            .default_open(false)
+            .show(ctx, |ui| {
+                let mut controls = self.controls;
+
+                ui.add(egui::Slider::new(&mut controls.restitution, 0.0..=1.0).text("Restitution"));
+                ui.add(
+                    egui::Slider::new(&mut controls.friction, 0.0..=MAX_FRICTION).text("Friction"),
+                );
+                ui.add(egui::Slider::new(&mut controls.gravity, 0.0..=MAX_GRAVITY).text("Gravity"));
+                ui.add(
+                    egui::Slider::new(&mut controls.cell_size, CELL_SIZE_RANGE)
+                        .logarithmic(true)
+                        .text("Cell size"),
+                );
+
+                self.log_controls(controls);
+            });
+    }
+
+    fn debug_ui(&mut self, ui: &mut egui::Ui) {
+        let kinetic_energy = thermo::kinetic_energy(&self.particles);
+        let times = self.solver.phase_times();
+
+        ui.label(format!("Particles: {}", self.particles.len()));
+        ui.label(format!("Step dt: {:.3} ms", self.last_dt * 1000.0));
+        ui.label(format!("Solver iterations: {}", self.solver.iterations));
+        ui.label(format!(
+            "Broad / narrow phase: {:.3} / {:.3} ms",
+            times.broad.as_secs_f64() * 1000.0,
+            times.narrow.as_secs_f64() * 1000.0
+        ));
+
+        if self.shadow.is_some() {
+            ui.label(format!(
+                "Lockstep divergence: {} particles",
+                self.ghosts.len()
+            ));
+        }
+
+        if let Some(rate) = self.solver.pair_cache_hit_rate() {
+            ui.label(format!("Pair cache hits: {:.1}%", rate * 100.0));
+        }
+
+        ui.label(format!("Kinetic energy: {kinetic_energy:.3e}"));
+        ui.checkbox(
+            &mut self.show_debug_lines,
+            "Swept paths and contact normals",
+        );
+        ui.checkbox(&mut self.show_velocities, "Velocity arrows (V)");
+
+        if !self.group.is_empty() {
+            ui.separator();
+            self.group_ui(ui);
+        }
+    }
+
+    fn take_impacts(&mut self) -> Vec<Vec2> {
+        std::mem::take(&mut self.impacts)
+    }
+
+    fn hud(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Frame {}", self.solver.recorder.frame),
+            format!("KE {:.3e}", thermo::kinetic_energy(&self.particles)),
+        ];
+
+        lines.extend(self.selected_info());
+        lines
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::KeyG => self.show_grid = !self.show_grid,
+            KeyCode::KeyV => self.show_velocities = !self.show_velocities,
+            KeyCode::KeyD => StateDump::capture(
+                self.solver.recorder.frame,
+                self.solver.recorder.time_s,
+                &self.particles,
+                self.solver.grid(),
+                self.solver.iterations,
+            )
+            .save_in_background(),
+            _ => {}
+        }
+    }
+
+    /// Picks the particle nearest to the click, as long as the click is close to its rim.
+    fn clicked(&mut self, world: Vec2) {
+        self.selected = self
+            .particles
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.position.distance(world) - p.radius))
+            .filter(|&(_, gap)| gap <= PICK_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i);
+        self.highlight();
+
+        if let Some(info) = self.selected_info() {
+            log::info!("Picked {info}");
+        }
+    }
+
+    fn selected_rect(&mut self, min: Vec2, max: Vec2) {
+        self.group = self
+            .particles
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.position.cmpge(min).all() && p.position.cmple(max).all())
+            .map(|(i, _)| i)
+            .collect();
+        self.highlight();
+    }
+
+    fn focus(&self) -> Option<Vec2> {
+        self.selected
+            .and_then(|i| self.particles.get(i))
+            .map(|p| p.position)
+    }
+
+    /// Under gravity, particles at rest only stay put while something holds them.
+    fn quiescent(&self) -> bool {
+        let held = |(i, p): (usize, &Particle)| !p.mass.is_finite() || self.solver.is_resting(i);
+
+        self.particles.iter().all(|p| p.velocity == Vec2::ZERO)
+            && (self.solver.gravity() == Vec2::ZERO || self.particles.iter().enumerate().all(held))
+    }
+
+    fn on_exit(&mut self) {
+        self.solver.finish();
+
+        if let Some(shadow) = &mut self.shadow {
+            shadow.finish();
+        }
+    }
+
+    fn scenery(&self) -> Vec<Shape> {
+        let bounds = Bounds {
+            width: 2.0 * self.half_extents.x,
+            height: 2.0 * self.half_extents.y,
+        };
+        let outline = self.solver.boundary().outline(&bounds);
+        let mut shapes = Vec::new();
+
+        Outline {
+            points: &outline,
+            color: WALL_COLOR,
+        }
+        .shapes(&mut shapes);
+        shapes
+    }
+
+    fn debug_lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+
+        if self.show_grid {
+            self.grid_lines(&mut lines);
+        }
+
+        if self.show_velocities {
+            self.velocity_arrows(&mut lines);
+        }
+
+        if !self.show_debug_lines {
+            return lines;
+        }
+
+        let paths = self.particles.iter().map(|p| {
+            Line::new(
+                p.position,
+                p.position + p.velocity * self.last_dt,
+                p.color.map(|c| 0.5 * c),
+            )
+        });
+        let normals = self.solver.contacts.iter().map(|&(point, normal)| {
+            Line::new(
+                point,
+                point + normal * CONTACT_NORMAL_LENGTH,
+                [1.0, 0.9, 0.2],
+            )
+        });
+
+        lines.extend(paths.chain(normals));
+        lines
+    }
 }
 
-fn main() -> anyhow::Result<()> {
-    env_logger::init();
+impl TCcdSim {
+    /// Checks the state the run ended in and logs it next to how far it got.
+    fn report_final_state(&self, name: &str) {
+        let bounds = Bounds {
+            width: 2.0 * self.half_extents.x,
+            height: 2.0 * self.half_extents.y,
+        };
+        let violations = Violations::check(&self.particles, self.solver.boundary(), &bounds);
 
-    let cli = Cli::parse();
+        log::info!(
+            "{name}: {} frames, {:.3} s simulated, final kinetic energy {:.3e}, {violations:?}",
+            self.solver.recorder.frame,
+            self.solver.recorder.time_s,
+            thermo::kinetic_energy(&self.particles),
+        );
+    }
 
-    engine::run_with(
-        TCcdSim {
-            particles: vec![Particle::default(); cli.particle_count as usize],
-            solver: Solver::new(cli.cell_size, cli.record, cli.method, cli.particle_count),
+    /// Runs `method` in lockstep next to the primary detector, recording into files tagged
+    /// `{method}_lockstep`.
+    fn with_shadow(mut self, cli: &Cli, method: DetectionType) -> Self {
+        let count = self.particles.len() as u64;
+        let tag = format!("{}_lockstep", method.tag());
+
+        self.shadow = Some(
+            Solver::new(
+                cli.cell_size,
+                None,
+                method,
+                cli.boundary,
+                cli.polygon_sides,
+                count,
+            )
+            .with_recorder(Recorder::tagged(cli.record, tag, count))
+            .with_resting_threshold(cli.resting_threshold)
+            .with_chain_trace(cli.trace_chains)
+            .with_pair_cache(cli.pair_cache),
+        );
+        self
+    }
+
+    /// Builds a simulation detecting with `method` that records into files tagged `tag`.
+    fn from_cli(cli: &Cli, method: DetectionType, tag: String) -> anyhow::Result<Self> {
+        let scenario = cli.scenario.as_deref().map(scenario::load).transpose()?;
+        let from_scenario = scenario.is_some();
+        let particles =
+            scenario.unwrap_or_else(|| vec![Particle::default(); cli.particle_count as usize]);
+        let particle_count = particles.len() as u64;
+
+        Ok(Self {
+            particles,
+            solver: Solver::new(
+                cli.cell_size,
+                None,
+                method,
+                cli.boundary,
+                cli.polygon_sides,
+                particle_count,
+            )
+            .with_recorder(Recorder::tagged(cli.record, tag, particle_count))
+            .with_resting_threshold(cli.resting_threshold)
+            .with_chain_trace(cli.trace_chains)
+            .with_pair_cache(cli.pair_cache),
+            last_dt: 0.0,
+            show_debug_lines: false,
+            show_grid: false,
+            show_velocities: false,
+            selected: None,
+            group: Vec::new(),
+            group_velocity: Vec2::ZERO,
+            group_color: [1.0; 3],
+            half_extents: Vec2::ZERO,
+            from_scenario,
 
-            _seed: cli.seed,
-        },
-        SimulationConfig {
-            fullscreen: cli.fullscreen,
             fps: cli.fps,
-        },
-    )?;
+            max_frames: cli.frames,
+            warmup_frames: cli.warmup_frames,
+            warmup_temperature: cli.warmup_temperature,
+            thermostat: cli
+                .thermostat
+                .map(|kind| Thermostat::new(kind, cli.thermostat_temperature, cli.thermostat_tau)),
+            shadow: None,
+            ghosts: Vec::new(),
+            impacts: Vec::new(),
+            controls: Controls {
+                restitution: cli.restitution,
+                friction: cli.friction,
+                gravity: cli.gravity,
+                cell_size: cli.cell_size,
+            },
+            seed: 0,
+            replay: cli
+                .replay_inputs
+                .as_deref()
+                .map(InputReplay::load)
+                .transpose()?,
+        })
+    }
+
+    /// Hands the control panel's values to the primary and lockstep solvers.
+    fn apply_controls(&mut self) {
+        let controls = &self.controls;
+
+        for solver in iter::once(&mut self.solver).chain(&mut self.shadow) {
+            if solver.grid().cell_size() != controls.cell_size {
+                solver.set_cell_size(controls.cell_size);
+            }
+
+            solver.set_restitution(controls.restitution);
+            solver.set_friction(controls.friction);
+            solver.set_gravity(Vec2::new(0.0, -controls.gravity));
+        }
+    }
+
+    /// Bulk edits for the particles picked with a shift-drag rectangle. Every change is written
+    /// to the event log so recorded runs show where the viewer interfered.
+    fn group_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Selected group: {}", self.group.len()));
+
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.group_velocity.x).prefix("vx "));
+            ui.add(egui::DragValue::new(&mut self.group_velocity.y).prefix("vy "));
+
+            if ui.button("Set velocity").clicked() {
+                action = Some(Intervention::Velocity(self.group_velocity));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgb(&mut self.group_color);
+
+            if ui.button("Apply color").clicked() {
+                action = Some(Intervention::Color(self.group_color));
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Freeze").clicked() {
+                action = Some(Intervention::Freeze);
+            }
+
+            if ui.button("Clear selection").clicked() {
+                self.group.clear();
+                self.highlight();
+            }
+        });
 
-    Ok(())
+        if let Some(action) = action {
+            self.intervene(&self.group.clone(), action);
+        }
+    }
+
+    /// Logs every slider that moved as an intervention, so replays repeat it on the same frame.
+    fn log_controls(&mut self, controls: Controls) {
+        let old = self.controls;
+
+        for (changed, action) in [
+            (
+                controls.restitution != old.restitution,
+                Intervention::Restitution(controls.restitution),
+            ),
+            (
+                controls.friction != old.friction,
+                Intervention::Friction(controls.friction),
+            ),
+            (
+                controls.gravity != old.gravity,
+                Intervention::Gravity(controls.gravity),
+            ),
+            (
+                controls.cell_size != old.cell_size,
+                Intervention::CellSize(controls.cell_size),
+            ),
+        ] {
+            if changed {
+                self.intervene(&[], action);
+            }
+        }
+    }
+
+    fn intervene(&mut self, ids: &[usize], action: Intervention) {
+        self.solver.recorder.write_input(ids, action);
+
+        match action {
+            Intervention::Restitution(v) => self.controls.restitution = v,
+            Intervention::Friction(v) => self.controls.friction = v,
+            Intervention::Gravity(v) => self.controls.gravity = v,
+            Intervention::CellSize(v) => self.controls.cell_size = v,
+            _ => {}
+        }
+
+        for &i in ids {
+            let p = &mut self.particles[i];
+
+            match action {
+                Intervention::Velocity(v) => p.velocity = v,
+                Intervention::Color(c) => p.color = c,
+                Intervention::Freeze => {
+                    p.velocity = Vec2::ZERO;
+                    p.mass = f32::INFINITY;
+                }
+                Intervention::Restitution(_)
+                | Intervention::Friction(_)
+                | Intervention::Gravity(_)
+                | Intervention::CellSize(_) => {}
+            }
+
+            self.solver
+                .recorder
+                .write_event(&Event::Intervention(InterventionEvent { i, action }));
+        }
+    }
+
+    /// Solves a copy of the current state with the shadow detector and records it under the same
+    /// frame number as the primary, so both datasets start every frame from identical input.
+    fn step_shadow(&mut self, ctx: &Context, dt: f32, bounds: &Bounds) -> Option<Vec<Particle>> {
+        let shadow = self.shadow.as_mut()?;
+
+        let mut state = self.particles.clone();
+
+        shadow.recorder.enabled = ctx.is_recording();
+        shadow.recorder.frame = self.solver.recorder.frame;
+        shadow.recorder.time_s = self.solver.recorder.time_s;
+
+        let start = Instant::now();
+        shadow.solve(&mut state, bounds, dt);
+        let solve_ms = start.elapsed().as_secs_f32() * 1000.0;
+
+        shadow.recorder.frame += 1;
+        shadow.recorder.time_s += dt;
+        shadow.recorder.write_particles_snapshot(&state);
+        shadow.recorder.write_frame(
+            thermo::kinetic_energy(&state),
+            thermo::momentum(&state),
+            1.0,
+            FrameTimes {
+                dt,
+                solve_ms,
+                ..Default::default()
+            },
+        );
+        shadow.recorder.flush();
+
+        Some(state)
+    }
+
+    /// Applies the recorded inputs due before this step, as if made in the viewer.
+    fn replay_inputs(&mut self) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+
+        for (ids, action) in replay.take(self.solver.recorder.frame) {
+            match ids.iter().find(|&&i| i >= self.particles.len()) {
+                Some(i) => log::warn!(
+                    "Skipping replayed {} on missing particle {i}",
+                    action.name()
+                ),
+                None => self.intervene(&ids, action),
+            }
+        }
+    }
+
+    fn selected_info(&self) -> Option<String> {
+        let i = self.selected?;
+        let p = self.particles.get(i)?;
+        let cell = self.solver.grid().cell_coord(p.position);
+
+        Some(format!(
+            "#{i} v=({:.1}, {:.1}) cell=({}, {})",
+            p.velocity.x, p.velocity.y, cell.x, cell.y
+        ))
+    }
+
+    /// Draws the clicked particle and the selected group as rings.
+    fn highlight(&mut self) {
+        for p in &mut self.particles {
+            p.outline = 0.0;
+            p.layer = 0;
+        }
+
+        for &i in self.group.iter().chain(&self.selected) {
+            if let Some(p) = self.particles.get_mut(i) {
+                p.outline = HIGHLIGHT_OUTLINE;
+                p.layer = HIGHLIGHT_LAYER;
+            }
+        }
+    }
+
+    /// One arrow per particle, starting at its rim and growing with its speed.
+    fn velocity_arrows(&self, lines: &mut Vec<Line>) {
+        let (sin, cos) = ARROW_HEAD_ANGLE.sin_cos();
+
+        for p in &self.particles {
+            let Some(dir) = p.velocity.try_normalize() else {
+                continue;
+            };
+
+            let start = p.position + dir * p.radius;
+            let end = start + p.velocity * VELOCITY_ARROW_SCALE;
+            let head = (p.velocity.length() * VELOCITY_ARROW_SCALE * 0.4).min(ARROW_HEAD_LENGTH);
+
+            lines.push(Line::new(start, end, ARROW_COLOR));
+
+            for side in [Vec2::new(cos, sin), Vec2::new(cos, -sin)] {
+                lines.push(Line::new(end, end - side.rotate(dir) * head, ARROW_COLOR));
+            }
+        }
+    }
+
+    /// Cell boundaries over the visible box, plus the cells the voxel traversal visits for the
+    /// selected particle.
+    fn grid_lines(&self, lines: &mut Vec<Line>) {
+        let grid = self.solver.grid();
+        let size = grid.cell_size();
+        let he = self.half_extents;
+        let (lo, hi) = ((-he / size).floor(), (he / size).ceil());
+
+        for x in lo.x as i32..=hi.x as i32 {
+            let x = x as f32 * size;
+            lines.push(
+                Line::new(Vec2::new(x, -he.y), Vec2::new(x, he.y), GRID_COLOR)
+                    .with_layer(GRID_LAYER),
+            );
+        }
+
+        for y in lo.y as i32..=hi.y as i32 {
+            let y = y as f32 * size;
+            lines.push(
+                Line::new(Vec2::new(-he.x, y), Vec2::new(he.x, y), GRID_COLOR)
+                    .with_layer(GRID_LAYER),
+            );
+        }
+
+        let Some(p) = self.selected.and_then(|i| self.particles.get(i)) else {
+            return;
+        };
+
+        for cell in grid.cells_along_sweep_with_radius(p, self.last_dt) {
+            let min = cell.as_vec2() * size;
+            let max = min + size;
+            let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+
+            for k in 0..4 {
+                lines.push(
+                    Line::new(corners[k], corners[(k + 1) % 4], SWEEP_CELL_COLOR)
+                        .with_layer(GRID_LAYER),
+                );
+            }
+        }
+    }
+
+    fn spawn(&mut self, rng: &mut StdRng, bounds: &Bounds) {
+        let (hw, hh) = bounds.half_extents();
+        let boundary = self.solver.boundary();
+
+        self.particles.iter_mut().for_each(|p| {
+            p.position = Vec2::new(
+                rng.random_range(-0.9 * hw..0.9 * hw),
+                rng.random_range(-0.9 * hh..0.9 * hh),
+            );
+            p.velocity = Vec2::new(
+                rng.random_range(-SPEED..SPEED),
+                rng.random_range(-SPEED..SPEED),
+            );
+            p.radius = rng.random_range(3.0..7.0);
+            p.mass = std::f32::consts::PI * p.radius * p.radius;
+            p.color = [rng.random(), rng.random(), rng.random()];
+
+            for _ in 0..MAX_SPAWN_ATTEMPTS {
+                if boundary.contains(p, bounds) {
+                    break;
+                }
+
+                p.position = Vec2::new(
+                    rng.random_range(-0.9 * hw..0.9 * hw),
+                    rng.random_range(-0.9 * hh..0.9 * hh),
+                );
+            }
+        });
+    }
+
+    fn warmup(&mut self, bounds: &Bounds) {
+        if self.warmup_frames == 0 {
+            return;
+        }
+
+        let dt = 1.0 / self.fps as f32;
+
+        self.solver.recorder.enabled = false;
+
+        for _ in 0..self.warmup_frames {
+            self.solver.solve(&mut self.particles, bounds, dt);
+
+            if let Some(target) = self.warmup_temperature {
+                thermo::rescale_to_temperature(&mut self.particles, target);
+            }
+        }
+
+        log::info!(
+            "Warm-up finished after {} frames (T = {:.3})",
+            self.warmup_frames,
+            thermo::temperature(&self.particles)
+        );
+    }
+}
+
+/// Rings at the shadow's result for every particle it put somewhere else than the primary.
+fn ghosts(shadow: &[Particle], primary: &[Particle]) -> Vec<Particle> {
+    shadow
+        .iter()
+        .zip(primary)
+        .filter(|(s, p)| s.position.distance(p.position) > GHOST_DISTANCE)
+        .map(|(s, _)| Particle {
+            color: GHOST_COLOR,
+            outline: GHOST_OUTLINE,
+            layer: HIGHLIGHT_LAYER,
+            ..*s
+        })
+        .collect()
+}
+
+/// Runs `sim` in a window, stepping it on a worker thread if `threaded` is set.
+fn run<S: Simulation + Send + 'static>(
+    sim: S,
+    config: SimulationConfig,
+    threaded: bool,
+) -> Result<S, engine::Error> {
+    let (sim, stats) = match threaded {
+        true => engine::run_with(Threaded::new(sim), config)
+            .map(|f| (f.simulation.into_inner(), f.stats))?,
+        false => engine::run_with(sim, config).map(|f| (f.simulation, f.stats))?,
+    };
+
+    log::info!(
+        "{} frames at {:.1} fps, {:.3} ms per step, {} dropped after stalls",
+        stats.frames,
+        stats.average_fps(),
+        stats.average_step_time().as_secs_f64() * 1e3,
+        stats.dropped_frames
+    );
+
+    Ok(sim)
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    logging::init(&cli)?;
+    simulator::format::set_float_format(cli.float_format);
+    std::fs::create_dir_all(&cli.output_dir)?;
+    simulator::miscs::set_output_dir(cli.output_dir.clone());
+
+    if cli.replay_inputs.is_some() && cli.physics_hz.is_none() {
+        log::warn!("Replaying inputs without --physics-hz; steps will not line up exactly");
+    }
+    // Grid cells are named after their seeds, so an unseeded run picks its seed here rather than
+    // leaving it to the engine.
+    let seed = cli.seed.unwrap_or_else(rand::random);
+    // Every cell of a grid records into its own files; the first one is `sim`.
+    let cell_tag = |i: u64| cli.method.seeded_tag(seed.wrapping_add(i));
+    let tag = match cli.grid {
+        Some(_) => cell_tag(0),
+        None => cli.method.tag().to_string(),
+    };
+    let mut sim = TCcdSim::from_cli(&cli, cli.method, tag)?;
+
+    if let (Some(method), true) = (cli.compare, cli.lockstep) {
+        sim = sim.with_shadow(&cli, method);
+    }
+
+    if let Some(duration) = cli.soak {
+        return soak::run(
+            sim,
+            soak::SoakConfig {
+                bounds: Bounds {
+                    width: cli.window_size.0 as f32,
+                    height: cli.window_size.1 as f32,
+                },
+                duration,
+                check_interval: Duration::from_secs(cli.soak_check_interval),
+                shard_frames: cli.soak_shard_frames,
+                capture: cli
+                    .soak_capture_every
+                    .zip(cli.capture_dir.clone())
+                    .map(|(every, dir)| (dir, every)),
+                seed,
+            },
+        );
+    }
+
+    // Side-by-side and grid runs draw every simulation's particles from one buffer.
+    let simulations = match (cli.compare, cli.grid) {
+        (Some(_), _) if !cli.lockstep => 2,
+        (None, Some(count)) => count as usize,
+        _ => 1,
+    };
+    let config = SimulationConfig::builder()
+        .title("Particle Simulation")
+        .window_size(cli.window_size.0, cli.window_size.1)
+        .fullscreen(cli.fullscreen)
+        .monitor(cli.monitor)
+        .dpi_scaling(cli.dpi_scaling)
+        .run_unfocused(cli.run_unfocused)
+        .fps(cli.fps)
+        .msaa_samples(cli.msaa)
+        .render_scale(cli.render_scale)
+        .instance_capacity((sim.particles.len() * simulations).max(1))
+        .capture_dir(cli.capture_dir.clone())
+        .clip_frames(cli.clip_frames)
+        .backend(cli.backend.into())
+        .present_mode(cli.present_mode.into())
+        .fixed_dt(cli.physics_hz.map(|hz| 1.0 / hz as f32))
+        .max_dt(cli.max_dt)
+        .camera_path(
+            cli.camera_path
+                .as_deref()
+                .map(CameraPath::load)
+                .transpose()?,
+        )
+        .theme(cli.theme.into())
+        .clear_color(cli.background)
+        .srgb(cli.srgb)
+        .heatmap(cli.heatmap)
+        .lock_bounds(cli.lock_bounds)
+        .seed(Some(seed))
+        .build()?;
+
+    let result = match (cli.compare, cli.grid) {
+        (Some(method), _) if !cli.lockstep => {
+            // Comparing a method with itself, e.g. to check it is deterministic, must not write
+            // both sides into the same files.
+            let tag = match method.tag() == cli.method.tag() {
+                true => format!("{}_compare", method.tag()),
+                false => method.tag().to_string(),
+            };
+            let right = TCcdSim::from_cli(&cli, method, tag.clone())?;
+
+            run(SplitScreen::new(sim, right), config, cli.threaded).map(|split| {
+                let (left, right) = split.into_inner();
+
+                left.report_final_state(cli.method.tag());
+                right.report_final_state(&tag);
+            })
+        }
+        (None, Some(count)) => {
+            // The grid seeds cell `i` with `seed + i`.
+            let name = |i: u64| format!("seed {}", seed.wrapping_add(i));
+            let cells = iter::once(Ok((name(0), sim)))
+                .chain((1..count).map(|i| {
+                    let sim = TCcdSim::from_cli(&cli, cli.method, cell_tag(i))?;
+
+                    Ok((name(i), sim))
+                }))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            run(Grid::new(cells), config, cli.threaded).map(|grid| {
+                for (name, sim) in grid.into_cells() {
+                    sim.report_final_state(&name);
+                }
+            })
+        }
+        _ => run(sim, config, cli.threaded).map(|sim| sim.report_final_state(cli.method.tag())),
+    };
+
+    if let Err(engine::Error::AdapterNotFound(_)) = result {
+        log::error!("No GPU adapter is available; --soak <duration> runs without a window");
+    }
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particles_about_to_fall_are_not_quiescent() {
+        let cli = Cli::parse_from(["simulator", "--particle-count", "8", "--gravity", "100"]);
+        let mut sim = TCcdSim::from_cli(&cli, cli.method, "gravity".to_string()).unwrap();
+
+        for p in &mut sim.particles {
+            p.velocity = Vec2::ZERO;
+        }
+
+        sim.apply_controls();
+        assert!(!sim.quiescent());
+
+        sim.solver.set_gravity(Vec2::ZERO);
+        assert!(sim.quiescent());
+    }
 }
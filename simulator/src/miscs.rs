@@ -1,15 +1,57 @@
-use std::{fs::File, io::BufWriter};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 use clap::ValueEnum;
-use engine::particle::Particle;
-use serde::Serialize;
+use engine::{Backend, PresentMode, Theme, particle::Particle};
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+
+use engine::boundary::Wall;
+
+use crate::{
+    alloc::{self, AllocCounts, Section},
+    chain::ChainStep,
+    event::{Event, Intervention, InterventionEvent, PairEvent, WallEvent},
+    format,
+    inputs::InputRow,
+};
+
+static OUTPUT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the directory recordings, dumps and reports are written into. Only the first call has an
+/// effect, so call it before anything is written.
+pub fn set_output_dir(dir: PathBuf) {
+    if let Err(dir) = OUTPUT_DIR.set(dir) {
+        log::warn!("Output directory already set, ignoring {}", dir.display());
+    }
+}
+
+/// Where an output file called `name` goes: the output directory, or the working directory if
+/// none was set.
+pub fn output_path(name: impl AsRef<Path>) -> PathBuf {
+    match OUTPUT_DIR.get() {
+        Some(dir) => dir.join(name),
+        None => name.as_ref().to_path_buf(),
+    }
+}
 
 pub struct Recorder {
     pub frame: u64,
     pub time_s: f32,
+    pub enabled: bool,
 
     particles_csv: Option<CsvSink>,
     events_csv: Option<CsvSink>,
+    frames_csv: Option<CsvSink>,
+    chains_csv: Option<CsvSink>,
+    inputs_csv: Option<CsvSink>,
+
+    tag: String,
+    particle_count: u64,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -19,72 +61,191 @@ pub enum RecorderType {
     Both,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BoundaryType {
+    Box,
+    Periodic,
+    Open,
+    Circle,
+    Polygon,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BackendType {
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl From<BackendType> for Backend {
+    fn from(value: BackendType) -> Self {
+        match value {
+            BackendType::Auto => Backend::Auto,
+            BackendType::Vulkan => Backend::Vulkan,
+            BackendType::Dx12 => Backend::Dx12,
+            BackendType::Metal => Backend::Metal,
+            BackendType::Gl => Backend::Gl,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PresentModeType {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl From<PresentModeType> for PresentMode {
+    fn from(value: PresentModeType) -> Self {
+        match value {
+            PresentModeType::Fifo => PresentMode::Fifo,
+            PresentModeType::Mailbox => PresentMode::Mailbox,
+            PresentModeType::Immediate => PresentMode::Immediate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ThemeType {
+    Dark,
+    Light,
+}
+
+impl From<ThemeType> for Theme {
+    fn from(value: ThemeType) -> Self {
+        match value {
+            ThemeType::Dark => Theme::Dark,
+            ThemeType::Light => Theme::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ThermostatType {
+    Rescale,
+    Berendsen,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum DetectionType {
     CellList,
     Tccd,
     SweptAabb,
+    KdTree,
 }
 
 impl DetectionType {
-    fn tag(&self) -> &'static str {
+    /// Short name used in recording file names.
+    pub fn tag(&self) -> &'static str {
         match self {
             DetectionType::CellList => "cell_list",
             DetectionType::Tccd => "tccd",
             DetectionType::SweptAabb => "swept_aabb",
+            DetectionType::KdTree => "kd_tree",
         }
     }
 
-    fn make_sink(prefix: &str, tag: &str, count: u64) -> CsvSink {
-        CsvSink::new(format!("{prefix}_{tag}_{count}.csv"))
+    /// Tag for one cell of a grid run, so cells seeded differently record into their own files.
+    pub fn seeded_tag(&self, seed: u64) -> String {
+        format!("{}_seed{seed}", self.tag())
     }
 }
 
+fn sink_name(prefix: &str, tag: &str, count: u64) -> String {
+    format!("{prefix}_{tag}_{count}.csv")
+}
+
+/// Every file prefix a recorder of `r_type` may open, and whether it does.
+fn sink_kinds(r_type: Option<RecorderType>) -> [(&'static str, bool); 4] {
+    let (snapshots, events) = match r_type {
+        None => (false, false),
+        Some(r) => (
+            matches!(r, RecorderType::Snapshots | RecorderType::Both),
+            matches!(r, RecorderType::Events | RecorderType::Both),
+        ),
+    };
+
+    [
+        ("particles", snapshots),
+        ("events", events),
+        ("frames", r_type.is_some()),
+        ("inputs", r_type.is_some()),
+    ]
+}
+
 impl Recorder {
     pub fn new(r_type: Option<RecorderType>, d_type: DetectionType, particle_count: u64) -> Self {
-        let (particles_csv, events_csv) = match r_type {
-            None => (None, None),
-            Some(r) => {
-                let tag = d_type.tag();
-                let has_particles = matches!(r, RecorderType::Snapshots | RecorderType::Both);
-                let has_events = matches!(r, RecorderType::Events | RecorderType::Both);
-
-                let p = if has_particles {
-                    Some(DetectionType::make_sink("particles", tag, particle_count))
-                } else {
-                    None
-                };
-                let e = if has_events {
-                    Some(DetectionType::make_sink("events", tag, particle_count))
-                } else {
-                    None
-                };
-
-                (p, e)
-            }
-        };
+        Self::tagged(r_type, d_type.tag().to_string(), particle_count)
+    }
+
+    /// Like [`Recorder::new`], but with `tag` in the file names instead of the detector's.
+    pub fn tagged(r_type: Option<RecorderType>, tag: String, particle_count: u64) -> Self {
+        let [particles_csv, events_csv, frames_csv, inputs_csv] = sink_kinds(r_type)
+            .map(|(prefix, on)| on.then(|| CsvSink::new(sink_name(prefix, &tag, particle_count))));
+
         Self {
             frame: 0,
             time_s: 0.0,
+            enabled: true,
             particles_csv,
             events_csv,
+            frames_csv,
+            chains_csv: None,
+            inputs_csv,
+            tag,
+            particle_count,
+        }
+    }
+
+    /// Files [`Recorder::tagged`] opens for the same arguments, without opening them.
+    pub fn file_names(r_type: Option<RecorderType>, tag: &str, particle_count: u64) -> Vec<String> {
+        sink_kinds(r_type)
+            .into_iter()
+            .filter(|&(_, on)| on)
+            .map(|(prefix, _)| sink_name(prefix, tag, particle_count))
+            .collect()
+    }
+
+    pub fn enable_chain_trace(&mut self) {
+        self.chains_csv.get_or_insert_with(|| {
+            CsvSink::new(sink_name("chains", &self.tag, self.particle_count))
+        });
+    }
+
+    /// Closes the current files and continues writing into `{prefix}_{tag}_{count}_shard{n}.csv`.
+    pub fn rotate(&mut self, shard: usize) {
+        let stem = format!("{}_{}_shard{shard}", self.tag, self.particle_count);
+
+        for (prefix, sink) in [
+            ("particles", &mut self.particles_csv),
+            ("events", &mut self.events_csv),
+            ("frames", &mut self.frames_csv),
+            ("chains", &mut self.chains_csv),
+            ("inputs", &mut self.inputs_csv),
+        ] {
+            if let Some(sink) = sink {
+                sink.flush();
+                *sink = CsvSink::new(format!("{prefix}_{stem}.csv"));
+            }
         }
     }
 
     pub fn write_particles_snapshot(&mut self, particles: &[Particle]) {
+        if !self.enabled {
+            return;
+        }
+
+        let _alloc = alloc::scope(Section::Recorder);
+
         if let Some(pw) = &mut self.particles_csv {
             for (i, p) in particles.iter().enumerate() {
-                if let Err(e) = pw.writer_mut().serialize(ParticleRow {
-                    frame: self.frame,
-                    time_s: self.time_s,
-                    particle_id: i,
-                    x: p.position.x,
-                    y: p.position.y,
-                    vx: p.velocity.x,
-                    vy: p.velocity.y,
-                    radius: p.radius,
-                    mass: p.mass,
-                }) {
+                if let Err(e) =
+                    pw.writer_mut()
+                        .serialize(ParticleRow::new(self.frame, self.time_s, i, p))
+                {
                     log::error!("Failed to write particle snapshot: {}", e);
                     break;
                 }
@@ -92,55 +253,202 @@ impl Recorder {
         }
     }
 
-    pub fn write_event_pair(
+    pub fn write_frame(
         &mut self,
-        (toi, i, j, nx, ny, vrel_n_before, vrel_n_after): (f32, usize, usize, f32, f32, f32, f32),
+        kinetic_energy: f64,
+        momentum: DVec2,
+        thermostat_scale: f32,
+        times: FrameTimes,
     ) {
-        if let Some(ew) = &mut self.events_csv
-            && let Err(e) = ew.writer_mut().serialize(EventRow::Pair {
+        if !self.enabled {
+            return;
+        }
+
+        let _alloc = alloc::scope(Section::Recorder);
+
+        if let Some(fw) = &mut self.frames_csv
+            && let Err(e) = fw.writer_mut().serialize(FrameRow {
                 frame: self.frame,
-                time_s: self.time_s + toi,
-                toi,
-                i,
-                j,
-                nx,
-                ny,
-                vrel_n_before,
-                vrel_n_after,
+                time_s: self.time_s,
+                kinetic_energy,
+                momentum_x: momentum.x,
+                momentum_y: momentum.y,
+                thermostat_scale,
+                solve_ms: times.solve_ms,
+                record_ms: times.record_ms,
+                render_ms: times.render_ms,
+                wall_ms: times.wall_ms,
+                real_time_factor: times.real_time_factor(),
+                #[cfg(feature = "alloc-audit")]
+                allocs_render: times.allocs.count(Section::Render),
+                #[cfg(feature = "alloc-audit")]
+                alloc_bytes_render: times.allocs.bytes(Section::Render),
+                #[cfg(feature = "alloc-audit")]
+                allocs_solver: times.allocs.count(Section::Solver),
+                #[cfg(feature = "alloc-audit")]
+                alloc_bytes_solver: times.allocs.bytes(Section::Solver),
+                #[cfg(feature = "alloc-audit")]
+                allocs_grid: times.allocs.count(Section::Grid),
+                #[cfg(feature = "alloc-audit")]
+                alloc_bytes_grid: times.allocs.bytes(Section::Grid),
+                #[cfg(feature = "alloc-audit")]
+                allocs_detector: times.allocs.count(Section::Detector),
+                #[cfg(feature = "alloc-audit")]
+                alloc_bytes_detector: times.allocs.bytes(Section::Detector),
+                #[cfg(feature = "alloc-audit")]
+                allocs_recorder: times.allocs.count(Section::Recorder),
+                #[cfg(feature = "alloc-audit")]
+                alloc_bytes_recorder: times.allocs.bytes(Section::Recorder),
             })
         {
-            log::error!("Failed to write pair event: {}", e);
+            log::error!("Failed to write frame row: {}", e);
         }
     }
 
-    pub fn write_event_wall(
-        &mut self,
-        (toi, i, wall, nx, ny, vn_before, vn_after): (f32, usize, &'static str, f32, f32, f32, f32),
-    ) {
+    pub fn write_event(&mut self, event: &Event) {
+        if !self.enabled {
+            return;
+        }
+
+        let _alloc = alloc::scope(Section::Recorder);
+
+        let row = match event {
+            Event::Pair(e) => EventRow::Pair(self.pair_row(e)),
+            Event::Wall(e) => EventRow::Wall(self.wall_row(e)),
+            Event::RestingPair(e) => EventRow::RestingPair(self.pair_row(e)),
+            Event::RestingWall(e) => EventRow::RestingWall(self.wall_row(e)),
+            Event::Intervention(e) => EventRow::Intervention(self.intervention_row(e)),
+        };
+
         if let Some(ew) = &mut self.events_csv
-            && let Err(e) = ew.writer_mut().serialize(EventRow::Wall {
+            && let Err(err) = ew.writer_mut().serialize(row)
+        {
+            log::error!("Failed to write event: {}", err);
+        }
+    }
+
+    /// Logs a viewer intervention so the session can be replayed with `--replay-inputs`. Inputs
+    /// are logged even while recording is off, since the replay has to see every one of them.
+    pub fn write_input(&mut self, ids: &[usize], action: Intervention) {
+        let _alloc = alloc::scope(Section::Recorder);
+
+        if let Some(iw) = &mut self.inputs_csv
+            && let Err(e) =
+                iw.writer_mut()
+                    .serialize(InputRow::new(self.frame, self.time_s, ids, action))
+        {
+            log::error!("Failed to write input: {}", e);
+        }
+    }
+
+    pub fn write_chain_step(&mut self, step: ChainStep, event: &Event, particles: &[Particle]) {
+        if !self.enabled {
+            return;
+        }
+
+        let _alloc = alloc::scope(Section::Recorder);
+
+        let (i, j) = event.particles();
+        let vj = j.map(|j| particles[j].velocity);
+
+        if let Some(cw) = &mut self.chains_csv
+            && let Err(e) = cw.writer_mut().serialize(ChainRow {
                 frame: self.frame,
-                time_s: self.time_s + toi,
-                toi,
+                chain: step.chain,
+                order: step.order,
+                t_frame: step.elapsed,
+                kind: event.kind(),
                 i,
-                wall,
-                nx,
-                ny,
-                vn_before,
-                vn_after,
+                j,
+                vix: particles[i].velocity.x,
+                viy: particles[i].velocity.y,
+                vjx: vj.map(|v| v.x),
+                vjy: vj.map(|v| v.y),
             })
         {
-            log::error!("Failed to write wall event: {}", e);
+            log::error!("Failed to write chain step: {}", e);
         }
     }
 
-    pub fn flush(&mut self) {
-        if self.frame % 60 == 0
-            && let (Some(pw), Some(ew)) = (&mut self.particles_csv, &mut self.events_csv)
-        {
-            pw.flush();
-            ew.flush();
+    fn pair_row(&self, e: &PairEvent) -> PairRow {
+        PairRow {
+            frame: self.frame,
+            time_s: self.time_s + e.toi,
+            toi: e.toi,
+            i: e.i,
+            j: e.j,
+            nx: e.normal.x,
+            ny: e.normal.y,
+            vrel_n_before: e.vrel_n_before,
+            vrel_n_after: e.vrel_n_after,
+        }
+    }
+
+    fn intervention_row(&self, e: &InterventionEvent) -> InterventionRow {
+        let (vx, vy) = match e.action {
+            Intervention::Velocity(v) => (v.x, v.y),
+            Intervention::Freeze => (0.0, 0.0),
+            _ => (f32::NAN, f32::NAN),
         };
+
+        InterventionRow {
+            frame: self.frame,
+            time_s: self.time_s,
+            toi: 0.0,
+            i: e.i,
+            action: e.action.name(),
+            vx,
+            vy,
+            mass: match e.action {
+                Intervention::Freeze => f32::INFINITY,
+                _ => f32::NAN,
+            },
+            color: match e.action {
+                Intervention::Color([r, g, b]) => format!(
+                    "#{:02x}{:02x}{:02x}",
+                    (r * 255.0).round() as u8,
+                    (g * 255.0).round() as u8,
+                    (b * 255.0).round() as u8
+                ),
+                _ => String::new(),
+            },
+        }
+    }
+
+    fn wall_row(&self, e: &WallEvent) -> WallRow {
+        WallRow {
+            frame: self.frame,
+            time_s: self.time_s + e.toi,
+            toi: e.toi,
+            i: e.i,
+            wall: e.wall,
+            nx: e.normal.x,
+            ny: e.normal.y,
+            vn_before: e.vn_before,
+            vn_after: e.vn_after,
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.frame.is_multiple_of(60) {
+            self.finish();
+        }
+    }
+
+    /// Flushes every file regardless of the frame, so nothing is lost at shutdown.
+    pub fn finish(&mut self) {
+        let _alloc = alloc::scope(Section::Recorder);
+
+        [
+            &mut self.particles_csv,
+            &mut self.events_csv,
+            &mut self.frames_csv,
+            &mut self.chains_csv,
+            &mut self.inputs_csv,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(CsvSink::flush);
     }
 }
 
@@ -150,12 +458,16 @@ pub struct CsvSink {
 }
 
 impl CsvSink {
-    fn new(path: String) -> Self {
+    fn new(name: String) -> Self {
+        let path = output_path(&name);
         let file = File::create(&path).expect("create csv");
         let buf = BufWriter::new(file);
         let writer = csv::WriterBuilder::new().from_writer(buf);
 
-        Self { name: path, writer }
+        Self {
+            name: path.display().to_string(),
+            writer,
+        }
     }
 
     fn flush(&mut self) {
@@ -169,42 +481,424 @@ impl CsvSink {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParticleRow {
     pub frame: u64,
+    #[serde(serialize_with = "format::f32")]
     pub time_s: f32,
     pub particle_id: usize,
+    #[serde(serialize_with = "format::f32")]
     pub x: f32,
+    #[serde(serialize_with = "format::f32")]
     pub y: f32,
+    #[serde(serialize_with = "format::f32")]
     pub vx: f32,
+    #[serde(serialize_with = "format::f32")]
     pub vy: f32,
+    #[serde(serialize_with = "format::f32")]
     pub radius: f32,
+    #[serde(serialize_with = "format::f32")]
     pub mass: f32,
 }
 
+impl ParticleRow {
+    pub fn new(frame: u64, time_s: f32, particle_id: usize, p: &Particle) -> Self {
+        Self {
+            frame,
+            time_s,
+            particle_id,
+            x: p.position.x,
+            y: p.position.y,
+            vx: p.velocity.x,
+            vy: p.velocity.y,
+            radius: p.radius,
+            mass: p.mass,
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+pub struct FrameRow {
+    pub frame: u64,
+    #[serde(serialize_with = "format::f32")]
+    pub time_s: f32,
+    #[serde(serialize_with = "format::f64")]
+    pub kinetic_energy: f64,
+    #[serde(serialize_with = "format::f64")]
+    pub momentum_x: f64,
+    #[serde(serialize_with = "format::f64")]
+    pub momentum_y: f64,
+    #[serde(serialize_with = "format::f32")]
+    pub thermostat_scale: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub solve_ms: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub record_ms: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub render_ms: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub wall_ms: f32,
+    #[serde(serialize_with = "format::opt_f32")]
+    pub real_time_factor: Option<f32>,
+    #[cfg(feature = "alloc-audit")]
+    pub allocs_render: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub alloc_bytes_render: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub allocs_solver: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub alloc_bytes_solver: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub allocs_grid: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub alloc_bytes_grid: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub allocs_detector: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub alloc_bytes_detector: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub allocs_recorder: u64,
+    #[cfg(feature = "alloc-audit")]
+    pub alloc_bytes_recorder: u64,
+}
+
+/// Where a step's wall-clock time went, in milliseconds, next to the sim time it advanced.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTimes {
+    pub dt: f32,
+    /// Time since the previous frame, rendering included; with `--physics-hz`, this step's share
+    /// of it.
+    pub wall_ms: f32,
+    pub solve_ms: f32,
+    /// Writing the particle snapshot.
+    pub record_ms: f32,
+    /// Drawing the previous frame.
+    pub render_ms: f32,
+    /// Allocations since the previous frame; all zero without the `alloc-audit` feature.
+    pub allocs: AllocCounts,
+}
+
+impl FrameTimes {
+    /// Simulated seconds per wall-clock second, unknown without a wall time.
+    pub fn real_time_factor(&self) -> Option<f32> {
+        (self.wall_ms > 0.0).then(|| self.dt * 1000.0 / self.wall_ms)
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChainRow {
+    pub frame: u64,
+    pub chain: usize,
+    pub order: usize,
+    #[serde(serialize_with = "format::f32")]
+    pub t_frame: f32,
+    pub kind: &'static str,
+    pub i: usize,
+    pub j: Option<usize>,
+    #[serde(serialize_with = "format::f32")]
+    pub vix: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub viy: f32,
+    #[serde(serialize_with = "format::opt_f32")]
+    pub vjx: Option<f32>,
+    #[serde(serialize_with = "format::opt_f32")]
+    pub vjy: Option<f32>,
+}
+
 #[derive(Serialize)]
 #[serde(tag = "type")]
 pub enum EventRow {
-    Pair {
-        frame: u64,
-        time_s: f32,
-        toi: f32,
-        i: usize,
-        j: usize,
-        nx: f32,
-        ny: f32,
-        vrel_n_before: f32,
-        vrel_n_after: f32,
-    },
-    Wall {
-        frame: u64,
-        time_s: f32,
-        toi: f32,
-        i: usize,
-        wall: &'static str,
-        nx: f32,
-        ny: f32,
-        vn_before: f32,
-        vn_after: f32,
-    },
+    Pair(PairRow),
+    Wall(WallRow),
+    RestingPair(PairRow),
+    RestingWall(WallRow),
+    Intervention(InterventionRow),
+}
+
+#[derive(Serialize)]
+pub struct PairRow {
+    pub frame: u64,
+    #[serde(serialize_with = "format::f32")]
+    pub time_s: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub toi: f32,
+    pub i: usize,
+    pub j: usize,
+    #[serde(serialize_with = "format::f32")]
+    pub nx: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub ny: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub vrel_n_before: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub vrel_n_after: f32,
+}
+
+#[derive(Serialize)]
+pub struct WallRow {
+    pub frame: u64,
+    #[serde(serialize_with = "format::f32")]
+    pub time_s: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub toi: f32,
+    pub i: usize,
+    pub wall: Wall,
+    #[serde(serialize_with = "format::f32")]
+    pub nx: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub ny: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub vn_before: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub vn_after: f32,
+}
+
+/// Same column count as the collision rows, which the CSV writer requires. Fields that do not
+/// apply to the action are left as NaN or empty.
+#[derive(Serialize)]
+pub struct InterventionRow {
+    pub frame: u64,
+    #[serde(serialize_with = "format::f32")]
+    pub time_s: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub toi: f32,
+    pub i: usize,
+    pub action: &'static str,
+    #[serde(serialize_with = "format::f32")]
+    pub vx: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub vy: f32,
+    #[serde(serialize_with = "format::f32")]
+    pub mass: f32,
+    pub color: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use engine::boundary::Wall;
+    use glam::Vec2;
+
+    use super::*;
+    use crate::scenario;
+
+    /// Writes `rows` the way the recorder's sinks do and reads them back as raw records.
+    fn round_trip<T: Serialize>(rows: impl IntoIterator<Item = T>) -> Vec<csv::StringRecord> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        for row in rows {
+            writer.serialize(row).unwrap();
+        }
+
+        let data = writer.into_inner().unwrap();
+        let mut reader = csv::Reader::from_reader(&data[..]);
+        let header = reader.headers().unwrap().clone();
+
+        // Keep the header as the first record so fields can be looked up by name.
+        std::iter::once(Ok(header))
+            .chain(reader.records())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn field<'a>(records: &'a [csv::StringRecord], row: usize, name: &str) -> &'a str {
+        let column = records[0].iter().position(|h| h == name).unwrap();
+
+        &records[row + 1][column]
+    }
+
+    fn recorder() -> Recorder {
+        let mut recorder = Recorder::new(None, DetectionType::Tccd, 4);
+
+        recorder.frame = 7;
+        recorder.time_s = 1.5;
+        recorder
+    }
+
+    #[test]
+    fn particle_rows_round_trip_through_the_scenario_reader() {
+        let particles = [
+            Particle::new(
+                Vec2::new(-1e-7, 3.25),
+                Vec2::new(0.1, -0.0),
+                5.0,
+                78.5,
+                [1.0; 3],
+            ),
+            Particle::new(
+                Vec2::new(1e6, -2.5e-3),
+                Vec2::ZERO,
+                0.5,
+                f32::INFINITY,
+                [1.0; 3],
+            ),
+        ];
+        let rows = particles
+            .iter()
+            .enumerate()
+            .map(|(i, p)| ParticleRow::new(3, 0.05, i, p))
+            .collect::<Vec<_>>();
+        let path = std::env::temp_dir().join(format!("tccd-rows-{}.csv", std::process::id()));
+
+        scenario::save(&path, &rows).unwrap();
+        let read = scenario::read_rows(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(read, rows);
+    }
+
+    #[test]
+    fn grid_cells_record_into_distinct_files() {
+        let cells = 4;
+        let names = (0..cells)
+            .flat_map(|seed| {
+                let tag = DetectionType::Tccd.seeded_tag(seed);
+
+                Recorder::file_names(Some(RecorderType::Both), &tag, 500)
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        assert_eq!(names.len(), 4 * cells as usize);
+        assert!(Recorder::file_names(None, "tccd", 500).is_empty());
+    }
+
+    #[test]
+    fn frame_rows_default_to_nine_significant_digits() {
+        let row = FrameRow {
+            frame: u64::MAX,
+            time_s: 0.1,
+            kinetic_energy: 0.1 + 0.2,
+            momentum_x: 1e-300,
+            momentum_y: -2.5,
+            thermostat_scale: 0.999,
+            solve_ms: 16.7,
+            record_ms: 0.25,
+            render_ms: 3.5,
+            wall_ms: 16.9,
+            ..Default::default()
+        };
+        let records = round_trip([&row]);
+        let read = |name| field(&records, 0, name);
+
+        assert_eq!(format::float_format(), format::FloatFormat::General(9));
+        assert_eq!(read("frame"), u64::MAX.to_string());
+        assert_eq!(read("time_s"), "0.100000001");
+        assert_eq!(read("kinetic_energy"), "0.3");
+        assert_eq!(read("momentum_x"), "1e-300");
+        assert_eq!(read("momentum_y"), "-2.5");
+        assert_eq!(read("thermostat_scale"), "0.999000013");
+        assert_eq!(read("solve_ms"), "16.7000008");
+        assert_eq!(read("wall_ms"), "16.8999996");
+        assert_eq!(read("real_time_factor"), "");
+
+        // Nine digits are enough for every f32 column to read back exactly.
+        assert_eq!(read("time_s").parse::<f32>().unwrap(), row.time_s);
+        assert_eq!(read("solve_ms").parse::<f32>().unwrap(), row.solve_ms);
+        assert_eq!(read("wall_ms").parse::<f32>().unwrap(), row.wall_ms);
+    }
+
+    #[test]
+    fn every_event_kind_fits_the_pair_columns() {
+        let recorder = recorder();
+        let pair = PairEvent::new(0.25, 1, 2)
+            .normal(Vec2::new(0.6, -0.8))
+            .vrel_n(-3.0, 2.7);
+        let wall = WallEvent::new(0.125, 3, Wall::Left)
+            .normal(Vec2::X)
+            .vn(-4.0, 4.0);
+        let events = [
+            Event::Pair(pair),
+            Event::Wall(wall),
+            Event::RestingPair(pair),
+            Event::RestingWall(wall),
+            Event::from(InterventionEvent {
+                i: 0,
+                action: Intervention::Velocity(Vec2::new(5.0, -6.0)),
+            }),
+            Event::from(InterventionEvent {
+                i: 1,
+                action: Intervention::Color([1.0, 0.5, 0.0]),
+            }),
+            Event::from(InterventionEvent {
+                i: 2,
+                action: Intervention::Freeze,
+            }),
+        ];
+        let records = round_trip(events.iter().map(|event| match event {
+            Event::Pair(e) => EventRow::Pair(recorder.pair_row(e)),
+            Event::Wall(e) => EventRow::Wall(recorder.wall_row(e)),
+            Event::RestingPair(e) => EventRow::RestingPair(recorder.pair_row(e)),
+            Event::RestingWall(e) => EventRow::RestingWall(recorder.wall_row(e)),
+            Event::Intervention(e) => EventRow::Intervention(recorder.intervention_row(e)),
+        }));
+        let read = |row, name| field(&records, row, name);
+        let float = |row, name| read(row, name).parse::<f32>().unwrap();
+
+        // The header comes from the pair row; later kinds reuse its columns positionally.
+        assert_eq!(
+            records[0].iter().collect::<Vec<_>>(),
+            [
+                "type",
+                "frame",
+                "time_s",
+                "toi",
+                "i",
+                "j",
+                "nx",
+                "ny",
+                "vrel_n_before",
+                "vrel_n_after"
+            ]
+        );
+        assert_eq!(records.len(), events.len() + 1);
+
+        for (row, kind) in ["Pair", "Wall", "RestingPair", "RestingWall"]
+            .into_iter()
+            .enumerate()
+        {
+            let toi = match row % 2 {
+                0 => pair.toi,
+                _ => wall.toi,
+            };
+
+            assert_eq!(read(row, "type"), kind);
+            assert_eq!(read(row, "frame"), "7");
+            assert_eq!(float(row, "toi"), toi);
+            assert_eq!(float(row, "time_s"), 1.5 + toi);
+        }
+
+        for row in [0, 2] {
+            assert_eq!((read(row, "i"), read(row, "j")), ("1", "2"));
+            assert_eq!((float(row, "nx"), float(row, "ny")), (0.6, -0.8));
+            assert_eq!(float(row, "vrel_n_before"), -3.0);
+            assert_eq!(float(row, "vrel_n_after"), 2.7);
+        }
+
+        // Walls put the wall name where pairs have `j`, and vn where pairs have vrel_n.
+        for row in [1, 3] {
+            assert_eq!((read(row, "i"), read(row, "j")), ("3", "left"));
+            assert_eq!((float(row, "nx"), float(row, "ny")), (1.0, 0.0));
+            assert_eq!(float(row, "vrel_n_before"), -4.0);
+            assert_eq!(float(row, "vrel_n_after"), 4.0);
+        }
+
+        // Interventions: action in `j`, velocity in nx/ny, mass and color in the vrel columns.
+        for row in 4..7 {
+            assert_eq!(read(row, "type"), "Intervention");
+            assert_eq!(read(row, "time_s"), "1.5");
+            assert_eq!(read(row, "i"), (row - 4).to_string());
+        }
+
+        assert_eq!(read(4, "j"), "velocity");
+        assert_eq!((float(4, "nx"), float(4, "ny")), (5.0, -6.0));
+        assert!(float(4, "vrel_n_before").is_nan());
+        assert_eq!(read(4, "vrel_n_after"), "");
+
+        assert_eq!(read(5, "j"), "color");
+        assert!(float(5, "nx").is_nan() && float(5, "ny").is_nan());
+        assert_eq!(read(5, "vrel_n_after"), "#ff8000");
+
+        assert_eq!(read(6, "j"), "freeze");
+        assert_eq!((float(6, "nx"), float(6, "ny")), (0.0, 0.0));
+        assert_eq!(float(6, "vrel_n_before"), f32::INFINITY);
+    }
 }
@@ -0,0 +1,81 @@
+//! Per-frame analysis hooks. Observers registered with [`Solver::add_observer`] see the state
+//! after every solve, so measurements like histograms or wall pressure can live outside the
+//! solver.
+//!
+//! [`Solver::add_observer`]: crate::solver::Solver::add_observer
+
+use engine::{Bounds, particle::Particle};
+
+use crate::event::Event;
+
+/// Read-only view of one solved frame.
+pub struct FrameView<'a> {
+    pub particles: &'a [Particle],
+    /// Collisions resolved during the frame, in order.
+    pub events: &'a [Event],
+    pub bounds: &'a Bounds,
+    pub dt: f32,
+}
+
+pub trait FrameObserver: Send {
+    fn observe(&mut self, frame: &FrameView);
+
+    /// Called once when the run ends, e.g. to write out what was gathered.
+    fn finish(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use glam::Vec2;
+
+    use super::*;
+    use crate::{
+        miscs::{BoundaryType, DetectionType},
+        solver::Solver,
+    };
+
+    /// Appends the number of events of every frame it sees.
+    struct EventCounter(Arc<Mutex<Vec<usize>>>);
+
+    impl FrameObserver for EventCounter {
+        fn observe(&mut self, frame: &FrameView) {
+            self.0.lock().unwrap().push(frame.events.len());
+        }
+    }
+
+    #[test]
+    fn sees_every_frame_and_its_collisions() {
+        let bounds = Bounds {
+            width: 400.0,
+            height: 200.0,
+        };
+        let mut particles = [
+            Particle::new(
+                Vec2::new(-12.0, 0.0),
+                Vec2::new(60.0, 0.0),
+                5.0,
+                1.0,
+                [1.0; 3],
+            ),
+            Particle::new(
+                Vec2::new(12.0, 0.0),
+                Vec2::new(-60.0, 0.0),
+                5.0,
+                1.0,
+                [1.0; 3],
+            ),
+        ];
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 2);
+
+        solver.add_observer(Box::new(EventCounter(counts.clone())));
+
+        for _ in 0..3 {
+            solver.solve(&mut particles, &bounds, 0.1);
+        }
+
+        assert_eq!(*counts.lock().unwrap(), [0, 1, 0]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Frame-to-frame reuse of broad-phase results. A particle's candidates are the occupants of the
+//! grid cells its query visits, so as long as it visits the same cells and none of them gained
+//! or lost a particle since the last query, the previous answer still holds.
+
+use std::collections::HashSet;
+
+use engine::particle::Particle;
+use glam::IVec2;
+
+use crate::{
+    detector::{
+        BroadPhase, CandidatePairs, CellListBroadPhase, KdTreeBroadPhase, SweptAabbBroadPhase,
+        TccdBroadPhase,
+    },
+    miscs::DetectionType,
+    spatial::SpatialGrid,
+};
+
+/// A broad phase whose candidates for particle `i` are the other particles in a list of cells,
+/// deduplicated in the order the cells are visited.
+pub trait CellQuery {
+    fn cells(&self, grid: &SpatialGrid, p: &Particle, dt: f32, out: &mut Vec<IVec2>);
+}
+
+impl CellQuery for CellListBroadPhase {
+    fn cells(&self, grid: &SpatialGrid, p: &Particle, _dt: f32, out: &mut Vec<IVec2>) {
+        out.extend(grid.cell_list_cells(p));
+    }
+}
+
+impl CellQuery for TccdBroadPhase {
+    fn cells(&self, grid: &SpatialGrid, p: &Particle, dt: f32, out: &mut Vec<IVec2>) {
+        out.extend(grid.cells_along_sweep_with_radius(p, dt));
+    }
+}
+
+impl CellQuery for SweptAabbBroadPhase {
+    fn cells(&self, grid: &SpatialGrid, p: &Particle, dt: f32, out: &mut Vec<IVec2>) {
+        let (cmin, cmax) = grid.swept_aabb_cells(p, dt);
+
+        for cy in cmin.y..=cmax.y {
+            out.extend((cmin.x..=cmax.x).map(|cx| IVec2::new(cx, cy)));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    valid: bool,
+    cells: Vec<IVec2>,
+    candidates: Vec<usize>,
+}
+
+/// Wraps a [`CellQuery`] broad phase and only re-queries particles whose cells changed. Debug
+/// builds check every answer against the wrapped broad phase.
+pub struct CachedBroadPhase<B> {
+    inner: B,
+    entries: Vec<Entry>,
+    /// Cell of every particle at the last call.
+    homes: Vec<IVec2>,
+    /// Cells that gained or lost a particle since the last call.
+    dirty: HashSet<IVec2>,
+    seen: HashSet<usize>,
+    scratch: Vec<IVec2>,
+    hits: u64,
+    queries: u64,
+}
+
+impl<B> CachedBroadPhase<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            entries: Vec::new(),
+            homes: Vec::new(),
+            dirty: HashSet::new(),
+            seen: HashSet::new(),
+            scratch: Vec::new(),
+            hits: 0,
+            queries: 0,
+        }
+    }
+
+    /// Marks the cells particles moved between, or everything when the particles were replaced.
+    fn track_moves(&mut self, grid: &SpatialGrid, particles: &[Particle]) {
+        self.dirty.clear();
+
+        if self.homes.len() != particles.len() {
+            self.homes.clear();
+            self.homes
+                .extend(particles.iter().map(|p| grid.home_cell(p.position)));
+            self.entries.clear();
+            self.entries.resize_with(particles.len(), Entry::default);
+            return;
+        }
+
+        for (home, p) in self.homes.iter_mut().zip(particles) {
+            let cell = grid.home_cell(p.position);
+
+            if cell != *home {
+                self.dirty.insert(*home);
+                self.dirty.insert(cell);
+                *home = cell;
+            }
+        }
+    }
+}
+
+impl<B: BroadPhase + CellQuery> BroadPhase for CachedBroadPhase<B> {
+    fn candidates(
+        &mut self,
+        grid: &SpatialGrid,
+        particles: &[Particle],
+        dt: f32,
+        out: &mut CandidatePairs,
+    ) {
+        self.track_moves(grid, particles);
+
+        for (i, p) in particles.iter().enumerate() {
+            self.scratch.clear();
+            self.inner.cells(grid, p, dt, &mut self.scratch);
+
+            let entry = &mut self.entries[i];
+            let hit = entry.valid
+                && entry.cells == self.scratch
+                && (self.dirty.is_empty()
+                    || !self
+                        .scratch
+                        .iter()
+                        .flat_map(|&c| grid.keys(c))
+                        .any(|k| self.dirty.contains(&k)));
+
+            self.queries += 1;
+
+            if hit {
+                self.hits += 1;
+            } else {
+                entry.valid = true;
+                entry.candidates.clear();
+                self.seen.clear();
+
+                for &c in &self.scratch {
+                    for j in grid.cell(c) {
+                        if j != i && self.seen.insert(j) {
+                            entry.candidates.push(j);
+                        }
+                    }
+                }
+
+                std::mem::swap(&mut entry.cells, &mut self.scratch);
+            }
+
+            for &j in entry.candidates.iter().filter(|&&j| j > i) {
+                out.push(i, j);
+            }
+        }
+
+        if cfg!(debug_assertions) {
+            let mut fresh = CandidatePairs::new(out.chunk_size());
+
+            self.inner.candidates(grid, particles, dt, &mut fresh);
+            assert!(
+                out.pairs() == fresh.pairs(),
+                "cached candidates differ from a full query"
+            );
+        }
+    }
+
+    fn cache_hit_rate(&self) -> Option<f32> {
+        Some(self.hits as f32 / self.queries.max(1) as f32)
+    }
+}
+
+/// Builds the cached variant of a detection method's broad phase. The k-d tree broad phase has
+/// no cells to key a cache on and comes back uncached.
+pub fn cached(d_type: DetectionType) -> Box<dyn BroadPhase> {
+    match d_type {
+        DetectionType::CellList => Box::new(CachedBroadPhase::new(CellListBroadPhase)),
+        DetectionType::Tccd => Box::new(CachedBroadPhase::new(TccdBroadPhase)),
+        DetectionType::SweptAabb => Box::new(CachedBroadPhase::new(SweptAabbBroadPhase)),
+        DetectionType::KdTree => Box::new(KdTreeBroadPhase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn reuses_candidates_until_a_cell_changes() {
+        // Two pairs far apart; only the second one moves into a new cell.
+        let mut particles = [(0.0, 0.0), (8.0, 0.0), (500.0, 0.0), (508.0, 0.0)]
+            .map(|(x, y)| Particle::new(Vec2::new(x, y), Vec2::ZERO, 3.0, 1.0, [1.0; 3]));
+        let mut grid = SpatialGrid::new(20.0);
+        let mut broad = CachedBroadPhase::new(TccdBroadPhase);
+        let mut pairs = CandidatePairs::default();
+
+        grid.rebuild(&particles);
+        broad.candidates(&grid, &particles, 0.1, &mut pairs);
+        assert_eq!(pairs.pairs(), [(0, 1), (2, 3)]);
+
+        particles[3].position.x = 560.0;
+        grid.rebuild(&particles);
+        pairs.clear();
+        broad.candidates(&grid, &particles, 0.1, &mut pairs);
+
+        // The first pair's cells are untouched; the second pair no longer neighbors.
+        assert_eq!(pairs.pairs(), [(0, 1)]);
+        assert_eq!(broad.hits, 2);
+    }
+}
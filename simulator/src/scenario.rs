@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use engine::particle::Particle;
+use glam::Vec2;
+
+use crate::miscs::ParticleRow;
+
+/// Reads a scenario: a CSV with the same columns as the particle snapshots, one row per particle.
+/// Rows are used in file order, so the `particle_id` column is only kept for reference.
+pub fn load(path: &Path) -> anyhow::Result<Vec<Particle>> {
+    let rows = read_rows(path)?;
+
+    if rows.is_empty() {
+        bail!("scenario {} has no particles", path.display());
+    }
+
+    Ok(rows
+        .iter()
+        .map(|r| {
+            Particle::new(
+                Vec2::new(r.x, r.y),
+                Vec2::new(r.vx, r.vy),
+                r.radius,
+                r.mass,
+                [1.0; 3],
+            )
+        })
+        .collect())
+}
+
+pub fn save(path: &Path, rows: &[ParticleRow]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("creating scenario {}", path.display()))?;
+
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// All rows of a recorded particles CSV (or a scenario).
+pub fn read_rows(path: &Path) -> anyhow::Result<Vec<ParticleRow>> {
+    csv::Reader::from_path(path)
+        .with_context(|| format!("reading {}", path.display()))?
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("parsing {}", path.display()))
+}
+
+/// The rows of `frame` for the `involved` particles followed by every other particle whose
+/// center lies within `radius` of one of them. Involved particles come first, in the given
+/// order, so they keep indices `0..involved.len()` when the scenario is loaded.
+pub fn neighborhood(
+    rows: &[ParticleRow],
+    frame: u64,
+    involved: &[usize],
+    radius: f32,
+) -> anyhow::Result<Vec<ParticleRow>> {
+    let at_frame = rows.iter().filter(|r| r.frame == frame).collect::<Vec<_>>();
+
+    if at_frame.is_empty() {
+        bail!("no particle rows recorded for frame {frame}");
+    }
+
+    let mut picked = Vec::with_capacity(involved.len());
+
+    for &id in involved {
+        let row = at_frame
+            .iter()
+            .find(|r| r.particle_id == id)
+            .with_context(|| format!("particle {id} is missing from frame {frame}"))?;
+
+        picked.push((*row).clone());
+    }
+
+    let centers = picked
+        .iter()
+        .map(|r| Vec2::new(r.x, r.y))
+        .collect::<Vec<_>>();
+
+    picked.extend(
+        at_frame
+            .into_iter()
+            .filter(|r| !involved.contains(&r.particle_id))
+            .filter(|r| {
+                let p = Vec2::new(r.x, r.y);
+                centers.iter().any(|c| c.distance(p) <= radius)
+            })
+            .cloned(),
+    );
+
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(frame: u64, particle_id: usize, x: f32) -> ParticleRow {
+        let p = Particle::new(Vec2::new(x, 0.0), Vec2::ZERO, 1.0, 1.0, [1.0; 3]);
+        ParticleRow::new(frame, 0.0, particle_id, &p)
+    }
+
+    #[test]
+    fn neighborhood_keeps_involved_first_and_nearby_only() {
+        let rows = [
+            row(1, 0, 0.0),
+            row(1, 1, 5.0),
+            row(1, 2, 100.0),
+            row(1, 3, 10.0),
+            row(2, 0, 0.0),
+        ];
+
+        let picked = neighborhood(&rows, 1, &[3, 0], 6.0).unwrap();
+        let ids = picked.iter().map(|r| r.particle_id).collect::<Vec<_>>();
+
+        assert_eq!(ids, [3, 0, 1]);
+        assert!(picked.iter().all(|r| r.frame == 1));
+    }
+
+    #[test]
+    fn neighborhood_rejects_missing_particles() {
+        let rows = [row(1, 0, 0.0)];
+
+        assert!(neighborhood(&rows, 1, &[4], 1.0).is_err());
+        assert!(neighborhood(&rows, 7, &[0], 1.0).is_err());
+    }
+}
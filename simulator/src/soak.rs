@@ -0,0 +1,222 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use engine::{Backend, Bounds, StepResult, camera::Camera, headless::Headless, render::Renderer};
+use glam::Vec2;
+use simulator::{invariants::Violations, miscs, thermo};
+use winit::dpi::PhysicalSize;
+
+use crate::TCcdSim;
+
+/// Written into the output directory.
+const REPORT_NAME: &str = "soak_report.txt";
+
+pub struct SoakConfig {
+    pub bounds: Bounds,
+    pub duration: Duration,
+    pub check_interval: Duration,
+    pub shard_frames: u64,
+    /// Directory and frame interval of the PNGs saved for a visual check of the run.
+    pub capture: Option<(PathBuf, u64)>,
+    pub seed: u64,
+}
+
+/// Runs the simulation without a window for a wall-clock duration, checking invariants
+/// periodically and writing a health report at the end.
+pub fn run(sim: TCcdSim, config: SoakConfig) -> anyhow::Result<()> {
+    log::info!("Seed {}", config.seed);
+
+    let dt = 1.0 / sim.fps as f32;
+    let mut run = Headless::new(sim, config.bounds, dt, config.seed);
+    // Sized after `init`, which places the particles that will be drawn.
+    let mut renderer = match &config.capture {
+        Some((dir, _)) => {
+            let particles = run.simulation().particles.len();
+
+            Some(headless_renderer(dir, config.bounds, particles)?)
+        }
+        None => None,
+    };
+    let start = Instant::now();
+    let initial_energy = thermo::kinetic_energy(&run.simulation().particles);
+    let mut last_check = start;
+    let mut checks = 0;
+    let mut failed_checks = 0;
+    let mut worst = Violations::default();
+    let mut first_failure = None;
+    let mut shard = 0;
+
+    log::info!("Soak test running for {:?}", config.duration);
+
+    while start.elapsed() < config.duration {
+        let result = run.step();
+        let sim = run.simulation_mut();
+        let frame = sim.solver.recorder.frame;
+
+        if config.shard_frames > 0 && frame.is_multiple_of(config.shard_frames) {
+            shard += 1;
+            sim.solver.recorder.rotate(shard);
+        }
+
+        if let (Some(renderer), Some((dir, every))) = (&mut renderer, &config.capture)
+            && frame.is_multiple_of(*every)
+        {
+            capture(renderer, sim, &dir.join(format!("frame_{frame:06}.png")));
+        }
+
+        if result == StepResult::Exit {
+            break;
+        }
+
+        if last_check.elapsed() < config.check_interval {
+            continue;
+        }
+
+        last_check = Instant::now();
+        checks += 1;
+
+        let violations = Violations::check(&sim.particles, sim.solver.boundary(), &config.bounds);
+
+        if violations.total() > 0 {
+            failed_checks += 1;
+            first_failure.get_or_insert(frame);
+            log::warn!("Frame {frame}: {violations:?}");
+        }
+
+        if violations.total() > worst.total() {
+            worst = violations;
+        }
+    }
+
+    let sim = run.finish();
+    let final_energy = thermo::kinetic_energy(&sim.particles);
+    let elapsed_s = start.elapsed().as_secs_f32();
+    let report = [
+        format!("elapsed_s: {elapsed_s:.1}"),
+        format!("frames: {}", sim.solver.recorder.frame),
+        format!("sim_time_s: {:.3}", sim.solver.recorder.time_s),
+        format!(
+            "real_time_factor: {:.3}",
+            sim.solver.recorder.time_s / elapsed_s
+        ),
+        format!("shards: {}", shard + 1),
+        format!("checks: {checks}"),
+        format!("failed_checks: {failed_checks}"),
+        format!(
+            "first_failure_frame: {}",
+            first_failure.map_or("none".to_string(), |f| f.to_string())
+        ),
+        format!("worst_overlaps: {}", worst.overlaps),
+        format!("worst_out_of_bounds: {}", worst.out_of_bounds),
+        format!("worst_non_finite: {}", worst.non_finite),
+        format!("summation: {}", thermo::SUMMATION),
+        format!("initial_kinetic_energy: {initial_energy:e}"),
+        format!("final_kinetic_energy: {final_energy:e}"),
+        format!(
+            "relative_energy_drift: {}",
+            relative_drift(initial_energy, final_energy)
+                .map_or("none".to_string(), |d| format!("{d:e}"))
+        ),
+    ]
+    .join("\n");
+
+    let path = miscs::output_path(REPORT_NAME);
+
+    fs::write(&path, report + "\n")?;
+    log::info!("Soak test finished, report written to {}", path.display());
+
+    Ok(())
+}
+
+/// `None` for a system that started at rest, which has no scale to measure drift against.
+fn relative_drift(initial: f64, last: f64) -> Option<f64> {
+    (initial != 0.0).then(|| (last - initial) / initial)
+}
+
+/// A renderer the size of `bounds` with room for every one of `particles`; the default instance
+/// capacity would silently drop the rest from the captures.
+fn headless_renderer(dir: &Path, bounds: Bounds, particles: usize) -> anyhow::Result<Renderer> {
+    let size = PhysicalSize::new(bounds.width as u32, bounds.height as u32);
+    let mut renderer = pollster::block_on(Renderer::headless(size, 1, Backend::default(), 1))?;
+
+    fs::create_dir_all(dir)?;
+    renderer.set_instance_capacity(particles.max(1));
+    renderer.set_camera(Camera::fit(
+        Vec2::new(bounds.width, bounds.height),
+        Vec2::new(size.width as f32, size.height as f32),
+        0.0,
+    ));
+
+    Ok(renderer)
+}
+
+fn capture(renderer: &mut Renderer, sim: &TCcdSim, path: &Path) {
+    renderer.upload_simulation(sim);
+
+    if let Some(image) = renderer.render_to_image()
+        && let Err(e) = image.save(path)
+    {
+        log::error!("Failed to write {}: {e}", path.display());
+    }
+}
+
+/// Parses durations like `90s`, `45m`, `8h` or `1h30m`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(format!("unknown unit '{c}' in '{s}'")),
+        };
+        let value = digits
+            .parse::<u64>()
+            .map_err(|_| format!("missing number before '{c}' in '{s}'"))?;
+
+        total += value * unit;
+        digits.clear();
+    }
+
+    if !digits.is_empty() {
+        return Err(format!("missing unit after '{digits}' in '{s}'"));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compound_durations() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 2d1s "), Ok(Duration::from_secs(172_801)));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5w").is_err());
+    }
+
+    #[test]
+    fn drift_needs_an_initial_energy() {
+        assert_eq!(relative_drift(2.0, 3.0), Some(0.5));
+        assert_eq!(relative_drift(0.0, 1.0), None);
+    }
+}
@@ -1,14 +1,30 @@
-use engine::{Bounds, particle::Particle};
+use engine::{
+    Bounds,
+    boundary::{self, BoundaryCondition, Circular, Open, Periodic, Polygon, ReflectiveBox},
+    particle::Particle,
+    trajectory,
+};
 use glam::Vec2;
 
 use crate::{
-    detector::{CellListDetector, Detector, SweptAabbDetector, TccdDetector},
-    miscs::{DetectionType, Recorder, RecorderType},
+    alloc::{self, Section},
+    chain::ChainTracker,
+    detector::{
+        BroadPhase, CellListBroadPhase, Detector, KdTreeBroadPhase, ParallelNarrowPhase,
+        PhaseTimes, SweptAabbBroadPhase, TccdBroadPhase,
+    },
+    event::{Event, PairEvent, WallEvent},
+    miscs::{BoundaryType, DetectionType, Recorder, RecorderType},
+    observer::{FrameObserver, FrameView},
+    pair_cache,
     spatial::SpatialGrid,
 };
 
 const EPS_T: f32 = 1e-5;
 const MAX_ITER: usize = 100;
+/// NaN/Inf checks are always on in debug builds and can be enabled in release builds with the
+/// `finite-checks` feature.
+const CHECK_FINITE: bool = cfg!(any(debug_assertions, feature = "finite-checks"));
 
 #[derive(Debug, Clone, Copy)]
 pub enum Collision {
@@ -33,9 +49,26 @@ impl From<(f32, Collision)> for Toi {
 
 pub struct Solver {
     pub recorder: Recorder,
+    pub iterations: usize,
+    /// Contact points and normals of the collisions resolved during the last solve.
+    pub contacts: Vec<(Vec2, Vec2)>,
 
     grid: SpatialGrid,
-    detector: Box<dyn Detector>,
+    detector: Detector,
+    d_type: DetectionType,
+    boundary: Box<dyn BoundaryCondition>,
+    resting_threshold: f32,
+    /// Share of the approach speed two particles separate with, 1 for elastic collisions.
+    restitution: f32,
+    /// Coulomb coefficient bounding the tangential impulse by this share of the normal one.
+    friction: f32,
+    gravity: Vec2,
+    chains: Option<ChainTracker>,
+    observers: Vec<Box<dyn FrameObserver>>,
+    /// Events of the current solve, kept only while there are observers to hand them to.
+    events: Vec<Event>,
+    /// Which particles were held by a resting contact during the last solve.
+    resting: Vec<bool>,
 }
 
 impl Solver {
@@ -43,168 +76,611 @@ impl Solver {
         cell_size: f32,
         r_type: Option<RecorderType>,
         d_type: DetectionType,
+        b_type: BoundaryType,
+        polygon_sides: usize,
         particle_count: u64,
     ) -> Self {
         Self {
             grid: SpatialGrid::new(cell_size),
             recorder: Recorder::new(r_type, d_type, particle_count),
-            detector: match d_type {
-                DetectionType::CellList => Box::new(CellListDetector),
-                DetectionType::Tccd => Box::new(TccdDetector),
-                DetectionType::SweptAabb => Box::new(SweptAabbDetector),
+            iterations: 0,
+            contacts: Vec::new(),
+            detector: Detector::new(broad_phase(d_type), Box::new(ParallelNarrowPhase)),
+            d_type,
+            boundary: match b_type {
+                BoundaryType::Box => Box::new(ReflectiveBox),
+                BoundaryType::Periodic => Box::new(Periodic),
+                BoundaryType::Open => Box::new(Open),
+                BoundaryType::Circle => Box::new(Circular),
+                BoundaryType::Polygon => Box::new(Polygon::regular(polygon_sides)),
             },
+            resting_threshold: 0.0,
+            restitution: 1.0,
+            friction: 0.0,
+            gravity: Vec2::ZERO,
+            chains: None,
+            observers: Vec::new(),
+            events: Vec::new(),
+            resting: Vec::new(),
+        }
+    }
+
+    /// Contacts approaching slower than `threshold` are treated as resting instead of bouncing.
+    pub fn with_resting_threshold(mut self, threshold: f32) -> Self {
+        self.resting_threshold = threshold;
+        self
+    }
+
+    /// Replaces the recorder made from the constructor's recorder type, e.g. to tag its files
+    /// differently.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Reuses each particle's broad-phase candidates while the grid cells they come from keep
+    /// their occupants.
+    pub fn with_pair_cache(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.detector = Detector::new(
+                pair_cache::cached(self.d_type),
+                Box::new(ParallelNarrowPhase),
+            );
+        }
+
+        self
+    }
+
+    /// Groups the collisions of every frame into chains of dependent impacts and records each
+    /// resolution step with the velocities right after it.
+    pub fn with_chain_trace(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.recorder.enable_chain_trace();
+            self.chains = Some(ChainTracker::default());
+        }
+
+        self
+    }
+
+    /// Calls `observer` after every solve with the resulting particles and the collisions
+    /// resolved on the way.
+    pub fn add_observer(&mut self, observer: Box<dyn FrameObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Flushes the recorder and lets the observers wrap up; call once when the run ends.
+    pub fn finish(&mut self) {
+        self.recorder.finish();
+
+        for observer in &mut self.observers {
+            observer.finish();
         }
     }
 
+    pub fn grid(&self) -> &SpatialGrid {
+        &self.grid
+    }
+
+    pub fn detection_type(&self) -> DetectionType {
+        self.d_type
+    }
+
+    /// Replaces the grid with one of `cell_size`; the next solve fills it.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.grid = SpatialGrid::new(cell_size);
+    }
+
+    pub fn restitution(&self) -> f32 {
+        self.restitution
+    }
+
+    /// Coefficient of restitution between particles; walls stay elastic.
+    pub fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution.clamp(0.0, 1.0);
+    }
+
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    /// Coefficient of friction for particle and wall contacts, 0 for frictionless ones. Without
+    /// rotation, friction only slows the tangential sliding, down to sticking at most.
+    pub fn set_friction(&mut self, friction: f32) {
+        self.friction = friction.max(0.0);
+    }
+
+    pub fn gravity(&self) -> Vec2 {
+        self.gravity
+    }
+
+    /// Whether particle `i` was held by a resting contact during the last solve.
+    pub fn is_resting(&self, i: usize) -> bool {
+        self.resting.get(i).copied().unwrap_or(false)
+    }
+
+    /// Acceleration of every movable particle. Paths between collisions become parabolas, which
+    /// the detectors follow exactly.
+    pub fn set_gravity(&mut self, gravity: Vec2) {
+        self.gravity = gravity;
+    }
+
+    /// Rebuilds the grid from the current positions. `solve` leaves it at the state of its last
+    /// iteration, so call this before querying `grid()` for neighbors or raycasts.
+    pub fn sync_grid(&mut self, particles: &[Particle]) {
+        self.grid.rebuild(particles);
+    }
+
+    /// Time until the next collision if it happens within `horizon`, leaving the particles
+    /// untouched.
+    pub fn next_event(
+        &mut self,
+        particles: &[Particle],
+        bounds: &Bounds,
+        horizon: f32,
+    ) -> Option<f32> {
+        self.grid.set_period(self.boundary.period(bounds));
+        self.grid.rebuild(particles);
+        self.grid.set_sweep_padding(self.sweep_padding(horizon));
+        self.detector
+            .find_min_toi(
+                &self.grid,
+                particles,
+                self.boundary.as_ref(),
+                bounds,
+                horizon,
+                self.gravity,
+            )
+            .map(|toi| toi.time)
+    }
+
+    /// Share of broad-phase queries answered from the pair cache, if it is on.
+    pub fn pair_cache_hit_rate(&self) -> Option<f32> {
+        self.detector.cache_hit_rate()
+    }
+
+    /// Time the last solve spent in the broad and narrow phases, across all its iterations.
+    pub fn phase_times(&self) -> PhaseTimes {
+        self.detector.times
+    }
+
+    pub fn boundary(&self) -> &dyn BoundaryCondition {
+        self.boundary.as_ref()
+    }
+
     pub fn solve(&mut self, particles: &mut [Particle], bounds: &Bounds, mut dt: f32) {
+        let _alloc = alloc::scope(Section::Solver);
+        let frame_dt = dt;
+
+        self.iterations = 0;
+        self.contacts.clear();
+        self.events.clear();
+        self.resting.clear();
+        self.resting.resize(particles.len(), false);
+        self.detector.times = PhaseTimes::default();
+
+        if let Some(chains) = &mut self.chains {
+            chains.begin_frame();
+        }
+
+        self.grid.set_period(self.boundary.period(bounds));
+        self.grid.set_sweep_padding(self.sweep_padding(dt));
+
+        let mut last_event = None;
+
         for _ in 0..MAX_ITER {
+            self.iterations += 1;
+
             if dt <= EPS_T {
-                Self::advance_all(particles, dt);
+                self.advance_all(particles, dt);
                 break;
             }
 
             self.grid.rebuild(particles);
 
-            let min_toi = self
-                .detector
-                .find_min_toi(&mut self.grid, particles, bounds, dt);
+            let min_toi = self.detector.find_min_toi(
+                &self.grid,
+                particles,
+                self.boundary.as_ref(),
+                bounds,
+                dt,
+                self.gravity,
+            );
+
+            if CHECK_FINITE && let Some(toi) = min_toi {
+                assert!(
+                    toi.time.is_finite(),
+                    "non-finite TOI {toi:?} (last event: {last_event:?})"
+                );
+            }
 
             match min_toi {
                 Some(toi) => {
-                    Self::advance_all(particles, toi.time);
-                    self.resolve_collision(particles, bounds, toi);
+                    self.advance_all(particles, toi.time);
+
+                    if let Some(chains) = &mut self.chains {
+                        chains.advance(toi.time);
+                    }
+
+                    if let Some(event) = self.resolve_collision(particles, bounds, toi) {
+                        if CHECK_FINITE {
+                            let (i, j) = event.particles();
+
+                            Self::assert_finite(
+                                particles,
+                                [Some(i), j].into_iter().flatten(),
+                                &event,
+                            );
+                        }
+
+                        last_event = Some(event);
+
+                        let p = &particles[event.particles().0];
+
+                        self.contacts
+                            .push((p.position + event.normal() * p.radius, event.normal()));
+
+                        if event.is_resting() {
+                            let (i, j) = event.particles();
+
+                            for k in [Some(i), j].into_iter().flatten() {
+                                self.resting[k] = true;
+                            }
+                        }
+
+                        self.recorder.write_event(&event);
+
+                        if !self.observers.is_empty() {
+                            self.events.push(event);
+                        }
+
+                        if let Some(chains) = &mut self.chains {
+                            let step = chains.link(&event);
+                            self.recorder.write_chain_step(step, &event, particles);
+                        }
+                    }
 
                     dt -= toi.time;
                 }
                 None => {
-                    Self::advance_all(particles, dt);
+                    self.advance_all(particles, dt);
                     break;
                 }
             }
         }
 
-        Self::clamp_particles(particles, bounds);
+        Self::clamp_particles(self.boundary.as_ref(), particles, bounds);
+
+        if CHECK_FINITE {
+            Self::assert_finite(particles, 0..particles.len(), &last_event);
+        }
+
+        let view = FrameView {
+            particles,
+            events: &self.events,
+            bounds,
+            dt: frame_dt,
+        };
+
+        for observer in &mut self.observers {
+            observer.observe(&view);
+        }
+    }
+
+    fn assert_finite(
+        particles: &[Particle],
+        indices: impl IntoIterator<Item = usize>,
+        last_event: &dyn std::fmt::Debug,
+    ) {
+        for i in indices {
+            let p = &particles[i];
+
+            assert!(
+                p.position.is_finite() && p.velocity.is_finite(),
+                "particle {i} became non-finite: {p:?} (last event: {last_event:?})"
+            );
+        }
     }
 
-    fn resolve_collision(&mut self, particles: &mut [Particle], bounds: &Bounds, toi: Toi) {
+    fn resolve_collision(
+        &self,
+        particles: &mut [Particle],
+        bounds: &Bounds,
+        toi: Toi,
+    ) -> Option<Event> {
         match toi.collision {
             Collision::Pair(i, j) => {
                 let p1 = &particles[i];
                 let p2 = &particles[j];
 
-                let n = p2.position - p1.position;
+                // Across a periodic seam, the nearer image is the one that was hit.
+                let n = match self.boundary.period(bounds) {
+                    Some(period) => boundary::minimum_image(p2.position - p1.position, period),
+                    None => p2.position - p1.position,
+                };
                 let dist2 = n.dot(n);
 
                 if dist2 == 0.0 {
-                    return;
+                    return None;
                 }
 
                 let n_hat = n / dist2.sqrt();
                 let v_rel_n = (p2.velocity - p1.velocity).dot(n_hat);
 
                 if v_rel_n >= 0.0 {
-                    return;
+                    return None;
                 }
 
                 let (m1, m2) = (p1.mass, p2.mass);
-                let impulse = (2.0 * m1 * m2 / (m1 + m2)) * v_rel_n * n_hat;
+                // Frozen particles have infinite mass and act like immovable obstacles.
+                let reduced_mass = match (m1.is_finite(), m2.is_finite()) {
+                    (true, true) => m1 * m2 / (m1 + m2),
+                    (false, true) => m2,
+                    (true, false) => m1,
+                    (false, false) => return None,
+                };
+                let resting = -v_rel_n < self.resting_threshold;
 
-                particles[i].velocity += impulse / m1;
-                particles[j].velocity -= impulse / m2;
+                // Resting contacts only cancel the approach instead of bouncing, then get pushed
+                // apart, so they do not generate an endless stream of near-zero TOIs.
+                let factor = if resting { 1.0 } else { 1.0 + self.restitution };
+                let impulse = (factor * reduced_mass) * v_rel_n * n_hat;
+                let v_rel_t = (p2.velocity - p1.velocity) - v_rel_n * n_hat;
+                let friction = self.friction_impulse(v_rel_t, reduced_mass, impulse.length());
 
-                let v_rel_n_after = (particles[j].velocity - particles[i].velocity).dot(n_hat);
+                particles[i].velocity += (impulse + friction) / m1;
+                particles[j].velocity -= (impulse + friction) / m2;
+
+                if resting {
+                    let overlap = particles[i].radius + particles[j].radius - dist2.sqrt();
+
+                    if overlap > 0.0 {
+                        particles[i].position -= n_hat * overlap * reduced_mass / m1;
+                        particles[j].position += n_hat * overlap * reduced_mass / m2;
+                    }
+                }
 
-                self.recorder.write_event_pair((
-                    toi.time,
-                    i,
-                    j,
-                    n_hat.x,
-                    n_hat.y,
-                    v_rel_n,
-                    v_rel_n_after,
-                ));
+                let v_rel_n_after = (particles[j].velocity - particles[i].velocity).dot(n_hat);
+                let event = PairEvent::new(toi.time, i, j)
+                    .normal(n_hat)
+                    .vrel_n(v_rel_n, v_rel_n_after);
+
+                Some(match resting {
+                    true => Event::RestingPair(event),
+                    false => Event::Pair(event),
+                })
             }
             Collision::Wall(i) => {
                 let p = &mut particles[i];
-                let (hw, hh) = bounds.half_extents();
-
-                let (x_min, x_max) = (-hw + p.radius, hw - p.radius);
-                let (y_min, y_max) = (-hh + p.radius, hh - p.radius);
-
-                let n = if p.position.x <= x_min {
-                    Vec2::new(-1.0, 0.0)
-                } else if p.position.x >= x_max {
-                    Vec2::new(1.0, 0.0)
-                } else if p.position.y <= y_min {
-                    Vec2::new(0.0, -1.0)
-                } else {
-                    Vec2::new(0.0, 1.0)
-                };
+                let contact = self.boundary.resolve(p, bounds)?;
+                let resting = contact.vn_before.abs() < self.resting_threshold;
 
-                let vn_before = p.velocity.dot(n);
-
-                if p.position.x <= x_min && p.velocity.x < 0.0 {
-                    p.position.x = x_min;
-                    p.velocity.x *= -1.0;
-                } else if p.position.x >= x_max && p.velocity.x > 0.0 {
-                    p.position.x = x_max;
-                    p.velocity.x *= -1.0;
+                if resting {
+                    p.velocity -= p.velocity.dot(contact.normal) * contact.normal;
                 }
 
-                if p.position.y <= y_min && p.velocity.y < 0.0 {
-                    p.position.y = y_min;
-                    p.velocity.y *= -1.0;
-                } else if p.position.y >= y_max && p.velocity.y > 0.0 {
-                    p.position.y = y_max;
-                    p.velocity.y *= -1.0;
-                }
+                let vn_after = p.velocity.dot(contact.normal);
+                let v_t = p.velocity - vn_after * contact.normal;
 
-                let vn_after = p.velocity.dot(n);
-                let wall = if p.position.x <= x_min {
-                    "left"
-                } else if p.position.x >= x_max {
-                    "right"
-                } else if p.position.y <= y_min {
-                    "bottom"
-                } else {
-                    "top"
-                };
+                // Per unit mass, as the wall's infinite mass leaves the particle's to cancel out.
+                p.velocity -= self.friction_impulse(v_t, 1.0, (vn_after - contact.vn_before).abs());
+
+                let event = WallEvent::new(toi.time, i, contact.wall)
+                    .normal(contact.normal)
+                    .vn(contact.vn_before, vn_after);
 
-                self.recorder
-                    .write_event_wall((toi.time, i, wall, n.x, n.y, vn_before, vn_after));
+                Some(match resting {
+                    true => Event::RestingWall(event),
+                    false => Event::Wall(event),
+                })
             }
         }
     }
 
+    /// Impulse along the tangential relative velocity `v_t` that slows the sliding, at most
+    /// `friction` times the normal impulse and never enough to reverse it.
+    fn friction_impulse(&self, v_t: Vec2, reduced_mass: f32, normal_impulse: f32) -> Vec2 {
+        let speed = v_t.length();
+
+        if self.friction == 0.0 || speed == 0.0 {
+            return Vec2::ZERO;
+        }
+
+        (reduced_mass * speed).min(self.friction * normal_impulse) * v_t / speed
+    }
+
     #[inline]
-    fn advance_all(particles: &mut [Particle], dt: f32) {
+    fn advance_all(&self, particles: &mut [Particle], dt: f32) {
+        if self.gravity == Vec2::ZERO {
+            for p in particles {
+                p.position += p.velocity * dt;
+            }
+
+            return;
+        }
+
         for p in particles {
-            p.position += p.velocity * dt;
+            trajectory::advance(p, trajectory::acceleration(p, self.gravity), dt);
         }
     }
 
-    fn clamp_particles(particles: &mut [Particle], bounds: &Bounds) {
-        let (hw, hh) = bounds.half_extents();
+    /// How far a falling particle strays within `dt` from the straight sweep the broad phases
+    /// search along.
+    fn sweep_padding(&self, dt: f32) -> f32 {
+        0.5 * self.gravity.length() * dt * dt
+    }
 
+    fn clamp_particles(
+        boundary: &dyn BoundaryCondition,
+        particles: &mut [Particle],
+        bounds: &Bounds,
+    ) {
         for p in particles {
-            let (x_min, x_max) = (-hw + p.radius, hw - p.radius);
-            let (y_min, y_max) = (-hh + p.radius, hh - p.radius);
-
-            if p.position.x < x_min {
-                p.position.x = x_min;
-                p.velocity.x *= -1.0;
-            } else if p.position.x > x_max {
-                p.position.x = x_max;
-                p.velocity.x *= -1.0;
-            }
+            boundary.clamp(p, bounds);
+        }
+    }
+}
 
-            if p.position.y < y_min {
-                p.position.y = y_min;
-                p.velocity.y *= -1.0;
-            } else if p.position.y > y_max {
-                p.position.y = y_max;
-                p.velocity.y *= -1.0;
-            }
+fn broad_phase(d_type: DetectionType) -> Box<dyn BroadPhase> {
+    match d_type {
+        DetectionType::CellList => Box::new(CellListBroadPhase),
+        DetectionType::Tccd => Box::new(TccdBroadPhase),
+        DetectionType::SweptAabb => Box::new(SweptAabbBroadPhase),
+        DetectionType::KdTree => Box::new(KdTreeBroadPhase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Logs what it was handed: the particle count of every frame, then `None` on finish.
+    struct Log(Arc<Mutex<Vec<Option<usize>>>>);
+
+    impl FrameObserver for Log {
+        fn observe(&mut self, frame: &FrameView) {
+            self.0.lock().unwrap().push(Some(frame.particles.len()));
+        }
+
+        fn finish(&mut self) {
+            self.0.lock().unwrap().push(None);
+        }
+    }
+
+    #[test]
+    fn observers_see_every_solve_then_finish() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let mut particles = [Particle::new(Vec2::ZERO, Vec2::X, 5.0, 1.0, [1.0; 3])];
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 1);
+
+        solver.add_observer(Box::new(Log(log.clone())));
+        solver.solve(&mut particles, &bounds, 0.1);
+        solver.solve(&mut particles, &bounds, 0.1);
+        solver.finish();
+
+        assert_eq!(*log.lock().unwrap(), [Some(1), Some(1), None]);
+    }
+
+    #[test]
+    fn inelastic_collisions_stop_equal_masses() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let mut particles = [(-20.0, 10.0), (20.0, -10.0)].map(|(x, vx)| {
+            Particle::new(Vec2::new(x, 0.0), Vec2::new(vx, 0.0), 5.0, 1.0, [1.0; 3])
+        });
+        let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 2);
+
+        solver.set_restitution(0.0);
+        solver.solve(&mut particles, &bounds, 2.0);
+
+        for p in particles {
+            assert!(p.velocity.length() < 1e-4, "{:?}", p.velocity);
         }
     }
+
+    #[test]
+    fn friction_slows_grazing_pairs_and_keeps_momentum() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let kinetic_energy = |friction: f32| {
+            let mut particles = [(-20.0, 10.0, 1.0), (20.0, -10.0, -1.0)].map(|(x, vx, vy)| {
+                Particle::new(Vec2::new(x, 0.0), Vec2::new(vx, vy), 5.0, 1.0, [1.0; 3])
+            });
+            let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 2);
+
+            solver.set_friction(friction);
+            solver.solve(&mut particles, &bounds, 2.0);
+
+            let momentum = particles[0].velocity + particles[1].velocity;
+
+            assert!(momentum.length() < 1e-4, "{momentum:?}");
+            particles
+                .iter()
+                .map(|p| 0.5 * p.velocity.length_squared())
+                .sum::<f32>()
+        };
+
+        assert!(kinetic_energy(0.5) < kinetic_energy(0.0) - 0.1);
+    }
+
+    #[test]
+    fn wall_friction_slows_sliding_without_reversing_it() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let bounce = |friction: f32| {
+            let mut particles = [Particle::new(
+                Vec2::new(0.0, -80.0),
+                Vec2::new(30.0, -10.0),
+                5.0,
+                1.0,
+                [1.0; 3],
+            )];
+            let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 1);
+
+            solver.set_friction(friction);
+            solver.solve(&mut particles, &bounds, 2.0);
+            particles[0].velocity
+        };
+
+        // The bounce flips a normal speed of 10, a normal impulse of 20 per unit mass.
+        assert!((bounce(0.5) - Vec2::new(20.0, 10.0)).length() < 1e-4);
+        assert!((bounce(2.0) - Vec2::new(0.0, 10.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn periodic_pairs_collide_across_the_seam() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let mut particles = [(-90.0, -10.0), (90.0, 10.0)].map(|(x, vx)| {
+            Particle::new(Vec2::new(x, 0.0), Vec2::new(vx, 0.0), 5.0, 1.0, [1.0; 3])
+        });
+        let mut solver = Solver::new(
+            20.0,
+            None,
+            DetectionType::Tccd,
+            BoundaryType::Periodic,
+            6,
+            2,
+        )
+        .with_pair_cache(true);
+
+        // Through the seam they are 20 apart and touch after half a second, then bounce back.
+        solver.solve(&mut particles, &bounds, 1.0);
+
+        assert_eq!(particles.map(|p| p.velocity.x), [10.0, -10.0]);
+        assert!((particles[0].position.x + 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn falling_particles_bounce_without_losing_energy() {
+        let bounds = Bounds {
+            width: 200.0,
+            height: 200.0,
+        };
+        let mut particles = [Particle::new(Vec2::ZERO, Vec2::ZERO, 5.0, 1.0, [1.0; 3])];
+        let mut solver = Solver::new(20.0, None, DetectionType::Tccd, BoundaryType::Box, 6, 1);
+
+        solver.set_gravity(Vec2::new(0.0, -100.0));
+        solver.solve(&mut particles, &bounds, 1.5);
+
+        let p = particles[0];
+        // Kinetic plus potential energy, zero at the start.
+        let energy = 0.5 * p.velocity.length_squared() + 100.0 * p.position.y;
+
+        assert!(p.velocity.y > 0.0 && p.position.y > -95.0, "{p:?}");
+        assert!(energy.abs() < 0.5, "{energy}");
+    }
 }
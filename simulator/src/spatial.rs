@@ -1,11 +1,21 @@
-use engine::particle::Particle;
+use std::{collections::HashSet, ops::RangeInclusive};
+
+use engine::{boundary, particle::Particle};
 use glam::{IVec2, Vec2};
-use std::collections::HashMap;
+
+use crate::alloc::{self, Section};
 
 pub struct SpatialGrid {
     cell_size: f32,
-    cells: HashMap<IVec2, Vec<usize>>,
-    r_max: f32,
+    cells: CellTable,
+    /// Added to every swept radius to cover paths that bend away from the straight sweep, such
+    /// as the drift gravity adds over a step.
+    sweep_padding: f32,
+    /// Largest radius seen by the last `rebuild`, so ray queries know how far to look sideways.
+    max_radius: f32,
+    /// Size of a periodic domain centered on the origin. Particles are filed under the cell of
+    /// their wrapped position, and lookups past an edge continue at the opposite one.
+    period: Option<Vec2>,
 }
 
 impl SpatialGrid {
@@ -24,28 +34,201 @@ impl SpatialGrid {
     pub fn new(cell_size: f32) -> Self {
         Self {
             cell_size,
-            cells: HashMap::new(),
-            r_max: 0.0,
+            cells: CellTable::default(),
+            sweep_padding: 0.0,
+            max_radius: 0.0,
+            period: None,
         }
     }
 
     pub fn rebuild(&mut self, particles: &[Particle]) {
-        self.cells.clear();
+        let _alloc = alloc::scope(Section::Grid);
+
+        self.cells.reset(particles.len());
+        self.max_radius = 0.0;
 
         for (i, p) in particles.iter().enumerate() {
-            let c = self.cell_coord(p.position);
+            let c = self.home_cell(p.position);
+
+            self.cells.insert(c, i);
+            self.max_radius = self.max_radius.max(p.radius);
+        }
+    }
+
+    pub fn set_sweep_padding(&mut self, padding: f32) {
+        self.sweep_padding = padding;
+    }
+
+    pub fn sweep_padding(&self) -> f32 {
+        self.sweep_padding
+    }
+
+    /// Makes the grid wrap around a domain of `period`, or stop wrapping with `None`. Takes
+    /// effect at the next `rebuild`.
+    pub fn set_period(&mut self, period: Option<Vec2>) {
+        self.period = period;
+    }
+
+    pub fn period(&self) -> Option<Vec2> {
+        self.period
+    }
 
-            self.cells.entry(c).or_default().push(i);
+    /// Particles whose centers lie within `r` of `pos`. Cells come from the last `rebuild`, so
+    /// `particles` should not have moved since.
+    pub fn neighbors_within<'a>(
+        &'a self,
+        particles: &'a [Particle],
+        pos: Vec2,
+        r: f32,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.rect_keys(pos - r, pos + r)
+            .flat_map(|c| self.cells.get(c))
+            .filter(move |&j| self.offset(pos, particles[j].position).length() <= r)
+    }
+
+    /// First particle hit by a ray from `pos` along `dir` within distance `max_t`, as its index
+    /// and the distance to its rim (zero if `pos` is inside it). In a periodic domain the ray
+    /// continues across the seams and meets the image of each particle nearest to it.
+    pub fn raycast(
+        &self,
+        particles: &[Particle],
+        pos: Vec2,
+        dir: Vec2,
+        max_t: f32,
+    ) -> Option<(usize, f32)> {
+        let dir = dir.try_normalize()?;
+        let k = (self.max_radius / self.cell_size).ceil() as i32;
+        let mut best: Option<(usize, f32)> = None;
+
+        for c in GridRayIter::new(pos, dir, max_t, self.cell_size) {
+            for dy in -k..=k {
+                for dx in -k..=k {
+                    let cell = IVec2::new(c.x + dx, c.y + dy);
+                    let center = (cell.as_vec2() + 0.5) * self.cell_size;
+
+                    for j in self.cell(cell) {
+                        let p = &particles[j];
+                        let image = center + self.offset(center, p.position);
+
+                        if let Some(t) = ray_circle(pos, dir, image, p.radius)
+                            && t <= max_t
+                            && best.is_none_or(|(_, bt)| t < bt)
+                        {
+                            best = Some((j, t));
+                        }
+                    }
+                }
+            }
         }
+
+        best
     }
 
     pub fn cell_list<'a>(&'a self, p: &Particle) -> impl Iterator<Item = usize> + 'a {
+        // Only a wrapped grid can reach the same stored cell twice, in domains a few cells wide.
+        let wrapped = self.period.is_some();
+        let mut seen = HashSet::new();
+
+        self.cell_list_cells(p)
+            .flat_map(|c| self.cell(c))
+            .filter(move |&j| !wrapped || seen.insert(j))
+    }
+
+    /// Cells visited by `cell_list`: the particle's own and the eight around it.
+    pub fn cell_list_cells(&self, p: &Particle) -> impl Iterator<Item = IVec2> + use<> {
         let base = self.cell_coord(p.position);
 
-        Self::DIRS.into_iter().flat_map(move |d| {
-            let c = base + d;
-            self.cells.get(&c).into_iter().flatten().copied()
-        })
+        Self::DIRS.into_iter().map(move |d| base + d)
+    }
+
+    /// Particles in cell `c`, in the order they were inserted by the last `rebuild`. Past the
+    /// edge of a periodic domain, these are the particles of every stored cell it overlaps.
+    pub fn cell(&self, c: IVec2) -> impl Iterator<Item = usize> + '_ {
+        self.keys(c).flat_map(|k| self.cells.get(k))
+    }
+
+    /// Stored cells `cell(c)` reads: `c` itself, or the ones it overlaps once wrapped.
+    pub fn keys(&self, c: IVec2) -> impl Iterator<Item = IVec2> + use<> {
+        let inside = match self.period {
+            Some(period) => {
+                let (kmin, kmax) = self.key_range(period);
+
+                // Cells between the edge ones lie wholly inside the domain.
+                c.cmpgt(kmin).all() && c.cmplt(kmax).all()
+            }
+            None => true,
+        };
+        let (xs, ys) = match inside {
+            true => ([c.x..=c.x, EMPTY], [c.y..=c.y, EMPTY]),
+            false => {
+                let lo = c.as_vec2() * self.cell_size;
+
+                self.rect_spans(lo, lo + self.cell_size)
+            }
+        };
+
+        product(xs, ys)
+    }
+
+    /// Cell a particle at `pos` is filed under, after wrapping it into a periodic domain.
+    pub fn home_cell(&self, pos: Vec2) -> IVec2 {
+        match self.period {
+            Some(period) => {
+                let half = 0.5 * period;
+                let (kmin, kmax) = self.key_range(period);
+
+                self.cell_coord((pos + half).rem_euclid(period) - half)
+                    .clamp(kmin, kmax)
+            }
+            None => self.cell_coord(pos),
+        }
+    }
+
+    /// Stored cells covering the rectangle `lo..=hi`, continuing across the seams of a periodic
+    /// domain.
+    fn rect_keys(&self, lo: Vec2, hi: Vec2) -> impl Iterator<Item = IVec2> + use<> {
+        let (xs, ys) = self.rect_spans(lo, hi);
+
+        product(xs, ys)
+    }
+
+    /// Columns and rows of `rect_keys`.
+    fn rect_spans(&self, lo: Vec2, hi: Vec2) -> (Span, Span) {
+        match self.period {
+            Some(period) => {
+                let (kmin, kmax) = self.key_range(period);
+                let key = |x: f32, k: usize| {
+                    ((x / self.cell_size).floor() as i32).clamp(kmin[k], kmax[k])
+                };
+                let span =
+                    |k: usize| wrap_span(lo[k], hi[k], period[k], kmin[k]..=kmax[k], |x| key(x, k));
+
+                (span(0), span(1))
+            }
+            None => {
+                let (cmin, cmax) = (self.cell_coord(lo), self.cell_coord(hi));
+
+                ([cmin.x..=cmax.x, EMPTY], [cmin.y..=cmax.y, EMPTY])
+            }
+        }
+    }
+
+    /// First and last stored cells of a periodic domain of `period`, centered on the origin.
+    fn key_range(&self, period: Vec2) -> (IVec2, IVec2) {
+        let half = 0.5 * period / self.cell_size;
+
+        (
+            (-half).floor().as_ivec2(),
+            half.ceil().as_ivec2() - IVec2::ONE,
+        )
+    }
+
+    /// Displacement from `from` to `to`, through the nearest seam of a periodic domain.
+    fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        match self.period {
+            Some(period) => boundary::minimum_image(to - from, period),
+            None => to - from,
+        }
     }
 
     pub fn candidates_along_sweep_with_radius<'a>(
@@ -54,70 +237,76 @@ impl SpatialGrid {
         i: usize,
         dt: f32,
     ) -> impl Iterator<Item = usize> + 'a {
-        use glam::{IVec2, Vec2};
-        use std::collections::{HashSet, VecDeque};
-
-        let p = &particles[i];
-        let dir: Vec2 = p.velocity;
-        let ray = GridRayIter::new(p.position, dir, dt, self.cell_size);
+        use glam::IVec2;
+        use std::collections::VecDeque;
 
-        let kf = ((p.radius + self.r_max) / self.cell_size).ceil().max(1.0);
-        let k = kf as i32;
+        let cells = self.cells_along_sweep_with_radius(&particles[i], dt);
 
         let mut seen = HashSet::<usize>::new();
         let mut out = VecDeque::<usize>::new();
 
         let push_cell = |c: IVec2, out: &mut VecDeque<usize>, seen: &mut HashSet<usize>| {
-            if let Some(list) = self.cells.get(&c) {
-                for &j in list {
-                    if j != i && seen.insert(j) {
-                        out.push_back(j);
-                    }
+            for j in self.cell(c) {
+                if j != i && seen.insert(j) {
+                    out.push_back(j);
                 }
             }
         };
 
-        for c in ray {
-            for dy in -k..=k {
-                for dx in -k..=k {
-                    push_cell(IVec2::new(c.x + dx, c.y + dy), &mut out, &mut seen);
-                }
-            }
+        for c in cells {
+            push_cell(c, &mut out, &mut seen);
         }
 
         out.into_iter()
     }
 
+    /// Cells visited by `candidates_along_sweep_with_radius`, in traversal order and possibly
+    /// repeated.
+    pub fn cells_along_sweep_with_radius(
+        &self,
+        p: &Particle,
+        dt: f32,
+    ) -> impl Iterator<Item = IVec2> + use<> {
+        let ray = GridRayIter::new(p.position, p.velocity, dt, self.cell_size);
+
+        let kf = ((p.radius + self.sweep_padding) / self.cell_size)
+            .ceil()
+            .max(1.0);
+        let k = kf as i32;
+
+        ray.flat_map(move |c| {
+            (-k..=k).flat_map(move |dy| (-k..=k).map(move |dx| IVec2::new(c.x + dx, c.y + dy)))
+        })
+    }
+
+    /// Occupied cells and how many particles each one holds.
+    pub fn occupancy(&self) -> impl Iterator<Item = (IVec2, usize)> + '_ {
+        self.cells.iter()
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
     pub fn candidates_swept_aabb<'a>(
         &'a self,
         particles: &'a [Particle],
         i: usize,
         dt: f32,
     ) -> impl Iterator<Item = usize> + 'a {
-        use glam::{IVec2, Vec2};
-        use std::collections::{HashSet, VecDeque};
+        use glam::IVec2;
+        use std::collections::VecDeque;
 
-        let p = &particles[i];
-        let p1 = p.position;
-        let p2 = p.position + p.velocity * dt;
-
-        let r = p.radius + self.r_max;
-        let mins = p1.min(p2) - Vec2::splat(r);
-        let maxs = p1.max(p2) + Vec2::splat(r);
-
-        let cmin = self.cell_coord(mins);
-        let cmax = self.cell_coord(maxs);
+        let (cmin, cmax) = self.swept_aabb_cells(&particles[i], dt);
 
         let mut seen = HashSet::new();
         let mut out = VecDeque::new();
 
         for cy in cmin.y..=cmax.y {
             for cx in cmin.x..=cmax.x {
-                if let Some(list) = self.cells.get(&IVec2::new(cx, cy)) {
-                    for &j in list {
-                        if j != i && seen.insert(j) {
-                            out.push_back(j);
-                        }
+                for j in self.cell(IVec2::new(cx, cy)) {
+                    if j != i && seen.insert(j) {
+                        out.push_back(j);
                     }
                 }
             }
@@ -126,8 +315,20 @@ impl SpatialGrid {
         out.into_iter()
     }
 
+    /// Corner cells of the rectangle searched by `candidates_swept_aabb`, inclusive.
+    pub fn swept_aabb_cells(&self, p: &Particle, dt: f32) -> (IVec2, IVec2) {
+        let p1 = p.position;
+        let p2 = p.position + p.velocity * dt;
+
+        let r = p.radius + self.sweep_padding;
+        let mins = p1.min(p2) - Vec2::splat(r);
+        let maxs = p1.max(p2) + Vec2::splat(r);
+
+        (self.cell_coord(mins), self.cell_coord(maxs))
+    }
+
     #[inline]
-    fn cell_coord(&self, pos: Vec2) -> IVec2 {
+    pub fn cell_coord(&self, pos: Vec2) -> IVec2 {
         IVec2::new(
             (pos.x / self.cell_size).floor() as i32,
             (pos.y / self.cell_size).floor() as i32,
@@ -135,6 +336,203 @@ impl SpatialGrid {
     }
 }
 
+/// Cells along one axis, as up to two ranges for the parts on either side of a seam.
+type Span = [RangeInclusive<i32>; 2];
+
+/// A range of no cells, for axes that do not cross a seam.
+const EMPTY: RangeInclusive<i32> = RangeInclusive::new(1, 0);
+
+/// Cells along one axis covering `lo..=hi` in a domain repeating every `period`, as the part
+/// before the seam and the part after it. `key` files a coordinate inside the domain under its
+/// cell, and `all` spans the whole domain.
+fn wrap_span(
+    lo: f32,
+    hi: f32,
+    period: f32,
+    all: RangeInclusive<i32>,
+    key: impl Fn(f32) -> i32,
+) -> Span {
+    if hi - lo >= period {
+        return [all, EMPTY];
+    }
+
+    let half = 0.5 * period;
+    let shift = ((lo + half) / period).floor() * period;
+    let (lo, hi) = (lo - shift, hi - shift);
+
+    if hi < half {
+        return [key(lo)..=key(hi), EMPTY];
+    }
+
+    let (first, last) = (key(lo), key(hi - period));
+
+    match last >= first {
+        true => [all, EMPTY],
+        false => [first..=*all.end(), *all.start()..=last],
+    }
+}
+
+/// Cells in the ranges `xs` by the ranges `ys`, row by row.
+fn product(xs: Span, ys: Span) -> impl Iterator<Item = IVec2> {
+    ys.into_iter().flatten().flat_map(move |y| {
+        xs.clone()
+            .into_iter()
+            .flatten()
+            .map(move |x| IVec2::new(x, y))
+    })
+}
+
+/// Particles stored inline per cell before spilling into a separate list.
+const INLINE: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    cell: IVec2,
+    /// Particles in the cell; zero marks an empty slot.
+    len: u32,
+    inline: [u32; INLINE],
+    /// Index into `CellTable::spill` of the particles past the inline ones.
+    spill: u32,
+}
+
+/// Flat open-addressing map from cell coordinates to the particles in them, for domains of any
+/// size. The table is a power of two at most half full, indexed by fibonacci hashing with linear
+/// probing; most cells hold only a few particles, so those live in the slot itself. Nothing is
+/// freed between rebuilds.
+#[derive(Default)]
+struct CellTable {
+    slots: Vec<Slot>,
+    shift: u32,
+    /// Slots in use, so a reset only touches those.
+    filled: Vec<u32>,
+    spill: Vec<Vec<u32>>,
+    spill_used: usize,
+}
+
+impl CellTable {
+    /// Empties the table and sizes it for up to `particles` occupied cells.
+    fn reset(&mut self, particles: usize) {
+        let capacity = (particles * 2).next_power_of_two().max(16);
+
+        if capacity > self.slots.len() {
+            self.slots = vec![Slot::default(); capacity];
+            self.shift = 64 - capacity.trailing_zeros();
+        } else {
+            for &s in &self.filled {
+                self.slots[s as usize].len = 0;
+            }
+        }
+
+        self.filled.clear();
+
+        for list in &mut self.spill[..self.spill_used] {
+            list.clear();
+        }
+
+        self.spill_used = 0;
+    }
+
+    #[inline]
+    fn hash(&self, cell: IVec2) -> usize {
+        let key = ((cell.x as u32 as u64) << 32) | cell.y as u32 as u64;
+
+        (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> self.shift) as usize
+    }
+
+    /// Slot holding `cell`, or the empty slot where it would go.
+    #[inline]
+    fn find(&self, cell: IVec2) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut s = self.hash(cell);
+
+        while self.slots[s].len != 0 && self.slots[s].cell != cell {
+            s = (s + 1) & mask;
+        }
+
+        s
+    }
+
+    /// Adds particle `i` to `cell`. The table must have been reset for at least as many
+    /// particles as are inserted.
+    fn insert(&mut self, cell: IVec2, i: usize) {
+        let s = self.find(cell);
+        let slot = &mut self.slots[s];
+        let n = slot.len as usize;
+
+        if n == 0 {
+            slot.cell = cell;
+            self.filled.push(s as u32);
+        }
+
+        if n < INLINE {
+            slot.inline[n] = i as u32;
+        } else {
+            if n == INLINE {
+                if self.spill_used == self.spill.len() {
+                    self.spill.push(Vec::new());
+                }
+
+                slot.spill = self.spill_used as u32;
+                self.spill_used += 1;
+            }
+
+            self.spill[slot.spill as usize].push(i as u32);
+        }
+
+        slot.len += 1;
+    }
+
+    /// Particles in `cell`, in insertion order.
+    fn get(&self, cell: IVec2) -> impl Iterator<Item = usize> + '_ {
+        let slot = match self.slots.is_empty() {
+            true => None,
+            false => Some(&self.slots[self.find(cell)]),
+        };
+
+        slot.into_iter().flat_map(|slot| self.particles(slot))
+    }
+
+    fn particles<'a>(&'a self, slot: &'a Slot) -> impl Iterator<Item = usize> + 'a {
+        let n = slot.len as usize;
+        let spilled = match n > INLINE {
+            true => &self.spill[slot.spill as usize][..],
+            false => &[],
+        };
+
+        slot.inline[..n.min(INLINE)]
+            .iter()
+            .chain(spilled)
+            .map(|&i| i as usize)
+    }
+
+    /// Occupied cells and how many particles each one holds.
+    fn iter(&self) -> impl Iterator<Item = (IVec2, usize)> + '_ {
+        self.filled.iter().map(|&s| {
+            let slot = &self.slots[s as usize];
+            (slot.cell, slot.len as usize)
+        })
+    }
+}
+
+/// Distance along the unit `dir` at which the ray enters `p`, if it does.
+fn ray_circle(origin: Vec2, dir: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let oc = center - origin;
+    let c = oc.length_squared() - radius * radius;
+
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+
+    let b = dir.dot(oc);
+    let disc = b * b - c;
+
+    if b < 0.0 || disc < 0.0 {
+        return None;
+    }
+
+    Some(b - disc.sqrt())
+}
+
 struct GridRayIter {
     cur: IVec2,
     step: IVec2,
@@ -254,3 +652,154 @@ impl Iterator for GridRayIter {
         Some(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn particle(x: f32, y: f32, radius: f32) -> Particle {
+        Particle::new(Vec2::new(x, y), Vec2::ZERO, radius, 1.0, [1.0; 3])
+    }
+
+    #[test]
+    fn neighbors_within_filters_by_center_distance() {
+        let particles = [
+            particle(0.0, 0.0, 1.0),
+            particle(15.0, 0.0, 1.0),
+            particle(-25.0, 30.0, 1.0),
+        ];
+        let mut grid = SpatialGrid::new(10.0);
+        grid.rebuild(&particles);
+
+        let mut found = grid
+            .neighbors_within(&particles, Vec2::new(5.0, 0.0), 11.0)
+            .collect::<Vec<_>>();
+        found.sort();
+
+        assert_eq!(found, [0, 1]);
+    }
+
+    #[test]
+    fn periodic_queries_reach_across_the_seam() {
+        // 95 across in cells of 10, so the edge cells are only partly inside.
+        let mut particles = [
+            particle(46.0, 0.0, 1.0),
+            particle(-46.0, 0.0, 1.0),
+            particle(0.0, 0.0, 1.0),
+        ];
+        let mut grid = SpatialGrid::new(10.0);
+
+        particles[0].velocity = Vec2::new(10.0, 0.0);
+        grid.rebuild(&particles);
+
+        assert_eq!(
+            grid.neighbors_within(&particles, particles[0].position, 5.0)
+                .count(),
+            1
+        );
+
+        grid.set_period(Some(Vec2::splat(95.0)));
+        grid.rebuild(&particles);
+
+        let mut found = grid
+            .neighbors_within(&particles, particles[0].position, 5.0)
+            .collect::<Vec<_>>();
+        found.sort();
+
+        assert_eq!(found, [0, 1]);
+        assert!(grid.cell_list(&particles[0]).any(|j| j == 1));
+        assert!(
+            grid.candidates_along_sweep_with_radius(&particles, 0, 1.0)
+                .any(|j| j == 1)
+        );
+        assert!(
+            grid.candidates_swept_aabb(&particles, 0, 1.0)
+                .any(|j| j == 1)
+        );
+        assert_eq!(
+            grid.home_cell(Vec2::new(50.0, 0.0)),
+            grid.home_cell(Vec2::new(-45.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn cell_table_matches_hash_map() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+        use std::collections::HashMap;
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut table = CellTable::default();
+
+        // Reuses the table at shrinking sizes, with crowded cells that spill.
+        for n in [500, 40, 500, 3] {
+            let cells = (0..n)
+                .map(|_| IVec2::new(rng.random_range(-6..6), rng.random_range(-1000..1000) / 200))
+                .collect::<Vec<_>>();
+            let mut expected = HashMap::<IVec2, Vec<usize>>::new();
+
+            table.reset(n);
+
+            for (i, &c) in cells.iter().enumerate() {
+                table.insert(c, i);
+                expected.entry(c).or_default().push(i);
+            }
+
+            assert_eq!(table.iter().count(), expected.len());
+
+            for (c, list) in &expected {
+                assert_eq!(table.get(*c).collect::<Vec<_>>(), *list);
+            }
+
+            assert_eq!(table.get(IVec2::new(100, 100)).count(), 0);
+        }
+    }
+
+    #[test]
+    fn raycast_returns_nearest_hit() {
+        let particles = [
+            particle(50.0, 0.0, 5.0),
+            particle(20.0, 3.0, 5.0),
+            particle(0.0, 40.0, 5.0),
+        ];
+        let mut grid = SpatialGrid::new(10.0);
+        grid.rebuild(&particles);
+
+        let (i, t) = grid
+            .raycast(&particles, Vec2::ZERO, Vec2::X, 100.0)
+            .unwrap();
+
+        assert_eq!(i, 1);
+        assert!((t - 16.0).abs() < 1e-4);
+        assert!(
+            grid.raycast(&particles, Vec2::ZERO, Vec2::X, 10.0)
+                .is_none()
+        );
+        assert!(
+            grid.raycast(&particles, Vec2::ZERO, -Vec2::X, 100.0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn periodic_raycast_crosses_the_seam() {
+        let particles = [particle(-45.0, 0.0, 3.0), particle(0.0, 30.0, 3.0)];
+        let mut grid = SpatialGrid::new(10.0);
+
+        grid.set_period(Some(Vec2::splat(100.0)));
+        grid.rebuild(&particles);
+
+        let (i, t) = grid
+            .raycast(&particles, Vec2::new(40.0, 0.0), Vec2::X, 30.0)
+            .unwrap();
+
+        assert_eq!(i, 0);
+        assert!((t - 12.0).abs() < 1e-4, "{t}");
+
+        let (i, t) = grid
+            .raycast(&particles, Vec2::new(0.0, -40.0), -Vec2::Y, 50.0)
+            .unwrap();
+
+        assert_eq!(i, 1);
+        assert!((t - 27.0).abs() < 1e-4, "{t}");
+    }
+}
@@ -0,0 +1,95 @@
+//! The standard setup of the offline experiment binaries: a 1280x720 box with 20-unit cells and
+//! uniformly spawned particles, so their results stay comparable with each other.
+
+use engine::{Bounds, particle::Particle};
+use glam::Vec2;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    miscs::{BoundaryType, DetectionType},
+    solver::Solver,
+};
+
+pub const BOUNDS: Bounds = Bounds {
+    width: 1280.0,
+    height: 720.0,
+};
+pub const CELL_SIZE: f32 = 20.0;
+pub const POLYGON_SIDES: usize = 6;
+const MAX_SPAWN_ATTEMPTS: usize = 100;
+
+/// A box solver without recording.
+pub fn new_solver(method: DetectionType, particle_count: usize) -> Solver {
+    Solver::new(
+        CELL_SIZE,
+        None,
+        method,
+        BoundaryType::Box,
+        POLYGON_SIDES,
+        particle_count as u64,
+    )
+}
+
+/// `count` particles anywhere in the inner 90% of [`BOUNDS`], moving at up to `speed` per axis.
+/// They may start overlapping.
+pub fn spawn(count: usize, speed: f32, seed: u64) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..count)
+        .map(|_| {
+            let radius = rng.random_range(3.0..7.0);
+            let position = position(&mut rng);
+
+            particle(&mut rng, position, radius, speed)
+        })
+        .collect()
+}
+
+/// Like [`spawn`], but retries a particle's position until it overlaps nothing spawned before
+/// it, giving up after a few attempts.
+pub fn spawn_apart(count: usize, speed: f32, seed: u64) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut particles: Vec<Particle> = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let radius = rng.random_range(3.0..7.0);
+        let mut position = Vec2::ZERO;
+
+        for _ in 0..MAX_SPAWN_ATTEMPTS {
+            position = self::position(&mut rng);
+
+            if particles
+                .iter()
+                .all(|p| p.position.distance(position) > p.radius + radius)
+            {
+                break;
+            }
+        }
+
+        particles.push(particle(&mut rng, position, radius, speed));
+    }
+
+    particles
+}
+
+fn position(rng: &mut StdRng) -> Vec2 {
+    let (hw, hh) = BOUNDS.half_extents();
+
+    Vec2::new(
+        rng.random_range(-0.9 * hw..0.9 * hw),
+        rng.random_range(-0.9 * hh..0.9 * hh),
+    )
+}
+
+fn particle(rng: &mut StdRng, position: Vec2, radius: f32, speed: f32) -> Particle {
+    Particle::new(
+        position,
+        Vec2::new(
+            rng.random_range(-speed..speed),
+            rng.random_range(-speed..speed),
+        ),
+        radius,
+        std::f32::consts::PI * radius * radius,
+        [1.0; 3],
+    )
+}